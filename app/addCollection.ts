@@ -10,7 +10,7 @@ import {
 import { AccountMeta, Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
-import { createAtaIfNeeded } from "./utils";
+import { createAtaIfNeeded, eventCpiAccounts } from "./utils";
 const MPL_TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
 /**
@@ -37,10 +37,11 @@ export async function addCollection(
   console.log("\nSending transaction to initialize program...");
   try {
     const tx = await (program.methods
-      .addCollection(collection) as any)
+      .addCollection(collection, { metaplex: {} }) as any)
       .accounts({
         pool: poolPDA,
         admin: admin.publicKey,
+        ...eventCpiAccounts(program),
       })
       .signers([admin])
       .rpc();
@@ -2,7 +2,6 @@
 
 import { Program, web3, AnchorProvider, BN } from "@coral-xyz/anchor";
 import {
-  TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
   createMint,
@@ -10,7 +9,7 @@ import {
 import { AccountMeta, Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
-import { createAtaIfNeeded } from "./utils";
+import { createAtaIfNeeded, eventCpiAccounts, tokenProgramOf } from "./utils";
 const MPL_TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
 /**
@@ -43,7 +42,13 @@ export async function addReward(
 
   const [rewardVaultPDA] = PublicKey.findProgramAddressSync([Buffer.from("reward_vault")], program.programId);
   console.log(`rewardVaultPDA: ${rewardVaultPDA}`)
-  const adminRewardTokenAccount = await getAssociatedTokenAddress(poolState.rewardMint, admin.publicKey);
+  const tokenProgram = await tokenProgramOf(provider.connection, poolState.rewardMint);
+  const adminRewardTokenAccount = await getAssociatedTokenAddress(
+    poolState.rewardMint,
+    admin.publicKey,
+    false,
+    tokenProgram
+  );
 
   try {
     const tx = await (program.methods
@@ -54,7 +59,9 @@ export async function addReward(
         rewardVault: rewardVaultPDA,
         rewardMint: poolState.rewardMint,
         adminRewardTokenAccount: adminRewardTokenAccount,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram,
+        systemProgram: SystemProgram.programId,
+        ...eventCpiAccounts(program),
       })
       .signers([admin])
       .rpc();
@@ -2,7 +2,6 @@
 
 import { Program, web3, AnchorProvider } from "@coral-xyz/anchor";
 import {
-  TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
   createMint,
@@ -10,7 +9,7 @@ import {
 import { AccountMeta, Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
-import { createAtaIfNeeded } from "./utils";
+import { createAtaIfNeeded, eventCpiAccounts, paysInSol, rewardStreamAccounts, solVaultOf, tokenProgramOf } from "./utils";
 const MPL_TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
 /**
@@ -45,29 +44,71 @@ export async function claimRewards(
     [Buffer.from("stake_entry"), userWallet.publicKey.toBuffer(), nftMintAddress.toBuffer()],
     program.programId
   );
-  const userRewardTokenAccount = await getAssociatedTokenAddress(poolState.rewardMint, userWallet.publicKey);
+  const stakeEntry = await program.account.nftStakeEntry.fetch(stakeEntryPDA);
+  const tokenProgram = await tokenProgramOf(provider.connection, poolState.rewardMint);
+  const userRewardTokenAccount = await getAssociatedTokenAddress(
+    poolState.rewardMint,
+    userWallet.publicKey,
+    false,
+    tokenProgram
+  );
+
+  // A reward delegate gets its share paid to its own token account, or its wallet when the pool pays in SOL
+  const rewardDelegate = stakeEntry.rewardDelegate.equals(PublicKey.default) ? undefined : stakeEntry.rewardDelegate;
+  const inSol = paysInSol(poolState);
+  let delegateRewardTokenAccount: PublicKey | null = null;
+  if (rewardDelegate && !inSol) {
+    delegateRewardTokenAccount = await getAssociatedTokenAddress(
+      poolState.rewardMint,
+      rewardDelegate,
+      true,
+      tokenProgram
+    );
+    await createAtaIfNeeded(
+      provider.connection,
+      userWallet,
+      delegateRewardTokenAccount,
+      rewardDelegate,
+      poolState.rewardMint,
+      tokenProgram
+    );
+  }
+  const remainingAccounts = await rewardStreamAccounts(program, poolState, {
+    payer: userWallet,
+    beneficiary: userWallet.publicKey,
+    rewardDelegate,
+  });
 
   console.log("\nSending transaction to initialize program...");
   try {
     const mintPubkeys = [mint];
+    // `null` claims everything pending, into the owner's own reward token account
     const tx = await (program.methods
-      .claimRewards() as any)
+      .claimReward(null) as any)
       .accounts({
+        authority: userWallet.publicKey,
         user: userWallet.publicKey,
         pool: poolPDA,
-        rewardVault: rewardVaultPDA,
-        rewardMint: poolState.rewardMint,
-        userRewardTokenAccount,
+        // SOL pools pay the wallets themselves and take none of the token accounts
+        rewardVault: inSol ? null : rewardVaultPDA,
+        solVault: solVaultOf(program, poolState),
+        rewardMint: inSol ? null : poolState.rewardMint,
+        userRewardTokenAccount: inSol ? null : userRewardTokenAccount,
+        recipientTokenAccount: null,
+        delegateRewardTokenAccount,
+        rewardDelegate: inSol && rewardDelegate ? rewardDelegate : null,
         stakeEntry: stakeEntryPDA,
         nftMint: nftMintAddress,
         systemProgram: SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        ...eventCpiAccounts(program),
       })
+      .remainingAccounts(remainingAccounts)
       .signers([userWallet])
       .rpc();
 
-    console.log(`✅ ${mintPubkeys.length} NFTs staked successfully!`);
+    console.log(`✅ Rewards claimed for ${mintPubkeys.length} NFTs!`);
     console.log(`Transaction signature: ${tx}`);
 
   } catch (error) {
@@ -1,8 +1,7 @@
 // file: appZ/initProgram.ts
 
-import { Program, web3, AnchorProvider } from "@coral-xyz/anchor";
+import { Program, web3, AnchorProvider, BN } from "@coral-xyz/anchor";
 import {
-  TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
   createMint,
@@ -10,6 +9,7 @@ import {
 import { Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
+import { eventCpiAccounts, tokenProgramOf } from "./utils";
 
 /**
  * Initializes the Solana program state.
@@ -58,21 +58,27 @@ export async function initProgram(
   // );
   // console.log(`Program's Token Account PDA: ${programTokenAccountPDA.toBase58()}`);
 
+  const tokenProgram = await tokenProgramOf(provider.connection, tokenAddress);
+
   console.log("\nSending transaction to initialize program...");
   
   try {
     // 4. Call the `initializeProgram` instruction from the smart contract
-    // Note: The on-chain program has `initialize_program(ctx: Context<InitializeProgram>)`
-    // which takes no extra arguments, so we call it with empty `()`.
+    // Rewards are paid from the pre-funded reward vault; the emission cap only applies to
+    // pools that mint their rewards (`{ mint: {} }`). Each day's rewards are split equally
+    // among the NFTs staked at its end.
     const txSignature = await (await program.methods
-      .initializePool as any)()
+      .initializePool as any)({ vault: {} }, new BN(0), { equalShare: {} })
       .accounts({
         admin: admin.publicKey,
         pool: programStatePDA,
         rewardMint: tokenAddress,
         rewardVault: rewardVaultPDA,
+        // Only used by pools paying their rewards in SOL
+        solVault: null,
         systemProgram: SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram,
+        ...eventCpiAccounts(program),
       })
       .signers([admin]) // The admin must sign to pay for account creation
       .rpc();
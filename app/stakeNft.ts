@@ -2,7 +2,6 @@
 
 import { Program, web3, AnchorProvider } from "@coral-xyz/anchor";
 import {
-  TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
   createMint,
@@ -10,7 +9,7 @@ import {
 import { AccountMeta, Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
-import { createAtaIfNeeded } from "./utils";
+import { createAtaIfNeeded, eventCpiAccounts, paysInSol, rewardStreamAccounts, solVaultOf, tokenProgramOf } from "./utils";
 const MPL_TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
 /**
@@ -52,7 +51,13 @@ export async function stakeNft(
     [Buffer.from("metadata"), MPL_TOKEN_METADATA_PROGRAM_ID.toBuffer(), nftMintAddress.toBuffer()],
     MPL_TOKEN_METADATA_PROGRAM_ID
   );
-  const userNftTokenAccount = await getAssociatedTokenAddress(nftMintAddress, userWallet.publicKey);
+  // SPL Token or Token-2022, whichever the NFT uses
+  const tokenProgram = await tokenProgramOf(provider.connection, nftMintAddress);
+  const userNftTokenAccount = await getAssociatedTokenAddress(nftMintAddress, userWallet.publicKey, false, tokenProgram);
+  const poolState = await program.account.pool.fetch(poolPDA);
+  const [rewardVaultPDA] = PublicKey.findProgramAddressSync([Buffer.from("reward_vault")], program.programId);
+  // The new entry starts its checkpoint in every active reward stream
+  const remainingAccounts = await rewardStreamAccounts(program, poolState);
   console.log("\nSending transaction to initialize program...");
   try {
     const mintPubkeys = [mint];
@@ -67,8 +72,13 @@ export async function stakeNft(
         userNftTokenAccount,
         nftVault: nftVaultPDA,
         systemProgram: SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram,
+        // Only read by fixed-rate pools, to check the vault covers the new position
+        rewardVault: paysInSol(poolState) ? null : rewardVaultPDA,
+        solVault: solVaultOf(program, poolState),
+        ...eventCpiAccounts(program),
       })
+      .remainingAccounts(remainingAccounts)
       .signers([userWallet])
       .rpc();
 
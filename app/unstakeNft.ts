@@ -2,7 +2,6 @@
 
 import { Program, web3, AnchorProvider } from "@coral-xyz/anchor";
 import {
-  TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
   createMint,
@@ -10,7 +9,16 @@ import {
 import { AccountMeta, Keypair, PublicKey, SystemProgram, SYSVAR_RENT_PUBKEY } from "@solana/web3.js";
 import { collection, tokenAddress } from "./Constants";
 import { NftStaking } from "../target/types/nft_staking";
-import { createAtaIfNeeded } from "./utils";
+import {
+  createAtaIfNeeded,
+  eventCpiAccounts,
+  paysInSol,
+  rewardIouOf,
+  rewardStreamAccounts,
+  solVaultOf,
+  streamRewardIouOf,
+  tokenProgramOf,
+} from "./utils";
 const MPL_TOKEN_METADATA_PROGRAM_ID = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 
 /**
@@ -47,7 +55,48 @@ export async function unstakeNft(
     [Buffer.from("nft_vault"), userWallet.publicKey.toBuffer(), nftMintAddress.toBuffer()],
     program.programId
   );
-  const userNftTokenAccount = await getAssociatedTokenAddress(nftMintAddress, userWallet.publicKey);
+  // The NFT and the reward mint can each be SPL Token or Token-2022
+  const tokenProgram = await tokenProgramOf(provider.connection, nftMintAddress);
+  const userNftTokenAccount = await getAssociatedTokenAddress(nftMintAddress, userWallet.publicKey, false, tokenProgram);
+
+  const poolState = await program.account.pool.fetch(poolPDA);
+  const stakeEntry = await program.account.nftStakeEntry.fetch(stakeEntryPDA);
+  const [rewardVaultPDA] = PublicKey.findProgramAddressSync([Buffer.from("reward_vault")], program.programId);
+  const rewardTokenProgram = await tokenProgramOf(provider.connection, poolState.rewardMint);
+  const userRewardTokenAccount = await getAssociatedTokenAddress(
+    poolState.rewardMint,
+    userWallet.publicKey,
+    false,
+    rewardTokenProgram
+  );
+
+  // A reward delegate gets its share paid (or owed) to its own accounts, or its wallet when the pool pays in SOL
+  const rewardDelegate = stakeEntry.rewardDelegate.equals(PublicKey.default) ? undefined : stakeEntry.rewardDelegate;
+  const inSol = paysInSol(poolState);
+  let delegateRewardTokenAccount: PublicKey | null = null;
+  if (rewardDelegate && !inSol) {
+    delegateRewardTokenAccount = await getAssociatedTokenAddress(
+      poolState.rewardMint,
+      rewardDelegate,
+      true,
+      rewardTokenProgram
+    );
+    await createAtaIfNeeded(
+      provider.connection,
+      userWallet,
+      delegateRewardTokenAccount,
+      rewardDelegate,
+      poolState.rewardMint,
+      rewardTokenProgram
+    );
+  }
+  // A delegate that is also the owner shares the owner's IOUs
+  const delegateOwed = rewardDelegate && !rewardDelegate.equals(userWallet.publicKey) ? rewardDelegate : undefined;
+  const remainingAccounts = await rewardStreamAccounts(program, poolState, {
+    payer: userWallet,
+    beneficiary: userWallet.publicKey,
+    rewardDelegate,
+  });
 
   console.log("\nSending transaction to initialize program...");
   try {
@@ -61,14 +110,29 @@ export async function unstakeNft(
         stakeEntry: stakeEntryPDA,
         nftVault: nftVaultPDA,
         userNftTokenAccount,
+        // SOL pools pay the wallets themselves and take none of the token accounts
+        rewardMint: inSol ? null : poolState.rewardMint,
+        userRewardTokenAccount: inSol ? null : userRewardTokenAccount,
+        rewardVault: inSol ? null : rewardVaultPDA,
+        solVault: solVaultOf(program, poolState),
+        delegateRewardTokenAccount,
+        rewardDelegate: inSol && rewardDelegate ? rewardDelegate : null,
+        // IOUs record whatever the vaults cannot pay right now; empty ones are closed again
+        rewardIou: rewardIouOf(program, userWallet.publicKey),
+        delegateRewardIou: delegateOwed ? rewardIouOf(program, delegateOwed) : null,
+        streamRewardIou: streamRewardIouOf(program, userWallet.publicKey),
+        delegateStreamRewardIou: delegateOwed ? streamRewardIouOf(program, delegateOwed) : null,
         systemProgram: SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram,
+        rewardTokenProgram,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        ...eventCpiAccounts(program),
       })
+      .remainingAccounts(remainingAccounts)
       .signers([userWallet])
       .rpc();
 
-    console.log(`✅ ${mintPubkeys.length} NFTs staked successfully!`);
+    console.log(`✅ ${mintPubkeys.length} NFTs unstaked successfully!`);
     console.log(`Transaction signature: ${tx}`);
 
   } catch (error) {
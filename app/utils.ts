@@ -1,12 +1,16 @@
 // app/utils.ts
-import {AccountInfo, clusterApiUrl, Connection, Keypair, PublicKey, sendAndConfirmTransaction, Transaction} from "@solana/web3.js";
+import {AccountInfo, AccountMeta, clusterApiUrl, Connection, Keypair, PublicKey, sendAndConfirmTransaction, Transaction} from "@solana/web3.js";
 import * as anchor from '@project-serum/anchor';
 import fs from "fs";
 import * as bs58 from 'bs58';
 import {AnchorProvider, Program, Provider, Wallet} from "@coral-xyz/anchor";
 import { deserialize } from "borsh"
 import { NftStaking } from "../target/types/nft_staking";
-import { createAssociatedTokenAccountInstruction } from "@solana/spl-token";
+import {
+    createAssociatedTokenAccountInstruction,
+    getAssociatedTokenAddressSync,
+    TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
 class NoteLog {
     leafNode: Uint8Array
     checker: PublicKey
@@ -81,7 +85,8 @@ export async function createAtaIfNeeded(
     payer: Keypair,
     ataAddress: PublicKey,
     owner: PublicKey,
-    mint: PublicKey
+    mint: PublicKey,
+    tokenProgram: PublicKey = TOKEN_PROGRAM_ID
 ): Promise<void> {
     const accountInfo: AccountInfo<Buffer> | null = await connection.getAccountInfo(ataAddress);
     if (!accountInfo) {
@@ -91,9 +96,94 @@ export async function createAtaIfNeeded(
                 payer.publicKey,
                 ataAddress,
                 owner,
-                mint
+                mint,
+                tokenProgram
             )
         );
         await sendAndConfirmTransaction(connection, tx, [payer]);
     }
-}
\ No newline at end of file
+}
+
+// The accounts `#[event_cpi]` adds to every instruction that emits events
+export function eventCpiAccounts(program: Program<NftStaking>) {
+    const [eventAuthority] = PublicKey.findProgramAddressSync(
+        [Buffer.from("__event_authority")],
+        program.programId
+    );
+    return { eventAuthority, program: program.programId };
+}
+
+// SPL Token or Token-2022, whichever owns the mint
+export async function tokenProgramOf(connection: Connection, mint: PublicKey): Promise<PublicKey> {
+    const accountInfo = await connection.getAccountInfo(mint);
+    if (!accountInfo) {
+        throw new Error(`Mint ${mint.toBase58()} not found`);
+    }
+    return accountInfo.owner;
+}
+
+// Pools paying in SOL pay wallets straight from the SOL vault, without a reward vault or token accounts
+export function paysInSol(pool: any): boolean {
+    return !!pool.rewardMode.sol;
+}
+
+// The SOL vault is only passed to pools that pay their rewards in SOL
+export function solVaultOf(program: Program<NftStaking>, pool: any): PublicKey | null {
+    if (!paysInSol(pool)) {
+        return null;
+    }
+    const [solVault] = PublicKey.findProgramAddressSync([Buffer.from("sol_vault")], program.programId);
+    return solVault;
+}
+
+export function rewardIouOf(program: Program<NftStaking>, owner: PublicKey): PublicKey {
+    const [rewardIou] = PublicKey.findProgramAddressSync(
+        [Buffer.from("reward_iou"), owner.toBuffer()],
+        program.programId
+    );
+    return rewardIou;
+}
+
+export function streamRewardIouOf(program: Program<NftStaking>, owner: PublicKey): PublicKey {
+    const [streamRewardIou] = PublicKey.findProgramAddressSync(
+        [Buffer.from("stream_reward_iou"), owner.toBuffer()],
+        program.programId
+    );
+    return streamRewardIou;
+}
+
+// Remaining accounts for the pool's active reward streams, in slot order. Staking only passes
+// the streams; payouts also pass each stream's vault, mint and token program and the
+// beneficiary's (and reward delegate's) token account, which `payer` creates if missing.
+export async function rewardStreamAccounts(
+    program: Program<NftStaking>,
+    pool: any,
+    payout?: { payer: Keypair; beneficiary: PublicKey; rewardDelegate?: PublicKey }
+): Promise<AccountMeta[]> {
+    const connection = program.provider.connection;
+    const accounts: AccountMeta[] = [];
+    for (const rewardStream of pool.rewardStreams as PublicKey[]) {
+        if (rewardStream.equals(PublicKey.default)) {
+            continue;
+        }
+        accounts.push({ pubkey: rewardStream, isSigner: false, isWritable: true });
+        if (!payout) {
+            continue;
+        }
+
+        const stream = await program.account.rewardStream.fetch(rewardStream);
+        const tokenProgram = await tokenProgramOf(connection, stream.rewardMint);
+        accounts.push(
+            { pubkey: stream.rewardVault, isSigner: false, isWritable: true },
+            { pubkey: stream.rewardMint, isSigner: false, isWritable: false },
+            { pubkey: tokenProgram, isSigner: false, isWritable: false }
+        );
+        const owners = payout.rewardDelegate ? [payout.beneficiary, payout.rewardDelegate] : [payout.beneficiary];
+        for (const owner of owners) {
+            const tokenAccount = getAssociatedTokenAddressSync(stream.rewardMint, owner, true, tokenProgram);
+            await createAtaIfNeeded(connection, payout.payer, tokenAccount, owner, stream.rewardMint, tokenProgram);
+            accounts.push({ pubkey: tokenAccount, isSigner: false, isWritable: true });
+        }
+    }
+    return accounts;
+}
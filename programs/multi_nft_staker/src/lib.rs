@@ -1,37 +1,154 @@
 // file: programs/nft_staking/src/lib.rs
 
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    entrypoint::MAX_PERMITTED_DATA_INCREASE, instruction::Instruction, program::invoke_signed,
+};
+use anchor_lang::system_program::{self, CreateAccount};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token_interface::{CloseAccount, Mint, TokenAccount, TokenInterface, Transfer},
+    associated_token::{self, AssociatedToken},
+    token_interface::{
+        self, spl_token_2022, Burn, CloseAccount, FreezeAccount, InitializeMint2, Mint, MintTo,
+        NonTransferableMintInitialize, ThawAccount, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 use mpl_token_metadata::accounts::Metadata;
+use spl_token_2022::extension::{
+    group_member_pointer::GroupMemberPointer, transfer_hook::TransferHook, BaseStateWithExtensions, ExtensionType,
+    StateWithExtensions,
+};
+use spl_token_group_interface::state::TokenGroupMember;
 
 // After your first successful `anchor build`, paste your new Program ID here.
 declare_id!("AEX1smJbH8pgMBL2Hpf6EJnuRaUwBt6NBYP7jVPixAeC");
 
+/// Denominator for every basis-point value stored on-chain.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Metaplex Core program.
+pub const MPL_CORE_ID: Pubkey = pubkey!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d");
+
+/// Runs `$body` for every collected `PayoutEvent`, with `$event` bound to the inner event. The
+/// body is written at the call site, where `emit_cpi!` finds the handler's `ctx`.
+macro_rules! for_each_payout_event {
+    ($events:expr, $event:ident => $body:expr) => {
+        for payout_event in $events {
+            match payout_event {
+                PayoutEvent::RewardClaimed($event) => $body,
+                PayoutEvent::RewardDeferred($event) => $body,
+                PayoutEvent::StreamRewardClaimed($event) => $body,
+                PayoutEvent::StreamRewardDeferred($event) => $body,
+            }
+        }
+    };
+}
+
 #[program]
 pub mod nft_staking {
     use super::*;
 
     // ... (previous admin instructions: initialize_pool, add_reward, add_collection, remove_collection) ...
 
-    pub fn initialize_pool(ctx: Context<InitializePool>) -> Result<()> {
+    /// Creates the pool. In `RewardMode::Mint` the pool PDA must already be the reward mint's
+    /// mint authority; rewards are then minted on claim, never beyond `emission_cap`. In
+    /// `RewardMode::Sol` the reward mint is the native mint and `sol_vault` is topped up to its
+    /// rent-exempt minimum, which payouts never touch. `reward_strategy` decides how rewards
+    /// are split among stakers and cannot be changed afterwards.
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        reward_mode: RewardMode,
+        emission_cap: u64,
+        reward_strategy: RewardStrategy,
+    ) -> Result<()> {
+        reward_strategy.validate()?;
+        if reward_mode == RewardMode::Mint {
+            require!(
+                ctx.accounts.reward_mint.mint_authority == Some(ctx.accounts.pool.key()).into(),
+                ErrorCode::MintAuthorityNotPool
+            );
+            require_gt!(emission_cap, 0, ErrorCode::ZeroRewardAmount);
+        }
+        if reward_mode == RewardMode::Sol {
+            require_keys_eq!(
+                ctx.accounts.reward_mint.key(),
+                anchor_spl::token::spl_token::native_mint::ID,
+                ErrorCode::WrongRewardMode
+            );
+            // Rewards are paid from the SOL vault alone
+            require!(ctx.accounts.reward_vault.is_none(), ErrorCode::WrongRewardMode);
+            let sol_vault = ctx.accounts.sol_vault.as_ref().ok_or(ErrorCode::MissingSolVault)?;
+            let shortfall = Rent::get()?
+                .minimum_balance(0)
+                .saturating_sub(sol_vault.lamports());
+            if shortfall > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.admin.to_account_info(),
+                            to: sol_vault.to_account_info(),
+                        },
+                    ),
+                    shortfall,
+                )?;
+            }
+        }
+
         let pool = &mut ctx.accounts.pool;
+        pool.version = Pool::VERSION;
         pool.admin = ctx.accounts.admin.key();
         pool.reward_mint = ctx.accounts.reward_mint.key();
-        pool.reward_vault = ctx.accounts.reward_vault.key();
+        pool.reward_vault = match reward_mode {
+            RewardMode::Vault | RewardMode::Mint => {
+                ctx.accounts.reward_vault.as_ref().ok_or(ErrorCode::MissingRewardVault)?.key()
+            }
+            RewardMode::Sol => Pubkey::default(),
+        };
+        pool.fixed_rate_terms = FixedRateTerms::default();
+        pool.reward_strategy = reward_strategy;
+        pool.total_shares = 0;
+        pool.accrued_until = Clock::get()?.unix_timestamp;
         pool.allowed_collections = Vec::new();
         pool.total_staked = 0;
-        pool.current_epoch = 0;
-        pool.last_update_time = Clock::get()?.unix_timestamp;
+        pool.total_funded = 0;
+        pool.total_distributed = 0;
+        pool.total_claimed = 0;
+        pool.total_admin_withdrawn = 0;
+        pool.stake_seconds = 0;
+        pool.stake_seconds_updated_at = Clock::get()?.unix_timestamp;
         pool.epoch_duration = 86400; // 24 hours
         pool.rewards_per_epoch = Vec::new();
         pool.bump = ctx.bumps.pool;
         pool.start_staking_timestamp = Clock::get()?.unix_timestamp;
-        pool.cumulative_reward_per_nft = 0; // Renamed
+        pool.cumulative_reward_per_nft = 0;
         pool.last_update_calc_reward_nft_index = 0; 
-        // Removed staked_counts, staked_counts_start_day, total_staked_at_window_start
+        pool.reward_streams = [Pubkey::default(); Pool::MAX_REWARD_STREAMS];
+        pool.next_reward_stream_id = 1;
+        pool.campaign_mints_restricted = false;
+        pool.reward_mode = reward_mode;
+        // The cap only applies to minted rewards
+        pool.emission_cap = match reward_mode {
+            RewardMode::Vault | RewardMode::Sol => 0,
+            RewardMode::Mint => emission_cap,
+        };
+        pool.total_scheduled = 0;
+        pool.total_minted = 0;
+        pool.sol_vault_bump = Pubkey::find_program_address(&[b"sol_vault"], &crate::ID).1;
+        pool.emission_schedule = None;
+        pool.outstanding_rewards = 0;
+        pool.utilization_curve = None;
+        pool.surplus_rewards = 0;
+
+        emit_cpi!(PoolInitialized {
+            pool: pool.key(),
+            admin: pool.admin,
+            reward_mint: pool.reward_mint,
+            reward_vault: pool.reward_vault,
+            reward_mode,
+            emission_cap: pool.emission_cap,
+            reward_strategy,
+            start_staking_timestamp: pool.start_staking_timestamp,
+        });
         Ok(())
     }
 
@@ -42,17 +159,10 @@ pub mod nft_staking {
     ) -> Result<()> {
         require_gt!(total_reward_amount, 0, ErrorCode::ZeroRewardAmount);
         require_gt!(num_epochs, 0, ErrorCode::ZeroEpochAmount);
+        require!(ctx.accounts.pool.reward_mode == RewardMode::Vault, ErrorCode::WrongRewardMode);
 
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.admin_reward_token_account.to_account_info(),
-            to: ctx.accounts.reward_vault.to_account_info(),
-            authority: ctx.accounts.admin.to_account_info(),
-        };
-        anchor_spl::token_interface::transfer(
-            CpiContext::new(cpi_program, cpi_accounts),
-            total_reward_amount,
-        )?;
+        // Book only what actually arrived; transfer-fee mints deliver less than was sent.
+        let total_reward_amount = fund_reward_vault(ctx.accounts, total_reward_amount)?;
 
         let pool = &mut ctx.accounts.pool;
         let reward_per_epoch = total_reward_amount
@@ -65,11 +175,15 @@ pub mod nft_staking {
         for _ in 0..num_epochs {
             pool.rewards_per_epoch.push(reward_per_epoch);
         }
+        pool.add_funding(total_reward_amount)?;
 
-        emit!(RewardAdded {
+        emit_cpi!(RewardAdded {
+            pool: pool.key(),
             funder: ctx.accounts.admin.key(),
             total_amount: total_reward_amount,
-            epochs_funded: num_epochs
+            epochs_funded: num_epochs,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
         });
         Ok(())
     }
@@ -80,578 +194,5912 @@ pub mod nft_staking {
         amount: u64,
     ) -> Result<()> {
         require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
-    
-        // Transfer reward token vào vault như bình thường
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.admin_reward_token_account.to_account_info(),
-            to: ctx.accounts.reward_vault.to_account_info(),
-            authority: ctx.accounts.admin.to_account_info(),
-        };
-        anchor_spl::token_interface::transfer(
-            CpiContext::new(cpi_program, cpi_accounts),
-            amount,
-        )?;
-    
+        require!(ctx.accounts.pool.reward_mode == RewardMode::Vault, ErrorCode::WrongRewardMode);
+
+        // Transfer the reward tokens into the vault as usual
+        let amount = fund_reward_vault(ctx.accounts, amount)?;
+
         let pool = &mut ctx.accounts.pool;
         let current_day = get_current_day(pool)?;
-    
-        // Nếu mảng chưa đủ dài tới current_day, mở rộng bằng 0
+
+        // Pad the schedule with zeros up to current_day if it is shorter
         if pool.rewards_per_epoch.len() <= current_day as usize {
             pool.rewards_per_epoch
                 .resize(current_day as usize + 1, 0);
         }
-    
-        // Cộng dồn reward cho ngày hiện tại
+
+        // Add to today's reward
         pool.rewards_per_epoch[current_day as usize] = pool.rewards_per_epoch[current_day as usize]
             .checked_add(amount)
             .ok_or(ErrorCode::RewardCalculationError)?;
-    
-        emit!(RewardAdded {
+        pool.add_funding(amount)?;
+
+        emit_cpi!(RewardAdded {
+            pool: pool.key(),
             funder: ctx.accounts.admin.key(),
             total_amount: amount,
             epochs_funded: 1,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
         });
-    
-        Ok(())
-    }
-    
 
-    pub fn add_collection(ctx: Context<ManageCollection>, collection_mint: Pubkey) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        // only admin
-        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
-        require!(
-            !pool.allowed_collections.contains(&collection_mint),
-            ErrorCode::CollectionAlreadyAllowed
-        );
-        require!(
-            pool.allowed_collections.len() < Pool::MAX_COLLECTIONS,
-            ErrorCode::MaxCollectionsExceeded
-        ); // Added max collection check
-        pool.allowed_collections.push(collection_mint);
         Ok(())
     }
 
-    pub fn remove_collection(
-        ctx: Context<ManageCollection>,
-        collection_mint: Pubkey,
+
+    /// Deposits `total_lamports` into the SOL vault, spread evenly over `num_epochs` days like
+    /// `add_reward`. Only for `RewardMode::Sol` pools.
+    pub fn add_sol_reward(
+        ctx: Context<AddSolReward>,
+        total_lamports: u64,
+        num_epochs: u64,
     ) -> Result<()> {
+        require_gt!(total_lamports, 0, ErrorCode::ZeroRewardAmount);
+        require_gt!(num_epochs, 0, ErrorCode::ZeroEpochAmount);
+        require!(ctx.accounts.pool.reward_mode == RewardMode::Sol, ErrorCode::WrongRewardMode);
+
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.admin.to_account_info(),
+                    to: ctx.accounts.sol_vault.to_account_info(),
+                },
+            ),
+            total_lamports,
+        )?;
+
         let pool = &mut ctx.accounts.pool;
-        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
-        let initial_len = pool.allowed_collections.len();
-        pool.allowed_collections
-            .retain(|&mint| mint != collection_mint);
+        let reward_per_epoch = total_lamports
+            .checked_div(num_epochs)
+            .ok_or(ErrorCode::ZeroEpochAmount)?;
         require!(
-            pool.allowed_collections.len() < initial_len,
-            ErrorCode::CollectionNotAllowed // Return error if collection wasn't found
+            pool.rewards_per_epoch.len() + (num_epochs as usize) <= Pool::MAX_EPOCHS,
+            ErrorCode::MaxEpochsExceeded
         );
+        for _ in 0..num_epochs {
+            pool.rewards_per_epoch.push(reward_per_epoch);
+        }
+        pool.add_funding(total_lamports)?;
+
+        emit_cpi!(RewardAdded {
+            pool: pool.key(),
+            funder: ctx.accounts.admin.key(),
+            total_amount: total_lamports,
+            epochs_funded: num_epochs,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
         Ok(())
     }
 
-    // --- USER INSTRUCTIONS ---
-
-    pub fn stake(ctx: Context<Stake>) -> Result<()> {
-        let nft_metadata_account_info = &ctx.accounts.nft_metadata_account.to_account_info();
-        let nft_metadata =
-            Metadata::safe_deserialize(&nft_metadata_account_info.try_borrow_data()?)?;
-
-        let collection = nft_metadata
-            .collection
-            .ok_or(ErrorCode::NotPartOfCollection)?;
-        require!(collection.verified, ErrorCode::CollectionNotVerified);
+    /// Schedules `total_reward_amount` of minted rewards spread evenly over the next
+    /// `num_epochs` unscheduled days. Only for `RewardMode::Mint` pools; everything ever
+    /// scheduled must fit under the emission cap.
+    pub fn schedule_emission(
+        ctx: Context<ScheduleEmission>,
+        total_reward_amount: u64,
+        num_epochs: u64,
+    ) -> Result<()> {
+        require_gt!(total_reward_amount, 0, ErrorCode::ZeroRewardAmount);
+        require_gt!(num_epochs, 0, ErrorCode::ZeroEpochAmount);
 
         let pool = &mut ctx.accounts.pool;
+        require!(pool.reward_mode == RewardMode::Mint, ErrorCode::WrongRewardMode);
+        let reward_per_epoch = total_reward_amount
+            .checked_div(num_epochs)
+            .ok_or(ErrorCode::ZeroEpochAmount)?;
+        // Only what actually lands in the schedule counts against the cap
+        let scheduled = reward_per_epoch
+            .checked_mul(num_epochs)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        pool.total_scheduled = pool
+            .total_scheduled
+            .checked_add(scheduled)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        require_gte!(pool.emission_cap, pool.total_scheduled, ErrorCode::EmissionCapExceeded);
+
+        let current_day = get_current_day(pool)? as usize;
+        let start_day = pool.rewards_per_epoch.len().max(current_day);
         require!(
-            pool.allowed_collections.contains(&collection.key),
-            ErrorCode::CollectionNotAllowed
+            start_day + (num_epochs as usize) <= Pool::MAX_EPOCHS,
+            ErrorCode::MaxEpochsExceeded
         );
+        // Minted rewards cannot be backdated, so days that passed unscheduled stay at zero
+        pool.rewards_per_epoch.resize(start_day, 0);
+        for _ in 0..num_epochs {
+            pool.rewards_per_epoch.push(reward_per_epoch);
+        }
+        pool.add_funding(scheduled)?;
 
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_nft_token_account.to_account_info(),
-            to: ctx.accounts.nft_vault.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        anchor_spl::token_interface::transfer(CpiContext::new(cpi_program, cpi_accounts), 1)?;
-        
-        // Ensure cumulative_reward_per_nft is updated before recording it for the stake entry
-        update_skipped_reward(pool)?; 
-
-        let stake_entry = &mut ctx.accounts.stake_entry;
-        stake_entry.user = ctx.accounts.user.key();
-        stake_entry.nft_mint = ctx.accounts.nft_mint.key();
-        stake_entry.staked_at = Clock::get()?.unix_timestamp;
-        stake_entry.last_claimed_epoch = pool.current_epoch; // This field might be redundant with cumulative_reward
-        stake_entry.bump = ctx.bumps.stake_entry;
-        stake_entry.skipped_reward = pool.cumulative_reward_per_nft; // Record current global cumulative reward
-
-        pool.total_staked = pool.total_staked.checked_add(1).unwrap();
-        
-        // Removed staked_counts related logic from stake
-        
-        // Emit StakeEvent
-        emit!(StakeEvent {
-            user: ctx.accounts.user.key(),
-            nft_mint: ctx.accounts.nft_mint.key(),
-            staked_at: stake_entry.staked_at,
+        emit_cpi!(RewardAdded {
+            pool: pool.key(),
+            funder: ctx.accounts.admin.key(),
+            total_amount: scheduled,
+            epochs_funded: num_epochs,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
         });
-
         Ok(())
     }
 
-    pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
+    /// Replaces the pool's emission curve, which pays out on top of `rewards_per_epoch`, or
+    /// removes it with `None`. Everything accrued under the old curve up to today is kept. The
+    /// new curve may not start in the past, and the rewards already owed to stakers plus
+    /// everything still to come from the schedule and the curve must be covered by the vault
+    /// (or by what is left under the emission cap in `Mint` mode).
+    pub fn set_emission_curve(
+        ctx: Context<SetEmissionCurve>,
+        schedule: Option<EmissionSchedule>,
+    ) -> Result<()> {
+        let available = available_reward(
+            &ctx.accounts.pool,
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
         let pool = &mut ctx.accounts.pool;
-        let stake_entry = &mut ctx.accounts.stake_entry;
-
-        // Ensure the pool's cumulative_reward_per_nft is up-to-date before calculating rewards
         update_skipped_reward(pool)?;
+        let current_day = get_current_day(pool)?;
 
-        // Calculate the reward amount: current global cumulative reward - cumulative reward at stake time
-        let reward_amount = pool.cumulative_reward_per_nft.checked_sub(stake_entry.skipped_reward)
-            .ok_or(ErrorCode::RewardCalculationError)?;
-        
-        // Only transfer rewards if there are any
-        if reward_amount > 0 {
-            let pool_seeds = &[
-                b"pool".as_ref(),
-                &[pool.bump],
-            ];
-            let pool_signer = &[&pool_seeds[..]];
-
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.reward_vault.to_account_info(),
-                to: ctx.accounts.user_reward_token_account.to_account_info(),
-                authority: pool.to_account_info(),
-            };
-            anchor_spl::token_interface::transfer(
-                CpiContext::new_with_signer(cpi_program, cpi_accounts, pool_signer),
-                reward_amount,
-            )?;
-
-            // Update the stake entry's skipped_reward to the current pool's cumulative_reward_per_nft.
-            stake_entry.skipped_reward = pool.cumulative_reward_per_nft;
-
-            emit!(RewardClaimed {
-                user: ctx.accounts.user.key(),
-                nft_mint: stake_entry.nft_mint,
-                amount: reward_amount,
-            });
+        if let Some(schedule) = &schedule {
+            schedule.validate(current_day)?;
         }
+        let old_schedule = std::mem::replace(&mut pool.emission_schedule, schedule);
 
+        let committed = pool.committed_rewards(current_day)?;
+        require_gte!(available, committed, ErrorCode::EmissionsNotCovered);
 
-        let user_key = ctx.accounts.user.key();
-        let nft_mint_key = ctx.accounts.nft_mint.key();
-        let stake_entry_seeds = &[
-            b"stake_entry".as_ref(),
-            user_key.as_ref(),
-            nft_mint_key.as_ref(),
-            &[ctx.accounts.stake_entry.bump],
-        ];
-        let stake_entry_signer = &[&stake_entry_seeds[..]];
+        emit_cpi!(EmissionCurveSet {
+            pool: pool.key(),
+            old_schedule,
+            new_schedule: pool.emission_schedule.clone(),
+            committed_rewards: committed,
+            available_rewards: available,
+        });
+        Ok(())
+    }
 
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts_transfer = Transfer {
-            from: ctx.accounts.nft_vault.to_account_info(),
-            to: ctx.accounts.user_nft_token_account.to_account_info(),
-            authority: ctx.accounts.stake_entry.to_account_info(),
-        };
-        anchor_spl::token_interface::transfer(
-            CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts_transfer, stake_entry_signer),
-            1,
+    /// Opens an additional reward stream paying `stream_reward_mint` alongside the pool's own
+    /// reward, e.g. a partner token. Each stream has its own vault, schedule and accumulator;
+    /// it starts accruing from today and is funded with `fund_reward_stream`. Only
+    /// `RewardStrategy::EqualShare` pools take streams.
+    pub fn add_reward_stream(ctx: Context<AddRewardStream>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let reward_stream = &mut ctx.accounts.reward_stream;
+        open_reward_stream(
+            pool,
+            reward_stream,
+            ctx.accounts.stream_reward_mint.key(),
+            ctx.accounts.stream_vault.key(),
+            ctx.accounts.admin.key(),
+            ctx.bumps.reward_stream,
         )?;
 
-        anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
-            cpi_program,
-            CloseAccount {
-                account: ctx.accounts.nft_vault.to_account_info(),
-                destination: ctx.accounts.user.to_account_info(),
-                authority: ctx.accounts.stake_entry.to_account_info(),
-            },
-            stake_entry_signer,
-        ))?;
-
-        pool.total_staked = pool.total_staked.checked_sub(1).unwrap();
-        
-        // Removed staked_counts related logic from unstake
-        
-        // Emit UnstakeEvent
-        emit!(UnstakeEvent {
-            user: ctx.accounts.user.key(),
-            nft_mint: ctx.accounts.nft_mint.key(),
-            unstaked_at: Clock::get()?.unix_timestamp,
+        emit_cpi!(RewardStreamAdded {
+            pool: pool.key(),
+            reward_stream: reward_stream.key(),
+            index: reward_stream.index,
+            reward_mint: reward_stream.reward_mint,
         });
-
         Ok(())
     }
 
-    pub fn claim_reward(ctx: Context<ClaimReward>) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        let stake_entry = &mut ctx.accounts.stake_entry;
-
-        // Ensure the pool's cumulative_reward_per_nft is up-to-date before calculating rewards
-        update_skipped_reward(pool)?;
+    /// Funds a reward stream with `total_reward_amount` spread evenly over `num_epochs` days,
+    /// starting today or right after the days already funded.
+    pub fn fund_reward_stream(
+        ctx: Context<FundRewardStream>,
+        total_reward_amount: u64,
+        num_epochs: u64,
+    ) -> Result<()> {
+        require_gt!(total_reward_amount, 0, ErrorCode::ZeroRewardAmount);
+        require_gt!(num_epochs, 0, ErrorCode::ZeroEpochAmount);
 
-        // Calculate the reward amount: current global cumulative reward - cumulative reward at stake time
-        let reward_amount = pool.cumulative_reward_per_nft.checked_sub(stake_entry.skipped_reward)
-            .ok_or(ErrorCode::RewardCalculationError)?; // Use a specific error for calculation issues
-        require_gt!(reward_amount, 0, ErrorCode::NoRewardsToClaim);
+        let accounts = &mut *ctx.accounts;
+        // Campaigns are funded once, up front, by their sponsor
+        require!(!accounts.reward_stream.is_campaign(), ErrorCode::NotOpenEndedStream);
+        let total_reward_amount = fund_vault(
+            accounts.admin_reward_token_account.to_account_info(),
+            &mut accounts.reward_vault,
+            &accounts.reward_mint,
+            accounts.admin.to_account_info(),
+            &accounts.token_program,
+            total_reward_amount,
+        )?;
 
-        // Transfer rewards from the pool's vault to the user
-        //let pool_key = pool.key();
-        let seeds = &[
-            b"pool".as_ref(),
-            &[pool.bump],
-        ];
-        let signer = &[&seeds[..]];
+        let current_day = get_current_day(&accounts.pool)? as usize;
+        let reward_stream = &mut accounts.reward_stream;
+        let reward_per_epoch = total_reward_amount
+            .checked_div(num_epochs)
+            .ok_or(ErrorCode::ZeroEpochAmount)?;
+        let start_day = reward_stream.rewards_per_epoch.len().max(current_day);
+        require!(
+            start_day + (num_epochs as usize) <= Pool::MAX_EPOCHS,
+            ErrorCode::MaxEpochsExceeded
+        );
+        // Days that passed unfunded stay at zero
+        reward_stream.rewards_per_epoch.resize(start_day, 0);
+        for _ in 0..num_epochs {
+            reward_stream.rewards_per_epoch.push(reward_per_epoch);
+        }
+        let previous_total_funded = reward_stream.total_funded;
+        reward_stream.total_funded = previous_total_funded
+            .checked_add(total_reward_amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
 
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.reward_vault.to_account_info(),
-            to: ctx.accounts.user_reward_token_account.to_account_info(),
-            authority: pool.to_account_info(),
+        let event = RewardStreamFunded {
+            pool: accounts.pool.key(),
+            reward_stream: reward_stream.key(),
+            funder: accounts.admin.key(),
+            total_amount: total_reward_amount,
+            epochs_funded: num_epochs,
+            previous_total_funded,
+            total_funded: reward_stream.total_funded,
         };
-        anchor_spl::token_interface::transfer(
-            CpiContext::new_with_signer(cpi_program, cpi_accounts, signer),
-            reward_amount,
-        )?;
+        emit_cpi!(event);
+        Ok(())
+    }
 
-        // Update the stake entry's skipped_reward to the current pool's cumulative_reward_per_nft.
-        // This ensures that future claims only account for new rewards accrued since this claim.
-        stake_entry.skipped_reward = pool.cumulative_reward_per_nft;
+    /// Restricts sponsored campaigns to the mints allowed with `allow_campaign_mint`, or opens
+    /// them to any mint again. Running campaigns are not affected.
+    pub fn set_campaign_mints_restricted(ctx: Context<SetCampaignMintsRestricted>, restricted: bool) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let previous_restricted = std::mem::replace(&mut pool.campaign_mints_restricted, restricted);
 
-        emit!(RewardClaimed {
-            user: ctx.accounts.user.key(),
-            nft_mint: stake_entry.nft_mint,
-            amount: reward_amount,
+        emit_cpi!(CampaignMintsRestrictedSet {
+            pool: pool.key(),
+            previous_restricted,
+            restricted,
         });
-
         Ok(())
     }
 
-    // --- ADMIN INSTRUCTIONS ---
+    /// Lets `mint` back sponsored campaigns, each funded with at least `min_amount` so that
+    /// the few reward stream slots are not cheap to fill. Calling it again updates the minimum.
+    /// Mints whose transfers need a transfer hook or are disabled cannot be paid out and are
+    /// rejected.
+    pub fn allow_campaign_mint(ctx: Context<AllowCampaignMint>, min_amount: u64) -> Result<()> {
+        require_gt!(min_amount, 0, ErrorCode::ZeroRewardAmount);
+        require!(
+            !mint_blocks_transfers(&ctx.accounts.mint.to_account_info())?,
+            ErrorCode::UnsupportedCampaignMint
+        );
 
-    /// Cho phép admin rút một lượng token cụ thể từ reward_vault.
-    pub fn admin_claim(ctx: Context<AdminClaim>, amount: u64) -> Result<()> {
-        let pool = &ctx.accounts.pool;
-        
-        // Chỉ admin mới có thể thực hiện giao dịch này
-        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
-        // Đảm bảo số lượng rút lớn hơn 0
-        require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
+        let campaign_mint = &mut ctx.accounts.campaign_mint;
+        let previous_min_amount = campaign_mint.min_amount;
+        campaign_mint.mint = ctx.accounts.mint.key();
+        campaign_mint.min_amount = min_amount;
+        campaign_mint.bump = ctx.bumps.campaign_mint;
 
-        // Kiểm tra số dư trong vault
-        require_gte!(ctx.accounts.reward_vault.amount, amount, ErrorCode::InsufficientVaultBalance);
+        emit_cpi!(CampaignMintAllowed {
+            pool: ctx.accounts.pool.key(),
+            mint: campaign_mint.mint,
+            previous_min_amount,
+            min_amount,
+        });
+        Ok(())
+    }
 
-        let seeds = &[
-            b"pool".as_ref(),
-            &[pool.bump],
-        ];
-        let signer = &[&seeds[..]];
+    /// Stops `mint` from backing new campaigns. Running campaigns are not affected.
+    pub fn disallow_campaign_mint(ctx: Context<DisallowCampaignMint>) -> Result<()> {
+        emit_cpi!(CampaignMintDisallowed {
+            pool: ctx.accounts.pool.key(),
+            mint: ctx.accounts.campaign_mint.mint,
+            min_amount: ctx.accounts.campaign_mint.min_amount,
+        });
+        Ok(())
+    }
 
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.reward_vault.to_account_info(),
-            to: ctx.accounts.admin_reward_token_account.to_account_info(),
-            authority: pool.to_account_info(),
-        };
-        anchor_spl::token_interface::transfer(
-            CpiContext::new_with_signer(cpi_program, cpi_accounts, signer),
+    /// Lets anyone sponsor rewards for every staker: `amount` of `reward_mint` is paid out evenly
+    /// per day from the day of `start_time` up to (not including) the day of `end_time`. The
+    /// campaign takes a free reward stream slot and shows up in `pool.reward_streams`. A mint
+    /// allowed with `allow_campaign_mint` is passed with its `campaign_mint` and needs at least
+    /// its minimum amount; other mints qualify unless `pool.campaign_mints_restricted`. Like
+    /// every stream, campaigns need a `RewardStrategy::EqualShare` pool.
+    pub fn create_campaign(
+        ctx: Context<CreateCampaign>,
+        start_time: i64,
+        end_time: i64,
+        amount: u64,
+    ) -> Result<()> {
+        require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
+        let pool = &ctx.accounts.pool;
+        let current_day = get_current_day(pool)?;
+        let start_day = day_at(pool, start_time);
+        let end_day = day_at(pool, end_time);
+        require_gte!(start_day, current_day, ErrorCode::InvalidCampaignWindow);
+        require_gt!(end_day, start_day, ErrorCode::InvalidCampaignWindow);
+        require_gte!(Pool::MAX_EPOCHS as u64, end_day, ErrorCode::MaxEpochsExceeded);
+        // A transfer hook can be added after the mint was allowed
+        require!(
+            !mint_blocks_transfers(&ctx.accounts.reward_mint.to_account_info())?,
+            ErrorCode::UnsupportedCampaignMint
+        );
+
+        let accounts = &mut *ctx.accounts;
+        let amount = fund_vault(
+            accounts.funder_token_account.to_account_info(),
+            &mut accounts.campaign_vault,
+            &accounts.reward_mint,
+            accounts.funder.to_account_info(),
+            &accounts.token_program,
             amount,
         )?;
+        match &accounts.campaign_mint {
+            // Checked on what arrived, so a transfer fee cannot take a campaign below the minimum
+            Some(campaign_mint) => require_gte!(amount, campaign_mint.min_amount, ErrorCode::CampaignBelowMinimum),
+            None => require!(!accounts.pool.campaign_mints_restricted, ErrorCode::CampaignMintNotAllowed),
+        }
 
-        Ok(())
-    }
-}
-
-fn get_current_day(pool: &Pool) -> Result<u64> {
-    let now = Clock::get()?.unix_timestamp;
+        let pool = &mut accounts.pool;
+        let reward_stream = &mut accounts.reward_stream;
+        open_reward_stream(
+            pool,
+            reward_stream,
+            accounts.reward_mint.key(),
+            accounts.campaign_vault.key(),
+            accounts.funder.key(),
+            ctx.bumps.reward_stream,
+        )?;
+        // The rounding remainder is never scheduled and stays reclaimable by the funder
+        let reward_per_epoch = amount / (end_day - start_day);
+        reward_stream.rewards_per_epoch.resize(start_day as usize, 0);
+        reward_stream.rewards_per_epoch.resize(end_day as usize, reward_per_epoch);
+        reward_stream.end_day = end_day;
+        reward_stream.total_funded = amount;
 
-    if now < pool.start_staking_timestamp {
-        return Ok(0); // Before staking started, consider day 0
+        let event = CampaignCreated {
+            pool: pool.key(),
+            reward_stream: reward_stream.key(),
+            funder: reward_stream.funder,
+            reward_mint: reward_stream.reward_mint,
+            start_day,
+            end_day,
+            amount,
+        };
+        emit_cpi!(event);
+        Ok(())
     }
 
-    let elapsed_seconds = now - pool.start_staking_timestamp;
-    let elapsed_days = elapsed_seconds / 86400; // 1 day = 86400 seconds
+    /// Sends a finished campaign's unallocated rewards back to its funder: days on which
+    /// nothing was staked and rounding remainders. Allocated rewards stay claimable.
+    pub fn reclaim_campaign_rewards(ctx: Context<ReclaimCampaignRewards>) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        let reward_stream = &mut ctx.accounts.reward_stream;
+        let current_day = get_current_day(pool)?;
+        require_gte!(current_day, reward_stream.end_day, ErrorCode::CampaignNotEnded);
 
-    Ok(elapsed_days as u64)
-}
+        reward_stream.update_accumulator(pool, current_day)?;
+        let amount = reward_stream.unallocated()?;
+        require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
 
-pub fn update_skipped_reward(pool: &mut Pool) -> Result<()> {
-    let current_day = get_current_day(pool)?;
+        transfer_from_reward_vault(
+            pool,
+            &ctx.accounts.campaign_vault,
+            &ctx.accounts.reward_mint,
+            ctx.accounts.funder_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        reward_stream.total_reclaimed = reward_stream
+            .total_reclaimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
 
-    // Không cần cập nhật nếu không có ngày mới
-    if pool.last_update_calc_reward_nft_index >= current_day {
-        return Ok(());
+        emit_cpi!(CampaignRewardsReclaimed {
+            pool: pool.key(),
+            reward_stream: reward_stream.key(),
+            funder: reward_stream.funder,
+            amount,
+        });
+        Ok(())
     }
 
-     // Thêm kiểm tra này: Nếu không có NFT nào được stake, không cần tính toán phần thưởng.
-    // Chỉ cập nhật last_update_calc_reward_nft_index để không tính lại các ngày này.
-    if pool.total_staked == 0 {
-        pool.last_update_calc_reward_nft_index = current_day;
-        return Ok(());
-    }
+    /// Retires a campaign once its claim window has passed: whatever stakers left unclaimed
+    /// goes back to the funder, the vault and campaign account are closed, and the slot is
+    /// freed for a new stream.
+    pub fn close_campaign(ctx: Context<CloseCampaign>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let reward_stream = &ctx.accounts.reward_stream;
+        let current_day = get_current_day(pool)?;
+        require_gte!(
+            current_day,
+            reward_stream.end_day + RewardStream::CAMPAIGN_CLAIM_WINDOW_DAYS,
+            ErrorCode::CampaignNotEnded
+        );
 
-    let mut plus_reward: u64 = 0; // Accumulator for new rewards
+        let remaining = ctx.accounts.campaign_vault.amount;
+        if remaining > 0 {
+            transfer_from_reward_vault(
+                pool,
+                &ctx.accounts.campaign_vault,
+                &ctx.accounts.reward_mint,
+                ctx.accounts.funder_token_account.to_account_info(),
+                &ctx.accounts.token_program,
+                remaining,
+            )?;
+        }
+        let pool_seeds = &[
+            b"pool".as_ref(),
+            &[pool.bump],
+        ];
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.campaign_vault.to_account_info(),
+                destination: ctx.accounts.funder.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            &[&pool_seeds[..]],
+        ))?;
 
-    // Iterate through days that haven't been calculated yet
-    for day in pool.last_update_calc_reward_nft_index..current_day {
-        let reward_today = *pool.rewards_per_epoch.get(day as usize).unwrap_or(&0);
+        pool.reward_streams[reward_stream.index as usize] = Pubkey::default();
 
-        // Use the current total_staked for calculation.
-        // If total_staked is 0, no reward per NFT for that day.
-        // LƯU Ý QUAN TRỌNG: Cách tính này giả định rằng `pool.total_staked`
-        // phản ánh chính xác số lượng NFT được stake trong suốt ngày `day`.
-        // Nếu `total_staked` thay đổi trong ngày và bạn cần tính toán chính xác hơn
-        // dựa trên các snapshot tại thời điểm cụ thể, bạn sẽ cần lại logic
-        // `staked_counts` phức tạp hơn.
-        if pool.total_staked > 0 {
-            let reward_for_this_day_per_nft = reward_today
-                .checked_div(pool.total_staked) // Divide by current total_staked
-                .ok_or(ErrorCode::RewardCalculationError)?;
-            
-            plus_reward = plus_reward
-                .checked_add(reward_for_this_day_per_nft)
-                .ok_or(ErrorCode::RewardCalculationError)?;
-        }
+        emit_cpi!(CampaignClosed {
+            pool: pool.key(),
+            reward_stream: reward_stream.key(),
+            funder: reward_stream.funder,
+            returned_amount: remaining,
+        });
+        Ok(())
     }
 
-    // Update the cumulative reward
-    pool.cumulative_reward_per_nft = pool
+    /// Allows NFTs from `collection_mint` to be staked. `kind` selects how membership is
+    /// verified: the Metaplex collection mint, or the Token-2022 group address.
+    pub fn add_collection(
+        ctx: Context<ManageCollection>,
+        collection_mint: Pubkey,
+        kind: CollectionKind,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        // only admin
+        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
+        require!(
+            !pool.allowed_collections.iter().any(|c| c.collection == collection_mint),
+            ErrorCode::CollectionAlreadyAllowed
+        );
+        require!(
+            pool.allowed_collections.len() < Pool::MAX_COLLECTIONS,
+            ErrorCode::MaxCollectionsExceeded
+        ); // Added max collection check
+        pool.allowed_collections.push(AllowedCollection {
+            collection: collection_mint,
+            kind,
+            total_supply: 0,
+            staked: 0,
+        });
+
+        emit_cpi!(CollectionAdded {
+            pool: pool.key(),
+            collection: collection_mint,
+            kind,
+            collection_count: pool.allowed_collections.len() as u8,
+        });
+        Ok(())
+    }
+
+    pub fn remove_collection(
+        ctx: Context<ManageCollection>,
+        collection_mint: Pubkey,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
+        let initial_len = pool.allowed_collections.len();
+        let staked = pool
+            .allowed_collections
+            .iter()
+            .find(|c| c.collection == collection_mint)
+            .map_or(0, |c| c.staked);
+        pool.allowed_collections
+            .retain(|c| c.collection != collection_mint);
+        require!(
+            pool.allowed_collections.len() < initial_len,
+            ErrorCode::CollectionNotAllowed // Return error if collection wasn't found
+        );
+
+        emit_cpi!(CollectionRemoved {
+            pool: pool.key(),
+            collection: collection_mint,
+            staked,
+            collection_count: pool.allowed_collections.len() as u8,
+        });
+        Ok(())
+    }
+
+    /// Registers the total supply of an allowed collection, which its utilization is measured
+    /// against. A supply of 0 leaves the collection out of utilization scaling.
+    pub fn set_collection_supply(
+        ctx: Context<ManageCollection>,
+        collection_mint: Pubkey,
+        total_supply: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        // Days so far are paid at the utilization they had
+        update_skipped_reward(pool)?;
+        let allowed_collection = pool
+            .allowed_collections
+            .iter_mut()
+            .find(|c| c.collection == collection_mint)
+            .ok_or(ErrorCode::CollectionNotAllowed)?;
+        let old_total_supply = std::mem::replace(&mut allowed_collection.total_supply, total_supply);
+
+        emit_cpi!(CollectionSupplySet {
+            pool: pool.key(),
+            collection: collection_mint,
+            old_total_supply,
+            new_total_supply: total_supply,
+        });
+        Ok(())
+    }
+
+    /// Sets the curve that scales each day's emission by how much of the collections' supply
+    /// is staked, or removes it with `None`. Whatever is not emitted stays in the vault and is
+    /// counted in `surplus_rewards`.
+    pub fn set_utilization_curve(
+        ctx: Context<SetUtilizationCurve>,
+        curve: Option<UtilizationCurve>,
+    ) -> Result<()> {
+        if let Some(curve) = &curve {
+            curve.validate()?;
+        }
+        let pool = &mut ctx.accounts.pool;
+        update_skipped_reward(pool)?;
+        let old_curve = std::mem::replace(&mut pool.utilization_curve, curve);
+
+        emit_cpi!(UtilizationCurveSet {
+            pool: pool.key(),
+            old_curve,
+            new_curve: pool.utilization_curve.clone(),
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+        });
+        Ok(())
+    }
+
+    /// Sets the terms of a `RewardStrategy::FixedRate` pool. Collection rates can only change
+    /// while nothing is staked; the campaign end and the liability cap can change at any time,
+    /// as long as what the pool owes and has promised stays within the cap and the vault.
+    pub fn set_fixed_rate_terms(ctx: Context<SetFixedRateTerms>, terms: FixedRateTerms) -> Result<()> {
+        terms.validate()?;
+        let pool = &mut ctx.accounts.pool;
+        require!(
+            matches!(pool.reward_strategy, RewardStrategy::FixedRate { .. }),
+            ErrorCode::WrongRewardStrategy
+        );
+        update_skipped_reward(pool)?;
+        let current_day = get_current_day(pool)?;
+        require_gte!(terms.end_day, current_day, ErrorCode::InvalidFixedRateTerms);
+        require!(
+            pool.total_staked == 0 || terms.collection_rates == pool.fixed_rate_terms.collection_rates,
+            ErrorCode::InvalidFixedRateTerms
+        );
+        let old_terms = std::mem::replace(&mut pool.fixed_rate_terms, terms);
+
+        let liabilities = pool.fixed_rate_liabilities()?;
+        require!(
+            terms.liability_cap == 0 || liabilities <= terms.liability_cap,
+            ErrorCode::LiabilityCapExceeded
+        );
+        let available = available_reward(pool, ctx.accounts.reward_vault.as_ref(), ctx.accounts.sol_vault.as_ref())?;
+        require_gte!(available, liabilities, ErrorCode::EmissionsNotCovered);
+
+        emit_cpi!(FixedRateTermsSet {
+            pool: pool.key(),
+            old_terms,
+            new_terms: terms,
+            liabilities,
+            available_rewards: available,
+        });
+        Ok(())
+    }
+
+    /// Sets how staked NFTs translate into voting power for the SPL Governance realm this pool
+    /// acts as a voter-weight plugin for.
+    pub fn configure_voter_weight(
+        ctx: Context<ConfigureVoterWeight>,
+        settings: VoterWeightSettings,
+    ) -> Result<()> {
+        settings.validate()?;
+        let config = &mut ctx.accounts.voter_weight_config;
+        let old_settings = std::mem::replace(&mut config.settings, settings);
+        config.bump = ctx.bumps.voter_weight_config;
+
+        emit_cpi!(VoterWeightConfigured {
+            pool: ctx.accounts.pool.key(),
+            old_settings,
+            new_settings: config.settings.clone(),
+        });
+        Ok(())
+    }
+
+    /// Converts a pool created before accounts were versioned (with or without the daily
+    /// `staked_counts` window) to the current layout in place, reallocating it. The admin pays for
+    /// the extra rent. Features added since start out disabled, per-collection stake counts
+    /// start at zero and are rebuilt by `migrate_stake_entry`.
+    pub fn migrate_pool(ctx: Context<MigratePool>) -> Result<()> {
+        let pool_info = ctx.accounts.pool.to_account_info();
+        let from_version = account_version(&pool_info, &LegacyPool::ACCOUNT_SIZES, Pool::VERSION)?;
+
+        // Both legacy layouts share every field up to the `staked_counts` window, which the
+        // accumulator made obsolete
+        let legacy = LegacyPool::deserialize(&mut &pool_info.try_borrow_data()?[8..])?;
+        let pool = legacy.into_pool(Clock::get()?.unix_timestamp);
+        require_keys_eq!(pool.admin, ctx.accounts.admin.key(), ErrorCode::Unauthorized);
+        rewrite_account(
+            &pool_info,
+            &pool,
+            8 + Pool::ACCOUNT_SPACE,
+            &ctx.accounts.admin,
+            &ctx.accounts.system_program,
+        )?;
+
+        emit_cpi!(AccountMigrated {
+            pool: pool_info.key(),
+            account: pool_info.key(),
+            from_version,
+            to_version: Pool::VERSION,
+        });
+        Ok(())
+    }
+
+    /// Converts a stake entry created before accounts were versioned to the current layout in
+    /// place; anyone may call it and pays the extra rent. The pool must be migrated first. The
+    /// entry's collection is read from the NFT's verified Metaplex collection and counted in
+    /// the pool's per-collection stake count.
+    pub fn migrate_stake_entry(ctx: Context<MigrateStakeEntry>) -> Result<()> {
+        let entry_info = ctx.accounts.stake_entry.to_account_info();
+        let from_version =
+            account_version(&entry_info, &[8 + LegacyNftStakeEntry::ACCOUNT_SPACE], NftStakeEntry::VERSION)?;
+
+        let legacy = LegacyNftStakeEntry::deserialize(&mut &entry_info.try_borrow_data()?[8..])?;
+        let expected = Pubkey::create_program_address(
+            &[
+                b"stake_entry".as_ref(),
+                legacy.user.as_ref(),
+                legacy.nft_mint.as_ref(),
+                &[legacy.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ErrorCode::UnknownAccountLayout))?;
+        require_keys_eq!(entry_info.key(), expected, ErrorCode::UnknownAccountLayout);
+
+        let metadata = &ctx.accounts.nft_metadata_account;
+        let (metadata_key, _) = Pubkey::find_program_address(
+            &[b"metadata", mpl_token_metadata::ID.as_ref(), legacy.nft_mint.as_ref()],
+            &mpl_token_metadata::ID,
+        );
+        require_keys_eq!(metadata.key(), metadata_key, ErrorCode::InvalidNftMetadata);
+        // Positions whose collection cannot be read stay out of the per-collection counts
+        let collection = if metadata.data_is_empty() {
+            Pubkey::default()
+        } else {
+            Metadata::safe_deserialize(&metadata.try_borrow_data()?)?
+                .collection
+                .filter(|collection| collection.verified)
+                .map_or(Pubkey::default(), |collection| collection.key)
+        };
+        if let Some(c) = ctx
+            .accounts
+            .pool
+            .allowed_collections
+            .iter_mut()
+            .find(|c| c.collection == collection)
+        {
+            c.staked += 1;
+        }
+
+        let stake_entry = NftStakeEntry {
+            version: NftStakeEntry::VERSION,
+            user: legacy.user,
+            nft_mint: legacy.nft_mint,
+            staked_at: legacy.staked_at,
+            lifetime_claimed: 0,
+            last_claimed_at: 0,
+            skipped_reward: legacy.skipped_reward,
+            bump: legacy.bump,
+            pending_reward: 0,
+            reward_delegate: Pubkey::default(),
+            delegate_share_bps: 0,
+            receipt_mint: Pubkey::default(),
+            custody: StakeCustody::Vault,
+            stream_checkpoints: [StreamCheckpoint::default(); Pool::MAX_REWARD_STREAMS],
+            collection,
+        };
+        rewrite_account(
+            &entry_info,
+            &stake_entry,
+            8 + NftStakeEntry::ACCOUNT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        emit_cpi!(AccountMigrated {
+            pool: ctx.accounts.pool.key(),
+            account: entry_info.key(),
+            from_version,
+            to_version: NftStakeEntry::VERSION,
+        });
+        Ok(())
+    }
+
+    // --- USER INSTRUCTIONS ---
+
+    pub fn stake<'info>(ctx: Context<'_, '_, 'info, 'info, Stake<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let collection = verify_nft_collection(
+            pool,
+            &ctx.accounts.nft_mint.to_account_info(),
+            &ctx.accounts.nft_metadata_account,
+        )?;
+
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.user_nft_token_account.to_account_info(),
+            mint: ctx.accounts.nft_mint.to_account_info(),
+            to: ctx.accounts.nft_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token_interface::transfer_checked(
+            CpiContext::new(cpi_program, cpi_accounts),
+            1,
+            ctx.accounts.nft_mint.decimals,
+        )?;
+    
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        checkpoint_reward_streams(pool, stake_entry, ctx.remaining_accounts)?;
+        open_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.nft_mint.key(),
+            collection,
+            ctx.bumps.stake_entry,
+        )?;
+        require_fixed_rate_covered(
+            pool,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
+    
+        // Emit StakeEvent
+        emit_cpi!(StakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: stake_entry.collection,
+            staked_at: stake_entry.staked_at,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    pub fn unstake<'info>(ctx: Context<'_, '_, 'info, 'info, Unstake<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        // Receipt-backed positions are redeemed by whoever holds the receipt
+        require!(!stake_entry.has_receipt(), ErrorCode::PositionHasReceipt);
+        require!(stake_entry.custody == StakeCustody::Vault, ErrorCode::WrongCustody);
+
+        // Ensure the pool's cumulative_reward_per_nft is up-to-date before calculating rewards
+        update_skipped_reward(pool)?;
+
+        // Everything accrued since the last checkpoint plus any unclaimed remainder is paid out now.
+        let mut payout_events = Vec::new();
+        let owner_destination = reward_destination(
+            pool,
+            ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+        )?;
+        let delegate_destination = delegate_reward_destination(
+            pool,
+            ctx.accounts.delegate_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.reward_delegate.as_ref().map(|account| account.to_account_info()),
+        );
+        pay_out_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.reward_mint.as_ref(),
+            owner_destination,
+            delegate_destination,
+            &ctx.accounts.reward_token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            &mut ctx.accounts.reward_iou,
+            ctx.bumps.reward_iou,
+            ctx.accounts.delegate_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_reward_iou),
+            &mut payout_events,
+        )?;
+        pay_out_reward_streams(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+            Some(StreamRewardIous {
+                owner: ctx.accounts.stream_reward_iou.as_deref_mut().zip(ctx.bumps.stream_reward_iou),
+                delegate: ctx.accounts.delegate_stream_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_stream_reward_iou),
+            }),
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+
+        release_nft_from_vault(
+            &ctx.accounts.stake_entry,
+            &ctx.accounts.nft_vault,
+            &ctx.accounts.nft_mint,
+            ctx.accounts.user_nft_token_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+
+        pool.record_unstake(&ctx.accounts.stake_entry.collection, Clock::get()?.unix_timestamp)?;
+
+        // Don't leave an empty IOU behind; the user gets the rent back.
+        if ctx.accounts.reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+        close_empty_reward_iou(ctx.accounts.delegate_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(ctx.accounts.stream_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(
+            ctx.accounts.delegate_stream_reward_iou.as_deref(),
+            ctx.accounts.user.to_account_info(),
+        )?;
+    
+        // Emit UnstakeEvent
+        emit_cpi!(UnstakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: ctx.accounts.stake_entry.collection,
+            unstaked_at: Clock::get()?.unix_timestamp,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Stakes like `stake` and additionally mints a 1-of-1 receipt token representing the
+    /// position. Whoever holds the receipt can claim the position's rewards and redeem the NFT,
+    /// so positions can be traded or used as collateral. With `soulbound` the receipt is a
+    /// Token-2022 non-transferable token instead.
+    pub fn stake_with_receipt<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StakeWithReceipt<'info>>,
+        soulbound: bool,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let collection = verify_nft_collection(
+            pool,
+            &ctx.accounts.nft_mint.to_account_info(),
+            &ctx.accounts.nft_metadata_account,
+        )?;
+
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.user_nft_token_account.to_account_info(),
+            mint: ctx.accounts.nft_mint.to_account_info(),
+            to: ctx.accounts.nft_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token_interface::transfer_checked(
+            CpiContext::new(cpi_program, cpi_accounts),
+            1,
+            ctx.accounts.nft_mint.decimals,
+        )?;
+
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        checkpoint_reward_streams(pool, stake_entry, ctx.remaining_accounts)?;
+        open_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.nft_mint.key(),
+            collection,
+            ctx.bumps.stake_entry,
+        )?;
+        require_fixed_rate_covered(
+            pool,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
+        stake_entry.receipt_mint = ctx.accounts.receipt_mint.key();
+
+        prepare_receipt_mint(&ctx, soulbound)?;
+
+        associated_token::create_idempotent(CpiContext::new(
+            ctx.accounts.associated_token_program.to_account_info(),
+            associated_token::Create {
+                payer: ctx.accounts.user.to_account_info(),
+                associated_token: ctx.accounts.user_receipt_token_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+                mint: ctx.accounts.receipt_mint.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                token_program: ctx.accounts.receipt_token_program.to_account_info(),
+            },
+        ))?;
+
+        let pool_seeds = &[
+            b"pool".as_ref(),
+            &[ctx.accounts.pool.bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+        token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.receipt_token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    to: ctx.accounts.user_receipt_token_account.to_account_info(),
+                    authority: ctx.accounts.pool.to_account_info(),
+                },
+                pool_signer,
+            ),
+            1,
+        )?;
+
+        emit_cpi!(StakeEvent {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: ctx.accounts.stake_entry.collection,
+            staked_at: ctx.accounts.stake_entry.staked_at,
+            cumulative_reward_per_nft: ctx.accounts.pool.cumulative_reward_per_nft,
+            total_staked: ctx.accounts.pool.total_staked,
+        });
+        emit_cpi!(ReceiptMinted {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            receipt_mint: ctx.accounts.receipt_mint.key(),
+            soulbound,
+        });
+
+        Ok(())
+    }
+
+    /// Claims `amount` of a receipt-backed position's rewards (everything if `None`) for the
+    /// current receipt holder.
+    pub fn claim_reward_with_receipt<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimRewardWithReceipt<'info>>,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+
+        update_skipped_reward(pool)?;
+        settle_stake_entry(pool, stake_entry)?;
+
+        let reward_amount = amount.unwrap_or(stake_entry.pending_reward);
+        require_gte!(stake_entry.pending_reward, reward_amount, ErrorCode::ClaimExceedsPendingReward);
+
+        // `amount` only limits the pool's own reward; reward streams are always paid in full
+        let mut payout_events = Vec::new();
+        let streams_paid = pay_out_reward_streams(
+            pool,
+            stake_entry,
+            ctx.accounts.holder.key(),
+            ctx.remaining_accounts,
+            None,
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+        require!(reward_amount > 0 || streams_paid, ErrorCode::NoRewardsToClaim);
+        if reward_amount == 0 {
+            return Ok(());
+        }
+
+        require_gte!(
+            available_reward(pool, ctx.accounts.reward_vault.as_deref(), ctx.accounts.sol_vault.as_ref())?,
+            reward_amount,
+            ErrorCode::InsufficientVaultBalance
+        );
+        let holder_destination = reward_destination(
+            pool,
+            ctx.accounts.holder_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.holder.to_account_info(),
+        )?;
+        pay_from_reward_vault(
+            pool,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.reward_mint.as_deref(),
+            holder_destination,
+            &ctx.accounts.token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            reward_amount,
+        )?;
+        pool.record_payout(reward_amount)?;
+        stake_entry.record_claim(reward_amount, Clock::get()?.unix_timestamp)?;
+        stake_entry.pending_reward -= reward_amount;
+
+        emit_cpi!(RewardClaimed {
+            pool: pool.key(),
+            user: ctx.accounts.holder.key(),
+            nft_mint: stake_entry.nft_mint,
+            collection: stake_entry.collection,
+            amount: reward_amount,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Redeems a receipt-backed position: burns the receipt, pays the holder the remaining
+    /// rewards and sends the NFT to the holder. Rent goes back to the original staker.
+    pub fn unstake_with_receipt<'info>(ctx: Context<'_, '_, 'info, 'info, UnstakeWithReceipt<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        let holder = ctx.accounts.holder.key();
+
+        update_skipped_reward(pool)?;
+        let mut payout_events = Vec::new();
+        let owner_destination = reward_destination(
+            pool,
+            ctx.accounts.holder_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.holder.to_account_info(),
+        )?;
+        pay_out_stake_entry(
+            pool,
+            stake_entry,
+            holder,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.reward_mint.as_deref(),
+            owner_destination,
+            None,
+            &ctx.accounts.reward_token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            &mut ctx.accounts.reward_iou,
+            ctx.bumps.reward_iou,
+            None,
+            &mut payout_events,
+        )?;
+        pay_out_reward_streams(
+            pool,
+            stake_entry,
+            holder,
+            ctx.remaining_accounts,
+            Some(StreamRewardIous {
+                owner: ctx.accounts.stream_reward_iou.as_deref_mut().zip(ctx.bumps.stream_reward_iou),
+                delegate: None,
+            }),
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.receipt_token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.receipt_mint.to_account_info(),
+                    from: ctx.accounts.holder_receipt_token_account.to_account_info(),
+                    authority: ctx.accounts.holder.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        release_nft_from_vault(
+            &ctx.accounts.stake_entry,
+            &ctx.accounts.nft_vault,
+            &ctx.accounts.nft_mint,
+            ctx.accounts.holder_nft_token_account.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+
+        pool.record_unstake(&ctx.accounts.stake_entry.collection, Clock::get()?.unix_timestamp)?;
+
+        if ctx.accounts.reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.holder.to_account_info())?;
+        }
+        close_empty_stream_reward_iou(ctx.accounts.stream_reward_iou.as_deref(), ctx.accounts.holder.to_account_info())?;
+
+        emit_cpi!(UnstakeEvent {
+            pool: pool.key(),
+            user: holder,
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: ctx.accounts.stake_entry.collection,
+            unstaked_at: Clock::get()?.unix_timestamp,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Moves a staked position to `new_owner` without unstaking. Rewards earned so far are paid
+    /// to the current owner, then the NFT and the position (including `staked_at`) move to the
+    /// new owner's `stake_entry` and `nft_vault` PDAs. The reward delegate is not carried over.
+    pub fn transfer_stake<'info>(ctx: Context<'_, '_, 'info, 'info, TransferStake<'info>>) -> Result<()> {
+        require_keys_neq!(ctx.accounts.new_owner.key(), ctx.accounts.user.key(), ErrorCode::InvalidNewOwner);
+
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        // A receipt-backed position already moves by transferring the receipt
+        require!(!stake_entry.has_receipt(), ErrorCode::PositionHasReceipt);
+        require!(stake_entry.custody == StakeCustody::Vault, ErrorCode::WrongCustody);
+
+        update_skipped_reward(pool)?;
+        let mut payout_events = Vec::new();
+        let owner_destination = reward_destination(
+            pool,
+            ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+        )?;
+        let delegate_destination = delegate_reward_destination(
+            pool,
+            ctx.accounts.delegate_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.reward_delegate.as_ref().map(|account| account.to_account_info()),
+        );
+        pay_out_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.reward_mint.as_ref(),
+            owner_destination,
+            delegate_destination,
+            &ctx.accounts.reward_token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            &mut ctx.accounts.reward_iou,
+            ctx.bumps.reward_iou,
+            ctx.accounts.delegate_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_reward_iou),
+            &mut payout_events,
+        )?;
+        pay_out_reward_streams(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+            Some(StreamRewardIous {
+                owner: ctx.accounts.stream_reward_iou.as_deref_mut().zip(ctx.bumps.stream_reward_iou),
+                delegate: ctx.accounts.delegate_stream_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_stream_reward_iou),
+            }),
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+
+        let new_stake_entry = &mut ctx.accounts.new_stake_entry;
+        new_stake_entry.version = NftStakeEntry::VERSION;
+        new_stake_entry.user = ctx.accounts.new_owner.key();
+        new_stake_entry.nft_mint = stake_entry.nft_mint;
+        new_stake_entry.staked_at = stake_entry.staked_at;
+        new_stake_entry.lifetime_claimed = stake_entry.lifetime_claimed;
+        new_stake_entry.last_claimed_at = stake_entry.last_claimed_at;
+        new_stake_entry.skipped_reward = stake_entry.skipped_reward;
+        new_stake_entry.bump = ctx.bumps.new_stake_entry;
+        new_stake_entry.pending_reward = 0;
+        new_stake_entry.reward_delegate = Pubkey::default();
+        new_stake_entry.delegate_share_bps = 0;
+        new_stake_entry.receipt_mint = Pubkey::default();
+        new_stake_entry.collection = stake_entry.collection;
+        // Settled above, so the new owner starts from the current stream checkpoints
+        new_stake_entry.stream_checkpoints = stake_entry.stream_checkpoints;
+
+        release_nft_from_vault(
+            &ctx.accounts.stake_entry,
+            &ctx.accounts.nft_vault,
+            &ctx.accounts.nft_mint,
+            ctx.accounts.new_nft_vault.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+
+        if ctx.accounts.reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+        close_empty_reward_iou(ctx.accounts.delegate_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(ctx.accounts.stream_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(
+            ctx.accounts.delegate_stream_reward_iou.as_deref(),
+            ctx.accounts.user.to_account_info(),
+        )?;
+
+        emit_cpi!(StakeTransferred {
+            pool: ctx.accounts.pool.key(),
+            from: ctx.accounts.user.key(),
+            to: ctx.accounts.new_owner.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            staked_at: ctx.accounts.new_stake_entry.staked_at,
+        });
+
+        Ok(())
+    }
+
+    /// Stakes an NFT without moving it: the user's token account is frozen by the pool, so the
+    /// NFT stays visible in the wallet and to holder-verification tools. Only works for mints
+    /// whose freeze authority has been handed to the pool PDA.
+    pub fn stake_in_place<'info>(ctx: Context<'_, '_, 'info, 'info, StakeInPlace<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let collection = verify_nft_collection(
+            pool,
+            &ctx.accounts.nft_mint.to_account_info(),
+            &ctx.accounts.nft_metadata_account,
+        )?;
+        require!(
+            ctx.accounts.nft_mint.freeze_authority == Some(pool.key()).into(),
+            ErrorCode::FreezeAuthorityNotPool
+        );
+
+        let pool_seeds = &[
+            b"pool".as_ref(),
+            &[pool.bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+        token_interface::freeze_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            FreezeAccount {
+                account: ctx.accounts.user_nft_token_account.to_account_info(),
+                mint: ctx.accounts.nft_mint.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            pool_signer,
+        ))?;
+
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        checkpoint_reward_streams(pool, stake_entry, ctx.remaining_accounts)?;
+        open_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.nft_mint.key(),
+            collection,
+            ctx.bumps.stake_entry,
+        )?;
+        require_fixed_rate_covered(
+            pool,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
+        stake_entry.custody = StakeCustody::Frozen;
+
+        emit_cpi!(StakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: stake_entry.collection,
+            staked_at: stake_entry.staked_at,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Ends an in-place stake: pays out rewards and thaws the user's token account.
+    pub fn unstake_in_place<'info>(ctx: Context<'_, '_, 'info, 'info, UnstakeInPlace<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        require!(stake_entry.custody == StakeCustody::Frozen, ErrorCode::WrongCustody);
+
+        update_skipped_reward(pool)?;
+        let mut payout_events = Vec::new();
+        let owner_destination = reward_destination(
+            pool,
+            ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+        )?;
+        let delegate_destination = delegate_reward_destination(
+            pool,
+            ctx.accounts.delegate_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.reward_delegate.as_ref().map(|account| account.to_account_info()),
+        );
+        pay_out_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.reward_mint.as_deref(),
+            owner_destination,
+            delegate_destination,
+            &ctx.accounts.reward_token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            &mut ctx.accounts.reward_iou,
+            ctx.bumps.reward_iou,
+            ctx.accounts.delegate_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_reward_iou),
+            &mut payout_events,
+        )?;
+        pay_out_reward_streams(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+            Some(StreamRewardIous {
+                owner: ctx.accounts.stream_reward_iou.as_deref_mut().zip(ctx.bumps.stream_reward_iou),
+                delegate: ctx.accounts.delegate_stream_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_stream_reward_iou),
+            }),
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+
+        let pool_seeds = &[
+            b"pool".as_ref(),
+            &[pool.bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+        token_interface::thaw_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            ThawAccount {
+                account: ctx.accounts.user_nft_token_account.to_account_info(),
+                mint: ctx.accounts.nft_mint.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            pool_signer,
+        ))?;
+
+        pool.record_unstake(&ctx.accounts.stake_entry.collection, Clock::get()?.unix_timestamp)?;
+
+        if ctx.accounts.reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+        close_empty_reward_iou(ctx.accounts.delegate_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(ctx.accounts.stream_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(
+            ctx.accounts.delegate_stream_reward_iou.as_deref(),
+            ctx.accounts.user.to_account_info(),
+        )?;
+
+        emit_cpi!(UnstakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.nft_mint.key(),
+            collection: ctx.accounts.stake_entry.collection,
+            unstaked_at: Clock::get()?.unix_timestamp,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Stakes a Metaplex Core asset from an allowed `MplCore` collection. The asset is transferred
+    /// to its stake entry PDA, which then holds custody until `unstake_core`. Rewards accrue and
+    /// are claimed exactly like SPL NFTs (`claim_reward` accepts the asset as `nft_mint`).
+    pub fn stake_core<'info>(ctx: Context<'_, '_, 'info, 'info, StakeCore<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+
+        let asset = CoreAsset::try_from_account(&ctx.accounts.asset)?;
+        require_keys_eq!(asset.owner, ctx.accounts.user.key(), ErrorCode::NotAssetOwner);
+        let collection = asset.collection().ok_or(ErrorCode::NotPartOfCollection)?;
+        require_keys_eq!(collection, ctx.accounts.collection.key(), ErrorCode::NotPartOfCollection);
+        require!(
+            pool.is_collection_allowed(&collection, CollectionKind::MplCore),
+            ErrorCode::CollectionNotAllowed
+        );
+
+        transfer_core_asset(
+            &ctx.accounts.mpl_core_program,
+            &ctx.accounts.asset,
+            &ctx.accounts.collection,
+            &ctx.accounts.user,
+            &ctx.accounts.user,
+            &ctx.accounts.stake_entry.to_account_info(),
+            &ctx.accounts.system_program,
+            &[],
+        )?;
+
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        checkpoint_reward_streams(pool, stake_entry, ctx.remaining_accounts)?;
+        open_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.asset.key(),
+            collection,
+            ctx.bumps.stake_entry,
+        )?;
+        require_fixed_rate_covered(
+            pool,
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
+        stake_entry.custody = StakeCustody::CoreAsset;
+
+        emit_cpi!(StakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: ctx.accounts.asset.key(),
+            collection: stake_entry.collection,
+            staked_at: stake_entry.staked_at,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Returns a staked Metaplex Core asset to its owner and pays out its rewards.
+    pub fn unstake_core<'info>(ctx: Context<'_, '_, 'info, 'info, UnstakeCore<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        require!(stake_entry.custody == StakeCustody::CoreAsset, ErrorCode::WrongCustody);
+
+        update_skipped_reward(pool)?;
+        let mut payout_events = Vec::new();
+        let owner_destination = reward_destination(
+            pool,
+            ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+        )?;
+        let delegate_destination = delegate_reward_destination(
+            pool,
+            ctx.accounts.delegate_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.reward_delegate.as_ref().map(|account| account.to_account_info()),
+        );
+        pay_out_stake_entry(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.accounts.reward_vault.as_deref(),
+            ctx.accounts.reward_mint.as_deref(),
+            owner_destination,
+            delegate_destination,
+            &ctx.accounts.token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            &mut ctx.accounts.reward_iou,
+            ctx.bumps.reward_iou,
+            ctx.accounts.delegate_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_reward_iou),
+            &mut payout_events,
+        )?;
+        pay_out_reward_streams(
+            pool,
+            stake_entry,
+            ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+            Some(StreamRewardIous {
+                owner: ctx.accounts.stream_reward_iou.as_deref_mut().zip(ctx.bumps.stream_reward_iou),
+                delegate: ctx.accounts.delegate_stream_reward_iou.as_deref_mut().zip(ctx.bumps.delegate_stream_reward_iou),
+            }),
+            &mut payout_events,
+        )?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+
+        let user_key = ctx.accounts.user.key();
+        let asset_key = ctx.accounts.asset.key();
+        let stake_entry_seeds = &[
+            b"stake_entry".as_ref(),
+            user_key.as_ref(),
+            asset_key.as_ref(),
+            &[ctx.accounts.stake_entry.bump],
+        ];
+        transfer_core_asset(
+            &ctx.accounts.mpl_core_program,
+            &ctx.accounts.asset,
+            &ctx.accounts.collection,
+            &ctx.accounts.user,
+            &ctx.accounts.stake_entry.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            &[&stake_entry_seeds[..]],
+        )?;
+
+        pool.record_unstake(&ctx.accounts.stake_entry.collection, Clock::get()?.unix_timestamp)?;
+
+        if ctx.accounts.reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+        close_empty_reward_iou(ctx.accounts.delegate_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(ctx.accounts.stream_reward_iou.as_deref(), ctx.accounts.user.to_account_info())?;
+        close_empty_stream_reward_iou(
+            ctx.accounts.delegate_stream_reward_iou.as_deref(),
+            ctx.accounts.user.to_account_info(),
+        )?;
+
+        emit_cpi!(UnstakeEvent {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            nft_mint: asset_key,
+            collection: ctx.accounts.stake_entry.collection,
+            unstaked_at: Clock::get()?.unix_timestamp,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Claims `amount` of the entry's rewards (everything if `None`). The owner's share goes to
+    /// `recipient_token_account` when one is passed, otherwise to the owner's ATA; the reward
+    /// delegate's share goes to `delegate_reward_token_account`.
+    /// Either the owner or the reward delegate may trigger the claim.
+    /// Every reward stream is paid out as well, through the per-stream accounts described on
+    /// `pay_out_reward_streams` passed as remaining accounts.
+    pub fn claim_reward<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimReward<'info>>,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let stake_entry = &mut ctx.accounts.stake_entry;
+
+        require!(!stake_entry.has_receipt(), ErrorCode::PositionHasReceipt);
+
+        let authority = ctx.accounts.authority.key();
+        let is_owner = authority == stake_entry.user;
+        require!(
+            is_owner || (stake_entry.has_reward_delegate() && authority == stake_entry.reward_delegate),
+            ErrorCode::Unauthorized
+        );
+        // Only the owner may send their share somewhere other than their own ATA.
+        require!(
+            is_owner || ctx.accounts.recipient_token_account.is_none(),
+            ErrorCode::Unauthorized
+        );
+        // SOL is paid straight to the owner's wallet
+        require!(
+            pool.reward_mode != RewardMode::Sol || ctx.accounts.recipient_token_account.is_none(),
+            ErrorCode::WrongRewardMode
+        );
+
+        // Ensure the pool's cumulative_reward_per_nft is up-to-date before calculating rewards
+        update_skipped_reward(pool)?;
+        settle_stake_entry(pool, stake_entry)?;
+
+        let reward_amount = amount.unwrap_or(stake_entry.pending_reward);
+        require_gte!(stake_entry.pending_reward, reward_amount, ErrorCode::ClaimExceedsPendingReward);
+
+        // `amount` only limits the pool's own reward; reward streams are always paid in full,
+        // to the owner's token accounts
+        let owner = stake_entry.user;
+        let mut payout_events = Vec::new();
+        let streams_paid =
+            pay_out_reward_streams(pool, stake_entry, owner, ctx.remaining_accounts, None, &mut payout_events)?;
+        for_each_payout_event!(payout_events, event => emit_cpi!(event));
+        require!(reward_amount > 0 || streams_paid, ErrorCode::NoRewardsToClaim);
+        if reward_amount == 0 {
+            return Ok(());
+        }
+
+        require_gte!(
+            available_reward(pool, ctx.accounts.reward_vault.as_ref(), ctx.accounts.sol_vault.as_ref())?,
+            reward_amount,
+            ErrorCode::InsufficientVaultBalance
+        );
+        let owner_destination = match &ctx.accounts.recipient_token_account {
+            Some(recipient_token_account) => recipient_token_account.to_account_info(),
+            None => reward_destination(
+                pool,
+                ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+                ctx.accounts.user.to_account_info(),
+            )?,
+        };
+        let delegate_destination = delegate_reward_destination(
+            pool,
+            ctx.accounts.delegate_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.reward_delegate.as_ref().map(|account| account.to_account_info()),
+        );
+
+        // Transfer rewards from the pool's vault to the owner and the reward delegate
+        let (owner_amount, delegate_amount) = pay_reward_split(
+            pool,
+            stake_entry,
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.reward_mint.as_ref(),
+            owner_destination,
+            delegate_destination,
+            &ctx.accounts.token_program,
+            ctx.accounts.sol_vault.as_ref(),
+            reward_amount,
+        )?;
+        pool.record_payout(reward_amount)?;
+        stake_entry.record_claim(reward_amount, Clock::get()?.unix_timestamp)?;
+
+        // Whatever was not claimed stays on the entry for a later claim or unstake.
+        stake_entry.pending_reward -= reward_amount;
+
+        emit_cpi!(RewardClaimed {
+            pool: pool.key(),
+            user: stake_entry.user,
+            nft_mint: stake_entry.nft_mint,
+            collection: stake_entry.collection,
+            amount: owner_amount,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        });
+        if delegate_amount > 0 {
+            emit_cpi!(RewardClaimed {
+                pool: pool.key(),
+                user: stake_entry.reward_delegate,
+                nft_mint: stake_entry.nft_mint,
+                collection: stake_entry.collection,
+                amount: delegate_amount,
+                cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+                total_staked: pool.total_staked,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Lets the owner of a stake entry share its rewards with another wallet, e.g. the operator
+    /// in a scholarship arrangement. `delegate_share_bps` of every payout goes to the delegate,
+    /// who may also trigger `claim_reward`. Pass `None` to remove the delegate.
+    pub fn set_reward_delegate(
+        ctx: Context<SetRewardDelegate>,
+        reward_delegate: Option<Pubkey>,
+        delegate_share_bps: u16,
+    ) -> Result<()> {
+        require_gte!(BPS_DENOMINATOR, delegate_share_bps as u64, ErrorCode::InvalidBasisPoints);
+
+        let stake_entry = &mut ctx.accounts.stake_entry;
+        require!(!stake_entry.has_receipt(), ErrorCode::PositionHasReceipt);
+        let previous_reward_delegate = stake_entry.reward_delegate;
+        let previous_delegate_share_bps = stake_entry.delegate_share_bps;
+        match reward_delegate {
+            Some(delegate) => {
+                require_keys_neq!(delegate, Pubkey::default(), ErrorCode::InvalidRewardDelegate);
+                stake_entry.reward_delegate = delegate;
+                stake_entry.delegate_share_bps = delegate_share_bps;
+            }
+            None => {
+                stake_entry.reward_delegate = Pubkey::default();
+                stake_entry.delegate_share_bps = 0;
+            }
+        }
+
+        emit_cpi!(RewardDelegateSet {
+            pool: ctx.accounts.pool.key(),
+            user: stake_entry.user,
+            nft_mint: stake_entry.nft_mint,
+            previous_reward_delegate,
+            previous_delegate_share_bps,
+            reward_delegate: stake_entry.reward_delegate,
+            delegate_share_bps: stake_entry.delegate_share_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Pays out rewards that `unstake` could not cover because the vault was short.
+    pub fn claim_owed(ctx: Context<ClaimOwed>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let reward_iou = &mut ctx.accounts.reward_iou;
+        require_gt!(reward_iou.amount_owed, 0, ErrorCode::NoOwedRewards);
+
+        // Partial payouts are fine: whatever is left stays on the IOU until the next top-up.
+        let sol_vault = ctx.accounts.sol_vault.as_ref();
+        let amount = reward_iou
+            .amount_owed
+            .min(available_reward(pool, ctx.accounts.reward_vault.as_ref(), sol_vault)?);
+        require_gt!(amount, 0, ErrorCode::InsufficientVaultBalance);
+
+        let user_destination = reward_destination(
+            pool,
+            ctx.accounts.user_reward_token_account.as_ref().map(|account| account.to_account_info()),
+            ctx.accounts.user.to_account_info(),
+        )?;
+        pay_from_reward_vault(
+            pool,
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.reward_mint.as_ref(),
+            user_destination,
+            &ctx.accounts.token_program,
+            sol_vault,
+            amount,
+        )?;
+        pool.record_payout(amount)?;
+
+        reward_iou.amount_owed -= amount;
+
+        emit_cpi!(OwedRewardClaimed {
+            user: ctx.accounts.user.key(),
+            amount,
+            remaining_owed: reward_iou.amount_owed,
+        });
+
+        if reward_iou.amount_owed == 0 {
+            ctx.accounts.reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Pays out what `reward_stream` owes the user from positions that left them while the
+    /// stream could not pay, e.g. because an account was frozen. Owed rewards are part of the
+    /// stream's allocation, so they stay in its vault until the stream is closed.
+    pub fn claim_owed_stream_reward(ctx: Context<ClaimOwedStreamReward>) -> Result<()> {
+        let reward_stream = &ctx.accounts.reward_stream;
+        let stream_reward_iou = &mut ctx.accounts.stream_reward_iou;
+        let owed = &mut stream_reward_iou.owed[reward_stream.index as usize];
+        require!(
+            owed.stream_id == reward_stream.id && owed.amount > 0,
+            ErrorCode::NoOwedRewards
+        );
+        let amount = std::mem::take(&mut owed.amount);
+
+        transfer_from_reward_vault(
+            &ctx.accounts.pool,
+            &ctx.accounts.stream_vault,
+            &ctx.accounts.reward_mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        emit_cpi!(OwedStreamRewardClaimed {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            reward_stream: reward_stream.key(),
+            reward_mint: reward_stream.reward_mint,
+            amount,
+        });
+
+        if stream_reward_iou.is_empty() {
+            ctx.accounts.stream_reward_iou.close(ctx.accounts.user.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Read-only: what the entry could claim right now, what it could claim once the next day
+    /// starts if nothing else changes, and how long it has been staked. Computed with the same
+    /// accounting as `claim_reward` on an in-memory copy of the pool and returned as return
+    /// data, so it works through `simulateTransaction` and through CPI.
+    pub fn get_pending_rewards(ctx: Context<GetPendingRewards>) -> Result<PendingRewards> {
+        let stake_entry = &ctx.accounts.stake_entry;
+        let now = Clock::get()?.unix_timestamp;
+        let current_day = get_current_day(&ctx.accounts.pool)?;
+
+        let next_day_starts_at = ctx.accounts.pool.start_staking_timestamp + (current_day as i64 + 1) * 86400;
+
+        let mut pool = (**ctx.accounts.pool).clone();
+        accrue_pool_until(&mut pool, now)?;
+        let claimable = pending_reward_at(&pool, stake_entry)?;
+        accrue_pool_until(&mut pool, next_day_starts_at)?;
+        let claimable_next_day = pending_reward_at(&pool, stake_entry)?;
+
+        Ok(PendingRewards {
+            claimable,
+            claimable_next_day,
+            staked_seconds: now - stake_entry.staked_at,
+            current_day,
+            next_day_starts_at,
+        })
+    }
+
+    /// Read-only pool statistics for dashboards, returned as return data. Today's numbers come
+    /// from running the regular accrual over today on an in-memory copy of the pool, assuming
+    /// nothing is staked or unstaked before the day ends; with `RewardStrategy::TimeWeighted`
+    /// and `RewardStrategy::FixedRate` they only cover what is left of today.
+    pub fn get_pool_stats(ctx: Context<GetPoolStats>) -> Result<PoolStats> {
+        let now = Clock::get()?.unix_timestamp;
+        let current_day = day_at(&ctx.accounts.pool, now);
+        let vault_balance = available_reward(
+            &ctx.accounts.pool,
+            ctx.accounts.reward_vault.as_ref(),
+            ctx.accounts.sol_vault.as_ref(),
+        )?;
+
+        let mut pool = (**ctx.accounts.pool).clone();
+        accrue_pool_until(&mut pool, now)?;
+        pool.accrue_stake_seconds(now);
+        let (cumulative_before, outstanding_before, surplus_before) =
+            (pool.cumulative_reward_per_nft, pool.outstanding_rewards, pool.surplus_rewards);
+        let committed_rewards = pool.committed_rewards(current_day)?;
+
+        let mut tomorrow = pool.clone();
+        accrue_pool_until(&mut tomorrow, pool.start_staking_timestamp + (current_day as i64 + 1) * 86400)?;
+        let todays_emission = tomorrow.outstanding_rewards - outstanding_before;
+
+        let daily_yield_per_nft = match pool.reward_strategy {
+            // Paid by the second, so a whole day at the default rate while the campaign runs
+            RewardStrategy::FixedRate { reward_per_day } => {
+                if current_day < pool.fixed_rate_terms.end_day {
+                    reward_per_day
+                } else {
+                    0
+                }
+            }
+            _ => tomorrow.cumulative_reward_per_nft - cumulative_before,
+        };
+
+        let table_end = pool
+            .rewards_per_epoch
+            .iter()
+            .rposition(|&reward| reward > 0)
+            .map_or(0, |day| day as u64 + 1);
+        let curve_end = pool
+            .emission_schedule
+            .as_ref()
+            .map_or(0, |schedule| schedule.end_day);
+
+        Ok(PoolStats {
+            reward_strategy: pool.reward_strategy,
+            total_staked: pool.total_staked,
+            collections: pool
+                .allowed_collections
+                .iter()
+                .map(|c| CollectionStats {
+                    collection: c.collection,
+                    kind: c.kind,
+                    total_supply: c.total_supply,
+                    staked: c.staked,
+                })
+                .collect(),
+            current_day,
+            last_updated_day: ctx.accounts.pool.last_update_calc_reward_nft_index,
+            todays_budget: todays_emission + (tomorrow.surplus_rewards - surplus_before),
+            todays_emission,
+            daily_yield_per_nft,
+            remaining_funded_days: table_end.max(curve_end).saturating_sub(current_day),
+            vault_balance,
+            outstanding_rewards: pool.outstanding_rewards,
+            committed_rewards,
+            surplus_rewards: pool.surplus_rewards,
+            total_funded: pool.total_funded,
+            total_distributed: pool.total_distributed,
+            total_claimed: pool.total_claimed,
+            total_admin_withdrawn: pool.total_admin_withdrawn,
+            stake_seconds: pool.stake_seconds,
+        })
+    }
+
+    /// Token-gating check for other programs: succeeds only if `user` has enough matching
+    /// positions staked, and returns the details. The user's `NftStakeEntry` accounts are passed
+    /// as remaining accounts. Receipt-backed positions belong to the receipt holder and are not
+    /// counted for the original staker. See `gating::verify_stake` for the CPI helper.
+    pub fn verify_stake<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifyStake<'info>>,
+        requirements: StakeRequirements,
+    ) -> Result<StakeVerification> {
+        let user = ctx.accounts.user.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut verification = StakeVerification {
+            user,
+            matching_count: 0,
+            oldest_staked_at: None,
+        };
+        for account in ctx.remaining_accounts {
+            // The same position must not be counted twice
+            require!(!seen.contains(account.key), ErrorCode::InvalidStakeEntry);
+            seen.push(account.key());
+
+            let stake_entry = Account::<NftStakeEntry>::try_from(account)?;
+            require_keys_eq!(stake_entry.user, user, ErrorCode::InvalidStakeEntry);
+            if stake_entry.has_receipt() || !requirements.matches(&stake_entry, now) {
+                continue;
+            }
+            verification.matching_count += 1;
+            verification.oldest_staked_at = Some(
+                verification
+                    .oldest_staked_at
+                    .map_or(stake_entry.staked_at, |oldest| oldest.min(stake_entry.staked_at)),
+            );
+        }
+
+        require_gte!(
+            verification.matching_count,
+            requirements.min_count.max(1),
+            ErrorCode::StakeRequirementsNotMet
+        );
+        Ok(verification)
+    }
+
+    /// Creates the governance `VoterWeightRecord` for `governing_token_owner`; it carries no
+    /// weight until `update_voter_weight_record` is called.
+    pub fn create_voter_weight_record(
+        ctx: Context<CreateVoterWeightRecord>,
+        governing_token_owner: Pubkey,
+    ) -> Result<()> {
+        let settings = &ctx.accounts.voter_weight_config.settings;
+        let record = &mut ctx.accounts.voter_weight_record;
+        record.realm = settings.realm;
+        record.governing_token_mint = settings.governing_token_mint;
+        record.governing_token_owner = governing_token_owner;
+        record.voter_weight = 0;
+        record.voter_weight_expiry = Some(0);
+        record.weight_action = None;
+        record.weight_action_target = None;
+
+        emit_cpi!(VoterWeightRecordCreated {
+            voter_weight_record: record.key(),
+            realm: record.realm,
+            governing_token_mint: record.governing_token_mint,
+            governing_token_owner,
+        });
+        Ok(())
+    }
+
+    /// Sets the owner's voter weight from their staked positions, for `action` on
+    /// `action_target` in the current slot only, so it must run in the same transaction as the
+    /// governance instruction. The owner's `NftStakeEntry` accounts are passed as remaining
+    /// accounts; for `CastVote` each is followed by its `NftVoteRecord` PDA for the proposal,
+    /// which is created here so the NFT cannot vote on that proposal again, even after being
+    /// unstaked and restaked.
+    pub fn update_voter_weight_record<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateVoterWeightRecord<'info>>,
+        action: VoterWeightAction,
+        action_target: Pubkey,
+    ) -> Result<()> {
+        let owner = ctx.accounts.governing_token_owner.key();
+        let settings = &ctx.accounts.voter_weight_config.settings;
+        let now = Clock::get()?.unix_timestamp;
+        let is_vote = action == VoterWeightAction::CastVote;
+        let stride = if is_vote { 2 } else { 1 };
+        require!(ctx.remaining_accounts.len().is_multiple_of(stride), ErrorCode::InvalidStakeEntry);
+
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len() / stride);
+        let mut voter_weight: u64 = 0;
+        for accounts in ctx.remaining_accounts.chunks(stride) {
+            require!(!seen.contains(accounts[0].key), ErrorCode::InvalidStakeEntry);
+            seen.push(accounts[0].key());
+
+            let stake_entry = Account::<NftStakeEntry>::try_from(&accounts[0])?;
+            require_keys_eq!(stake_entry.user, owner, ErrorCode::InvalidStakeEntry);
+            require!(!stake_entry.has_receipt(), ErrorCode::InvalidStakeEntry);
+
+            if is_vote {
+                create_nft_vote_record(
+                    &accounts[1],
+                    &ctx.accounts.governing_token_owner,
+                    &ctx.accounts.system_program,
+                    action_target,
+                    &stake_entry,
+                )?;
+            }
+            voter_weight = voter_weight
+                .checked_add(settings.weight_of(&stake_entry, now)?)
+                .ok_or(ErrorCode::RewardCalculationError)?;
+        }
+
+        let record = &mut ctx.accounts.voter_weight_record;
+        let old_voter_weight = std::mem::replace(&mut record.voter_weight, voter_weight);
+        record.voter_weight_expiry = Some(Clock::get()?.slot);
+        record.weight_action = Some(action);
+        record.weight_action_target = Some(action_target);
+
+        emit_cpi!(VoterWeightRecordUpdated {
+            voter_weight_record: record.key(),
+            governing_token_owner: owner,
+            old_voter_weight,
+            voter_weight,
+            weight_action: action,
+            weight_action_target: action_target,
+            nft_count: seen.len() as u32,
+        });
+        Ok(())
+    }
+
+    // --- ADMIN INSTRUCTIONS ---
+
+    /// Lets the admin withdraw `amount` from the reward vault, as long as what stays behind
+    /// covers everything owed and promised to stakers (see `Pool::committed_rewards`). Only
+    /// for `RewardMode::Vault` pools; minted rewards are never held and SOL has its own vault.
+    pub fn admin_claim(ctx: Context<AdminClaim>, amount: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        require_keys_eq!(ctx.accounts.admin.key(), pool.admin, ErrorCode::Unauthorized);
+        require!(pool.reward_mode == RewardMode::Vault, ErrorCode::WrongRewardMode);
+        require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
+
+        // Bring what stakers are owed up to now before working out what is left over
+        update_skipped_reward(pool)?;
+        let committed = pool.committed_rewards(get_current_day(pool)?)?;
+        require_gte!(
+            ctx.accounts.reward_vault.amount.saturating_sub(committed),
+            amount,
+            ErrorCode::InsufficientVaultBalance
+        );
+
+        let pool = &ctx.accounts.pool;
+        transfer_from_reward_vault(
+            pool,
+            &ctx.accounts.reward_vault,
+            &ctx.accounts.reward_mint,
+            ctx.accounts.admin_reward_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        let pool = &mut ctx.accounts.pool;
+        pool.total_admin_withdrawn = pool
+            .total_admin_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+
+        emit_cpi!(AdminClaimed {
+            pool: pool.key(),
+            admin: ctx.accounts.admin.key(),
+            amount,
+            vault_balance: ctx.accounts.reward_vault.amount - amount,
+        });
+        Ok(())
+    }
+
+    /// `admin_claim` for `RewardMode::Sol` pools: withdraws `amount` lamports from the SOL
+    /// vault as long as what stays behind covers everything owed and promised to stakers and
+    /// the vault's rent-exempt minimum.
+    pub fn admin_claim_sol(ctx: Context<AdminClaimSol>, amount: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        require!(pool.reward_mode == RewardMode::Sol, ErrorCode::WrongRewardMode);
+        require_gt!(amount, 0, ErrorCode::ZeroRewardAmount);
+
+        update_skipped_reward(pool)?;
+        let committed = pool.committed_rewards(get_current_day(pool)?)?;
+        let available = available_reward(pool, None, Some(&ctx.accounts.sol_vault))?;
+        require_gte!(available.saturating_sub(committed), amount, ErrorCode::InsufficientVaultBalance);
+
+        transfer_from_sol_vault(pool, &ctx.accounts.sol_vault, ctx.accounts.admin.to_account_info(), amount)?;
+        let pool = &mut ctx.accounts.pool;
+        pool.total_admin_withdrawn = pool
+            .total_admin_withdrawn
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+
+        emit_cpi!(AdminClaimed {
+            pool: pool.key(),
+            admin: ctx.accounts.admin.key(),
+            amount,
+            vault_balance: available - amount,
+        });
+        Ok(())
+    }
+}
+
+/// An event raised while paying out a position. `emit_cpi!` needs the instruction's `ctx`, so
+/// the payout helpers collect these for the handler to publish with `for_each_payout_event!`.
+enum PayoutEvent {
+    RewardClaimed(RewardClaimed),
+    RewardDeferred(RewardDeferred),
+    StreamRewardClaimed(StreamRewardClaimed),
+    StreamRewardDeferred(StreamRewardDeferred),
+}
+
+fn get_current_day(pool: &Pool) -> Result<u64> {
+    Ok(day_at(pool, Clock::get()?.unix_timestamp))
+}
+
+/// The pool day `timestamp` falls on.
+fn day_at(pool: &Pool, timestamp: i64) -> u64 {
+    if timestamp < pool.start_staking_timestamp {
+        return 0; // Before staking started, consider day 0
+    }
+
+    let elapsed_seconds = timestamp - pool.start_staking_timestamp;
+    let elapsed_days = elapsed_seconds / 86400; // 1 day = 86400 seconds
+
+    elapsed_days as u64
+}
+
+pub fn update_skipped_reward(pool: &mut Pool) -> Result<()> {
+    accrue_pool_until(pool, Clock::get()?.unix_timestamp)
+}
+
+/// Brings the pool's accumulator up to `until` following its `RewardStrategy`. Views run it
+/// on a copy of the pool to see what `update_skipped_reward` would do, including in the future.
+fn accrue_pool_until(pool: &mut Pool, until: i64) -> Result<()> {
+    let current_day = day_at(pool, until);
+    let accrual = match pool.reward_strategy {
+        RewardStrategy::TimeWeighted => {
+            if pool.accrued_until >= until {
+                return Ok(());
+            }
+            time_weighted_between(pool, pool.accrued_until, until)?
+        }
+        RewardStrategy::FixedRate { .. } => {
+            if pool.accrued_until >= until {
+                return Ok(());
+            }
+            fixed_rate_between(pool, pool.accrued_until, until)?
+        }
+        RewardStrategy::EqualShare | RewardStrategy::WeightedShare { .. } => {
+            // Nothing to do until a new day has started
+            if pool.last_update_calc_reward_nft_index >= current_day {
+                return Ok(());
+            }
+            shared_between(pool, pool.last_update_calc_reward_nft_index, current_day)?
+        }
+    };
+
+    // Update the cumulative reward
+    pool.cumulative_reward_per_nft = pool
+        .cumulative_reward_per_nft
+        .checked_add(accrual.per_share)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    pool.outstanding_rewards = pool
+        .outstanding_rewards
+        .checked_add(accrual.accrued)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    pool.total_distributed = pool
+        .total_distributed
+        .checked_add(accrual.accrued)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    pool.add_surplus(accrual.budget.saturating_sub(accrual.accrued as u128))?;
+
+    // Update the last calculated index
+    pool.last_update_calc_reward_nft_index = pool.last_update_calc_reward_nft_index.max(current_day);
+    pool.accrued_until = pool.accrued_until.max(until);
+
+    Ok(())
+}
+
+/// What one catch-up hands out: per share, in total, and what the schedule and the curve
+/// offered over the same time. Whatever is offered but not handed to stakers is surplus.
+#[derive(Default)]
+struct Accrual {
+    per_share: u64,
+    accrued: u64,
+    budget: u128,
+}
+impl Accrual {
+    /// Every one of `shares` is owed `per_share` more.
+    fn add(&mut self, per_share: u64, shares: u64) -> Result<()> {
+        self.per_share = self
+            .per_share
+            .checked_add(per_share)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.accrued = per_share
+            .checked_mul(shares)
+            .and_then(|accrued| accrued.checked_add(self.accrued))
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        Ok(())
+    }
+}
+
+/// What the day-by-day schedule and the emission curve offer over `[from_day, to_day)`.
+fn offered_between(pool: &Pool, from_day: u64, to_day: u64) -> Result<u128> {
+    let mut offered: u128 = pool
+        .rewards_per_epoch
+        .iter()
+        .take(to_day as usize)
+        .skip(from_day as usize)
+        .map(|&reward| reward as u128)
+        .sum();
+    if let Some(schedule) = &pool.emission_schedule {
+        offered += schedule.total_between(from_day, to_day)? as u128;
+    }
+    Ok(offered)
+}
+
+/// Scales a per-share reward by the utilization curve, if any, at `staked` NFTs.
+fn utilization_scaled(pool: &Pool, reward: u64, staked: u64) -> u64 {
+    match &pool.utilization_curve {
+        Some(curve) => {
+            let emission_bps = curve.weighted_emission_bps(&pool.allowed_collections, staked);
+            (reward as u128 * emission_bps as u128 / BPS_DENOMINATOR as u128) as u64
+        }
+        None => reward,
+    }
+}
+
+/// `EqualShare` and `WeightedShare`: each day is shared by the shares staked over it. The pool
+/// is brought up to date before every change to its shares, so the current total held over the
+/// whole range. Days on which nothing was staked only add to the surplus.
+fn shared_between(pool: &Pool, from_day: u64, to_day: u64) -> Result<Accrual> {
+    let mut accrual = Accrual {
+        budget: offered_between(pool, from_day, to_day)?,
+        ..Default::default()
+    };
+    let shares = pool.total_shares;
+    if shares == 0 {
+        return Ok(accrual);
+    }
+    let mut reward = reward_per_nft_between(&pool.rewards_per_epoch, shares, from_day, to_day)?;
+    if let Some(schedule) = &pool.emission_schedule {
+        // The curve is integrated over the whole range at once rather than day by day
+        let curve_reward = schedule.total_between(from_day, to_day)?;
+        reward = reward
+            .checked_add(curve_reward / shares)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+    }
+    accrual.add(utilization_scaled(pool, reward, pool.total_staked), shares)?;
+    Ok(accrual)
+}
+
+/// `TimeWeighted`: each day's rewards are released evenly over its seconds and split among
+/// the NFTs staked during each of them.
+fn time_weighted_between(pool: &Pool, from: i64, to: i64) -> Result<Accrual> {
+    let mut accrual = Accrual::default();
+    let mut at = from.max(pool.start_staking_timestamp);
+    while at < to {
+        let day = day_at(pool, at);
+        let day_end = pool.start_staking_timestamp + (day as i64 + 1) * 86400;
+        let segment_end = day_end.min(to);
+        let released = released_between(pool, at, segment_end)?;
+        accrual.budget += released;
+        // Every change to total_staked brings the pool up to the second first, so the current
+        // count held throughout
+        if pool.total_staked > 0 {
+            let per_nft = u64::try_from(released / pool.total_staked as u128)
+                .map_err(|_| error!(ErrorCode::RewardCalculationError))?;
+            accrual.add(utilization_scaled(pool, per_nft, pool.total_staked), pool.total_staked)?;
+        }
+        at = segment_end;
+    }
+    Ok(accrual)
+}
+
+/// What the schedule and the curve release over `[from, to)`, each day's offer spread evenly
+/// over its seconds.
+fn released_between(pool: &Pool, from: i64, to: i64) -> Result<u128> {
+    let mut released = 0;
+    let mut at = from.max(pool.start_staking_timestamp);
+    while at < to {
+        let day = day_at(pool, at);
+        let segment_end = (pool.start_staking_timestamp + (day as i64 + 1) * 86400).min(to);
+        released += offered_between(pool, day, day + 1)? * (segment_end - at) as u128 / 86400;
+        at = segment_end;
+    }
+    Ok(released)
+}
+
+/// `FixedRate`: every share earns one token per day, by the second, until the campaign's
+/// `end_day`, so each NFT earns its daily rate for exactly the time it is staked. The
+/// accumulator counts earning seconds per share. The schedule only records what was funded;
+/// whatever it released beyond the fixed payouts, by the second, is surplus.
+fn fixed_rate_between(pool: &Pool, from: i64, to: i64) -> Result<Accrual> {
+    // Shares only change right after the pool was brought up to the second, so the current
+    // total covers the whole range
+    let earning_seconds = (to.min(pool.fixed_rate_end_time()) - from.max(pool.start_staking_timestamp)).max(0) as u64;
+    Ok(Accrual {
+        per_share: earning_seconds,
+        // Rounded up, so the entries settling their own share, rounded down, never owe more
+        accrued: fixed_rate_reward(earning_seconds, pool.total_shares, true)?,
+        budget: released_between(pool, from, to)?,
+    })
+}
+
+/// What `shares` of a `FixedRate` pool earn over `seconds`, one token per share per day.
+fn fixed_rate_reward(seconds: u64, shares: u64, round_up: bool) -> Result<u64> {
+    let earned = seconds as u128 * shares as u128;
+    let reward = if round_up {
+        earned.div_ceil(86400)
+    } else {
+        earned / 86400
+    };
+    u64::try_from(reward).map_err(|_| error!(ErrorCode::RewardCalculationError))
+}
+
+/// Under `RewardStrategy::FixedRate`, requires what the pool owes and has promised until the
+/// campaign ends to stay within the liability cap and what the vault can pay. Stakes run it
+/// after counting the new position.
+fn require_fixed_rate_covered(
+    pool: &Pool,
+    reward_vault: Option<&InterfaceAccount<TokenAccount>>,
+    sol_vault: Option<&SystemAccount>,
+) -> Result<()> {
+    if !matches!(pool.reward_strategy, RewardStrategy::FixedRate { .. }) {
+        return Ok(());
+    }
+    let current_day = get_current_day(pool)?;
+    require_gt!(pool.fixed_rate_terms.end_day, current_day, ErrorCode::FixedRateCampaignEnded);
+    let liabilities = pool.fixed_rate_liabilities()?;
+    let cap = pool.fixed_rate_terms.liability_cap;
+    require!(cap == 0 || liabilities <= cap, ErrorCode::LiabilityCapExceeded);
+    require_gte!(
+        available_reward(pool, reward_vault, sol_vault)?,
+        liabilities,
+        ErrorCode::EmissionsNotCovered
+    );
+    Ok(())
+}
+
+/// Sums what one staked NFT earns from a day-indexed schedule over `[from_day, to_day)`, a
+/// span over which `total_staked` NFTs were staked at the end of every day.
+fn reward_per_nft_between(
+    rewards_per_epoch: &[u64],
+    total_staked: u64,
+    from_day: u64,
+    to_day: u64,
+) -> Result<u64> {
+    let mut plus_reward: u64 = 0; // Accumulator for new rewards
+
+    // Iterate through days that haven't been calculated yet
+    for day in from_day..to_day {
+        let reward_today = *rewards_per_epoch.get(day as usize).unwrap_or(&0);
+
+        // If total_staked is 0, no reward per NFT for that day.
+        if total_staked > 0 {
+            let reward_for_this_day_per_nft = reward_today
+                .checked_div(total_staked)
+                .ok_or(ErrorCode::RewardCalculationError)?;
+            
+            plus_reward = plus_reward
+                .checked_add(reward_for_this_day_per_nft)
+                .ok_or(ErrorCode::RewardCalculationError)?;
+        }
+    }
+
+    Ok(plus_reward)
+}
+
+/// Returns the NFT's verified, allowed collection. A Token-2022 NFT whose `TokenGroupMember`
+/// extension points at an allowed `TokenGroup` collection is accepted as is; otherwise the
+/// NFT's Metaplex metadata must name a verified, allowed `Metaplex` collection.
+fn verify_nft_collection(
+    pool: &Pool,
+    nft_mint: &AccountInfo,
+    nft_metadata_account: &AccountInfo,
+) -> Result<Pubkey> {
+    if let Some(group) = token_group_of(nft_mint)? {
+        if pool.is_collection_allowed(&group, CollectionKind::TokenGroup) {
+            return Ok(group);
+        }
+    }
+
+    // Token-2022 NFTs without Metaplex metadata have nothing to fall back on
+    require!(!nft_metadata_account.data_is_empty(), ErrorCode::NotPartOfCollection);
+    let nft_metadata = Metadata::safe_deserialize(&nft_metadata_account.try_borrow_data()?)?;
+
+    let collection = nft_metadata
+        .collection
+        .ok_or(ErrorCode::NotPartOfCollection)?;
+    require!(collection.verified, ErrorCode::CollectionNotVerified);
+    require!(
+        pool.is_collection_allowed(&collection.key, CollectionKind::Metaplex),
+        ErrorCode::CollectionNotAllowed
+    );
+
+    Ok(collection.key)
+}
+
+/// Reads the group from a Token-2022 mint's `TokenGroupMember` extension, if it has one.
+/// Members can only be added with the group's update authority, so the group is trustworthy.
+/// Mints whose `GroupMemberPointer` points at a member account elsewhere are rejected: that
+/// account's layout is up to the program owning it.
+fn token_group_of(nft_mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    if nft_mint.owner != &spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = nft_mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    if let Ok(pointer) = mint.get_extension::<GroupMemberPointer>() {
+        if let Some(member_address) = Option::<Pubkey>::from(pointer.member_address) {
+            require_keys_eq!(member_address, nft_mint.key(), ErrorCode::ExternalGroupMember);
+        }
+    }
+    match mint.get_extension::<TokenGroupMember>() {
+        Ok(member) if member.mint == nft_mint.key() => {
+            require!(mint.base.supply == 1 && mint.base.decimals == 0, ErrorCode::GroupMemberNotAnNft);
+            Ok(Some(member.group))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Whether transfers of `mint` fail no matter which accounts are passed: Token-2022 mints with
+/// a transfer hook program (whose extra accounts we never pass) or without transfers at all.
+fn mint_blocks_transfers(mint: &AccountInfo) -> Result<bool> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(false);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    if let Ok(transfer_hook) = mint.get_extension::<TransferHook>() {
+        if Option::<Pubkey>::from(transfer_hook.program_id).is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(mint.get_extension_types()?.contains(&ExtensionType::NonTransferable))
+}
+
+/// Records a new position on a freshly initialized `stake_entry` and counts it in the pool.
+fn open_stake_entry(
+    pool: &mut Pool,
+    stake_entry: &mut NftStakeEntry,
+    user: Pubkey,
+    nft_mint: Pubkey,
+    collection: Pubkey,
+    bump: u8,
+) -> Result<()> {
+    // Ensure cumulative_reward_per_nft is updated before recording it for the stake entry
+    update_skipped_reward(pool)?;
+
+    stake_entry.version = NftStakeEntry::VERSION;
+    stake_entry.user = user;
+    stake_entry.nft_mint = nft_mint;
+    stake_entry.staked_at = Clock::get()?.unix_timestamp;
+    stake_entry.lifetime_claimed = 0;
+    stake_entry.last_claimed_at = 0;
+    stake_entry.bump = bump;
+    stake_entry.skipped_reward = pool.cumulative_reward_per_nft; // Record current global cumulative reward
+    stake_entry.pending_reward = 0;
+    stake_entry.collection = collection;
+
+    pool.record_stake(&collection, stake_entry.staked_at)?;
+    Ok(())
+}
+
+/// Sends the staked NFT out of its `nft_vault` and closes the vault, returning its rent to
+/// `rent_destination`. The vault is owned by the stake entry PDA, which signs.
+fn release_nft_from_vault<'info>(
+    stake_entry: &Account<'info, NftStakeEntry>,
+    nft_vault: &InterfaceAccount<'info, TokenAccount>,
+    nft_mint: &InterfaceAccount<'info, Mint>,
+    destination: AccountInfo<'info>,
+    rent_destination: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let stake_entry_seeds = &[
+        b"stake_entry".as_ref(),
+        stake_entry.user.as_ref(),
+        stake_entry.nft_mint.as_ref(),
+        &[stake_entry.bump],
+    ];
+    let stake_entry_signer = &[&stake_entry_seeds[..]];
+
+    let cpi_program = token_program.to_account_info();
+    let cpi_accounts_transfer = TransferChecked {
+        from: nft_vault.to_account_info(),
+        mint: nft_mint.to_account_info(),
+        to: destination,
+        authority: stake_entry.to_account_info(),
+    };
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts_transfer, stake_entry_signer),
+        1,
+        nft_mint.decimals,
+    )?;
+
+    anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
+        cpi_program,
+        CloseAccount {
+            account: nft_vault.to_account_info(),
+            destination: rent_destination,
+            authority: stake_entry.to_account_info(),
+        },
+        stake_entry_signer,
+    ))
+}
+
+/// The layout version of a program account about to be migrated: 0 for the unversioned
+/// layouts, recognised by their size, otherwise the stored version, which must be older than
+/// `current`.
+fn account_version(account: &AccountInfo, legacy_sizes: &[usize], current: u8) -> Result<u8> {
+    let data = account.try_borrow_data()?;
+    if legacy_sizes.contains(&data.len()) {
+        return Ok(0);
+    }
+    // The version is the first field after the discriminator
+    let version = *data.get(8).ok_or(ErrorCode::UnknownAccountLayout)?;
+    require_gt!(current, version, ErrorCode::AlreadyMigrated);
+    Ok(version)
+}
+
+/// Resizes `account` to `space`, settling the rent difference with `payer`, and overwrites it
+/// with `value`.
+fn rewrite_account<'info, T: AccountSerialize>(
+    account: &AccountInfo<'info>,
+    value: &T,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    resize_account(account, space, payer, system_program)?;
+    let mut data = account.try_borrow_mut_data()?;
+    data.fill(0);
+    value.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Reallocates a program-owned account to `space` bytes, settling the rent difference with
+/// `payer`. New bytes are zeroed.
+fn resize_account<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+    if rent > lamports {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent - lamports,
+        )?;
+    } else if lamports > rent {
+        // The account is program-owned, so the surplus can be moved directly
+        **account.try_borrow_mut_lamports()? -= lamports - rent;
+        **payer.try_borrow_mut_lamports()? += lamports - rent;
+    }
+
+    account.resize(space)?;
+    Ok(())
+}
+
+/// Creates the `NftVoteRecord` of `stake_entry`'s NFT for `proposal`; fails if the NFT has
+/// already been counted for it.
+fn create_nft_vote_record<'info>(
+    nft_vote_record: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    proposal: Pubkey,
+    stake_entry: &NftStakeEntry,
+) -> Result<()> {
+    let (expected, bump) = Pubkey::find_program_address(
+        &[b"nft_vote_record", proposal.as_ref(), stake_entry.nft_mint.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(nft_vote_record.key(), expected, ErrorCode::InvalidNftVoteRecord);
+    require!(
+        nft_vote_record.owner == &system_program::ID && nft_vote_record.data_is_empty(),
+        ErrorCode::NftAlreadyVoted
+    );
+
+    let space = 8 + NftVoteRecord::ACCOUNT_SPACE;
+    let seeds = &[
+        b"nft_vote_record".as_ref(),
+        proposal.as_ref(),
+        stake_entry.nft_mint.as_ref(),
+        &[bump],
+    ];
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            CreateAccount {
+                from: payer.to_account_info(),
+                to: nft_vote_record.clone(),
+            },
+            &[&seeds[..]],
+        ),
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        &crate::ID,
+    )?;
+
+    let record = NftVoteRecord {
+        proposal,
+        nft_mint: stake_entry.nft_mint,
+        governing_token_owner: payer.key(),
+        bump,
+    };
+    let mut data = nft_vote_record.try_borrow_mut_data()?;
+    record.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Creates the receipt mint for `nft_mint` on its first use, with the pool as mint authority.
+/// The mint is kept (with zero supply) after redemption and reused when the NFT is staked
+/// again, so an existing mint must match the requested receipt kind.
+fn prepare_receipt_mint(ctx: &Context<StakeWithReceipt>, soulbound: bool) -> Result<()> {
+    let receipt_mint = &ctx.accounts.receipt_mint;
+    let receipt_token_program = &ctx.accounts.receipt_token_program;
+    if soulbound {
+        // Non-transferable tokens only exist in Token-2022
+        require_keys_eq!(receipt_token_program.key(), spl_token_2022::ID, ErrorCode::InvalidReceiptMint);
+    }
+
+    if receipt_mint.owner == &system_program::ID {
+        let extensions = if soulbound {
+            vec![ExtensionType::NonTransferable]
+        } else {
+            Vec::new()
+        };
+        let space = token_interface::find_mint_account_size(Some(&extensions))?;
+
+        let nft_mint_key = ctx.accounts.nft_mint.key();
+        let receipt_mint_seeds = &[
+            b"receipt_mint".as_ref(),
+            nft_mint_key.as_ref(),
+            &[ctx.bumps.receipt_mint],
+        ];
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                CreateAccount {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: receipt_mint.to_account_info(),
+                },
+                &[&receipt_mint_seeds[..]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &receipt_token_program.key(),
+        )?;
+
+        if soulbound {
+            token_interface::non_transferable_mint_initialize(CpiContext::new(
+                receipt_token_program.to_account_info(),
+                NonTransferableMintInitialize {
+                    token_program_id: receipt_token_program.to_account_info(),
+                    mint: receipt_mint.to_account_info(),
+                },
+            ))?;
+        }
+        token_interface::initialize_mint2(
+            CpiContext::new(
+                receipt_token_program.to_account_info(),
+                InitializeMint2 {
+                    mint: receipt_mint.to_account_info(),
+                },
+            ),
+            0,
+            &ctx.accounts.pool.key(),
+            None,
+        )?;
+        return Ok(());
+    }
+
+    require_keys_eq!(*receipt_mint.owner, receipt_token_program.key(), ErrorCode::InvalidReceiptMint);
+    let data = receipt_mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    require!(
+        mint.base.mint_authority == Some(ctx.accounts.pool.key()).into() && mint.base.supply == 0,
+        ErrorCode::InvalidReceiptMint
+    );
+    let is_soulbound = mint
+        .get_extension_types()?
+        .contains(&ExtensionType::NonTransferable);
+    require!(is_soulbound == soulbound, ErrorCode::InvalidReceiptMint);
+
+    Ok(())
+}
+
+/// Calls Metaplex Core `TransferV1` to move `asset` to `new_owner`. `authority` is the current
+/// owner and signs either directly or through `signer_seeds` when it is a stake entry PDA.
+#[allow(clippy::too_many_arguments)]
+fn transfer_core_asset<'info>(
+    mpl_core_program: &AccountInfo<'info>,
+    asset: &AccountInfo<'info>,
+    collection: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    new_owner: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let ix = Instruction {
+        program_id: MPL_CORE_ID,
+        accounts: vec![
+            AccountMeta::new(asset.key(), false),
+            AccountMeta::new_readonly(collection.key(), false),
+            AccountMeta::new(payer.key(), true),
+            AccountMeta::new_readonly(authority.key(), true),
+            AccountMeta::new_readonly(new_owner.key(), false),
+            AccountMeta::new_readonly(system_program.key(), false),
+            // Core marks an omitted optional account (here: log_wrapper) with its own program id
+            AccountMeta::new_readonly(MPL_CORE_ID, false),
+        ],
+        // No compression proof: the asset is uncompressed
+        data: vec![CoreAsset::TRANSFER_V1_DISCRIMINATOR, 0],
+    };
+    invoke_signed(
+        &ix,
+        &[
+            asset.clone(),
+            collection.clone(),
+            payer.clone(),
+            authority.clone(),
+            new_owner.clone(),
+            system_program.clone(),
+            mpl_core_program.clone(),
+        ],
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Moves everything the entry has accrued since its last checkpoint into `pending_reward`
+/// and moves the checkpoint up to the pool's current `cumulative_reward_per_nft`, which
+/// counts per share of the pool's `RewardStrategy`.
+fn settle_stake_entry(pool: &Pool, stake_entry: &mut NftStakeEntry) -> Result<()> {
+    let per_share = pool
         .cumulative_reward_per_nft
-        .checked_add(plus_reward)
+        .checked_sub(stake_entry.skipped_reward)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    let accrued = pool.reward_for(per_share, pool.shares_of(&stake_entry.collection))?;
+    stake_entry.pending_reward = stake_entry
+        .pending_reward
+        .checked_add(accrued)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    stake_entry.skipped_reward = pool.cumulative_reward_per_nft;
+    Ok(())
+}
+
+/// What `settle_stake_entry` would leave in the entry's `pending_reward` against `pool`.
+fn pending_reward_at(pool: &Pool, stake_entry: &NftStakeEntry) -> Result<u64> {
+    let mut stake_entry = stake_entry.clone();
+    settle_stake_entry(pool, &mut stake_entry)?;
+    Ok(stake_entry.pending_reward)
+}
+
+/// Settles the entry and pays out everything it has earned to `owner`, split with the reward
+/// delegate. Used whenever a position leaves its owner (unstake, transfer, receipt redemption).
+/// The vault (or the emission cap in `Mint` mode) may be short, so whatever it cannot cover
+/// right now is recorded for `claim_owed`, split the same way: the delegate's share goes into
+/// the delegate's own IOU and the rest into the owner's.
+#[allow(clippy::too_many_arguments)]
+fn pay_out_stake_entry<'info>(
+    pool: &mut Account<'info, Pool>,
+    stake_entry: &mut NftStakeEntry,
+    owner: Pubkey,
+    reward_vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    reward_mint: Option<&InterfaceAccount<'info, Mint>>,
+    owner_destination: AccountInfo<'info>,
+    delegate_destination: Option<AccountInfo<'info>>,
+    token_program: &Interface<'info, TokenInterface>,
+    sol_vault: Option<&SystemAccount<'info>>,
+    reward_iou: &mut Account<'info, RewardIou>,
+    reward_iou_bump: u8,
+    delegate_reward_iou: Option<(&mut Account<'info, RewardIou>, u8)>,
+    events: &mut Vec<PayoutEvent>,
+) -> Result<()> {
+    settle_stake_entry(pool, stake_entry)?;
+    let reward_amount = stake_entry.pending_reward;
+
+    let paid_amount = reward_amount.min(available_reward(pool, reward_vault, sol_vault)?);
+    let owed_amount = reward_amount - paid_amount;
+
+    if paid_amount > 0 {
+        let (owner_amount, delegate_amount) = pay_reward_split(
+            pool,
+            stake_entry,
+            reward_vault,
+            reward_mint,
+            owner_destination,
+            delegate_destination,
+            token_program,
+            sol_vault,
+            paid_amount,
+        )?;
+        pool.record_payout(paid_amount)?;
+        stake_entry.record_claim(paid_amount, Clock::get()?.unix_timestamp)?;
+
+        events.push(PayoutEvent::RewardClaimed(RewardClaimed {
+            pool: pool.key(),
+            user: owner,
+            nft_mint: stake_entry.nft_mint,
+            collection: stake_entry.collection,
+            amount: owner_amount,
+            cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+            total_staked: pool.total_staked,
+        }));
+        if delegate_amount > 0 {
+            events.push(PayoutEvent::RewardClaimed(RewardClaimed {
+                pool: pool.key(),
+                user: stake_entry.reward_delegate,
+                nft_mint: stake_entry.nft_mint,
+                collection: stake_entry.collection,
+                amount: delegate_amount,
+                cumulative_reward_per_nft: pool.cumulative_reward_per_nft,
+                total_staked: pool.total_staked,
+            }));
+        }
+    }
+
+    stake_entry.pending_reward = 0;
+
+    // A delegate that is also the owner would share the owner's IOU; there is nothing to split
+    let delegate_owed = if stake_entry.reward_delegate == owner {
+        0
+    } else {
+        stake_entry.delegate_share_of(owed_amount)?
+    };
+    let owner_owed = owed_amount - delegate_owed;
+
+    if delegate_owed > 0 {
+        let (delegate_reward_iou, delegate_reward_iou_bump) =
+            delegate_reward_iou.ok_or(ErrorCode::MissingDelegateRewardIou)?;
+        record_reward_owed(
+            delegate_reward_iou,
+            stake_entry.reward_delegate,
+            delegate_reward_iou_bump,
+            stake_entry.nft_mint,
+            delegate_owed,
+            events,
+        )?;
+    }
+    if owner_owed > 0 {
+        record_reward_owed(reward_iou, owner, reward_iou_bump, stake_entry.nft_mint, owner_owed, events)?;
+    }
+
+    Ok(())
+}
+
+/// Closes an optional IOU that ended up with nothing owed, e.g. a delegate IOU created for a
+/// payout the vault could cover in full.
+fn close_empty_reward_iou<'info>(
+    reward_iou: Option<&Account<'info, RewardIou>>,
+    rent_receiver: AccountInfo<'info>,
+) -> Result<()> {
+    match reward_iou {
+        Some(reward_iou) if reward_iou.amount_owed == 0 => reward_iou.close(rent_receiver),
+        _ => Ok(()),
+    }
+}
+
+/// Adds `amount` to `user`'s IOU, initializing it on first use.
+fn record_reward_owed<'info>(
+    reward_iou: &mut Account<'info, RewardIou>,
+    user: Pubkey,
+    bump: u8,
+    nft_mint: Pubkey,
+    amount: u64,
+    events: &mut Vec<PayoutEvent>,
+) -> Result<()> {
+    reward_iou.user = user;
+    reward_iou.bump = bump;
+    reward_iou.amount_owed = reward_iou
+        .amount_owed
+        .checked_add(amount)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+
+    events.push(PayoutEvent::RewardDeferred(RewardDeferred {
+        user,
+        nft_mint,
+        amount,
+        total_owed: reward_iou.amount_owed,
+    }));
+    Ok(())
+}
+
+/// Transfers `amount` reward tokens out of the pool-owned reward vault.
+fn transfer_from_reward_vault<'info>(
+    pool: &Account<'info, Pool>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    reward_mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"pool".as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: reward_vault.to_account_info(),
+        mint: reward_mint.to_account_info(),
+        to,
+        authority: pool.to_account_info(),
+    };
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer),
+        amount,
+        reward_mint.decimals,
+    )
+}
+
+/// How much of the pool's own reward can be paid out right now: the vault balance, the
+/// lamports the SOL vault holds above its rent-exempt minimum, or in `Mint` mode what is left
+/// under the emission cap.
+fn available_reward(
+    pool: &Pool,
+    reward_vault: Option<&InterfaceAccount<TokenAccount>>,
+    sol_vault: Option<&SystemAccount>,
+) -> Result<u64> {
+    Ok(match pool.reward_mode {
+        RewardMode::Vault => reward_vault.ok_or(ErrorCode::MissingRewardVault)?.amount,
+        RewardMode::Mint => pool.emission_cap.saturating_sub(pool.total_minted),
+        RewardMode::Sol => {
+            let sol_vault = sol_vault.ok_or(ErrorCode::MissingSolVault)?;
+            sol_vault
+                .lamports()
+                .saturating_sub(Rent::get()?.minimum_balance(0))
+        }
+    })
+}
+
+/// Pays `amount` of earned rewards from `reward_vault`. For the pool's own rewards in `Mint`
+/// mode they are minted instead, and in `Sol` mode the lamports go from the SOL vault
+/// straight into `to`, a wallet; callers book them with `Pool::record_payout`. Reward
+/// streams always pay from their own vault.
+fn pay_from_reward_vault<'info>(
+    pool: &Account<'info, Pool>,
+    reward_vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    reward_mint: Option<&InterfaceAccount<'info, Mint>>,
+    to: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    sol_vault: Option<&SystemAccount<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let stream_vault = reward_vault.filter(|vault| vault.key() != pool.reward_vault);
+    if stream_vault.is_none() && pool.reward_mode == RewardMode::Sol {
+        let sol_vault = sol_vault.ok_or(ErrorCode::MissingSolVault)?;
+        return transfer_from_sol_vault(pool, sol_vault, to, amount);
+    }
+    let reward_mint = reward_mint.ok_or(ErrorCode::MissingRewardTokenAccounts)?;
+    if stream_vault.is_some() || pool.reward_mode == RewardMode::Vault {
+        let reward_vault = reward_vault.ok_or(ErrorCode::MissingRewardVault)?;
+        return transfer_from_reward_vault(pool, reward_vault, reward_mint, to, token_program, amount);
+    }
+
+    let seeds = &[
+        b"pool".as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&seeds[..]];
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: reward_mint.to_account_info(),
+                to,
+                authority: pool.to_account_info(),
+            },
+            signer,
+        ),
+        amount,
+    )
+}
+
+/// Transfers `amount` lamports out of the pool's SOL vault, which signs for itself.
+fn transfer_from_sol_vault<'info>(
+    pool: &Pool,
+    sol_vault: &SystemAccount<'info>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let sol_vault_seeds = &[
+        b"sol_vault".as_ref(),
+        &[pool.sol_vault_bump],
+    ];
+    invoke_signed(
+        &solana_system_interface::instruction::transfer(&sol_vault.key(), &to.key(), amount),
+        &[sol_vault.to_account_info(), to],
+        &[&sol_vault_seeds[..]],
+    )
+    .map_err(Into::into)
+}
+
+/// Where the pool's own rewards for `wallet` land: the wallet itself when the pool pays in
+/// SOL, otherwise its reward token account.
+fn reward_destination<'info>(
+    pool: &Pool,
+    reward_token_account: Option<AccountInfo<'info>>,
+    wallet: AccountInfo<'info>,
+) -> Result<AccountInfo<'info>> {
+    match pool.reward_mode {
+        RewardMode::Sol => Ok(wallet),
+        RewardMode::Vault | RewardMode::Mint => {
+            reward_token_account.ok_or(error!(ErrorCode::MissingRewardTokenAccounts))
+        }
+    }
+}
+
+/// `reward_destination` for the reward delegate, which may be left out if it has no share.
+fn delegate_reward_destination<'info>(
+    pool: &Pool,
+    delegate_reward_token_account: Option<AccountInfo<'info>>,
+    reward_delegate: Option<AccountInfo<'info>>,
+) -> Option<AccountInfo<'info>> {
+    match pool.reward_mode {
+        RewardMode::Sol => reward_delegate,
+        RewardMode::Vault | RewardMode::Mint => delegate_reward_token_account,
+    }
+}
+
+/// Moves `amount` from the admin's token account into the reward vault and returns how much
+/// the vault actually received, which is less than `amount` for mints with a transfer fee.
+fn fund_reward_vault(accounts: &mut AddReward, amount: u64) -> Result<u64> {
+    fund_vault(
+        accounts.admin_reward_token_account.to_account_info(),
+        &mut accounts.reward_vault,
+        &accounts.reward_mint,
+        accounts.admin.to_account_info(),
+        &accounts.token_program,
+        amount,
+    )
+}
+
+/// Moves `amount` from `from` into `vault` and returns how much the vault actually received.
+fn fund_vault<'info>(
+    from: AccountInfo<'info>,
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = vault.amount;
+
+    let cpi_program = token_program.to_account_info();
+    let cpi_accounts = TransferChecked {
+        from,
+        mint: mint.to_account_info(),
+        to: vault.to_account_info(),
+        authority,
+    };
+    token_interface::transfer_checked(
+        CpiContext::new(cpi_program, cpi_accounts),
+        amount,
+        mint.decimals,
+    )?;
+
+    vault.reload()?;
+    let received = vault
+        .amount
+        .checked_sub(balance_before)
+        .ok_or(ErrorCode::RewardCalculationError)?;
+    require_gt!(received, 0, ErrorCode::ZeroRewardAmount);
+    Ok(received)
+}
+
+/// Pays `amount` out of the reward vault, split between the entry's reward delegate and
+/// the owner. Returns `(owner_amount, delegate_amount)`.
+#[allow(clippy::too_many_arguments)]
+fn pay_reward_split<'info>(
+    pool: &Account<'info, Pool>,
+    stake_entry: &NftStakeEntry,
+    reward_vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    reward_mint: Option<&InterfaceAccount<'info, Mint>>,
+    owner_destination: AccountInfo<'info>,
+    delegate_destination: Option<AccountInfo<'info>>,
+    token_program: &Interface<'info, TokenInterface>,
+    sol_vault: Option<&SystemAccount<'info>>,
+    amount: u64,
+) -> Result<(u64, u64)> {
+    let delegate_amount = stake_entry.delegate_share_of(amount)?;
+    let owner_amount = amount - delegate_amount;
+
+    if delegate_amount > 0 {
+        let delegate_destination = delegate_destination.ok_or(ErrorCode::MissingDelegateTokenAccount)?;
+        pay_from_reward_vault(
+            pool,
+            reward_vault,
+            reward_mint,
+            delegate_destination,
+            token_program,
+            sol_vault,
+            delegate_amount,
+        )?;
+    }
+    if owner_amount > 0 {
+        pay_from_reward_vault(
+            pool,
+            reward_vault,
+            reward_mint,
+            owner_destination,
+            token_program,
+            sol_vault,
+            owner_amount,
+        )?;
+    }
+
+    Ok((owner_amount, delegate_amount))
+}
+
+/// Puts a freshly initialized stream into the pool's first free slot. It starts accruing
+/// from today. Streams split every day equally among the staked NFTs, so only
+/// `RewardStrategy::EqualShare` pools take them.
+fn open_reward_stream(
+    pool: &mut Pool,
+    reward_stream: &mut RewardStream,
+    reward_mint: Pubkey,
+    reward_vault: Pubkey,
+    funder: Pubkey,
+    bump: u8,
+) -> Result<()> {
+    require!(
+        pool.reward_strategy == RewardStrategy::EqualShare,
+        ErrorCode::WrongRewardStrategy
+    );
+    let index = pool
+        .reward_streams
+        .iter()
+        .position(|key| *key == Pubkey::default())
+        .ok_or(ErrorCode::MaxRewardStreamsExceeded)?;
+    let current_day = get_current_day(pool)?;
+
+    reward_stream.id = pool.next_reward_stream_id;
+    reward_stream.index = index as u8;
+    reward_stream.reward_mint = reward_mint;
+    reward_stream.reward_vault = reward_vault;
+    reward_stream.funder = funder;
+    reward_stream.end_day = 0;
+    reward_stream.rewards_per_epoch = Vec::new();
+    reward_stream.cumulative_reward_per_nft = 0;
+    // Nothing accrues for the days before the stream existed
+    reward_stream.last_update_calc_reward_nft_index = current_day;
+    reward_stream.total_funded = 0;
+    reward_stream.total_allocated = 0;
+    reward_stream.total_reclaimed = 0;
+    reward_stream.bump = bump;
+
+    pool.reward_streams[index] = Pubkey::find_program_address(
+        &[b"reward_stream".as_ref(), &reward_stream.id.to_le_bytes()],
+        &crate::ID,
+    )
+    .0;
+    pool.next_reward_stream_id += 1;
+    Ok(())
+}
+
+/// Loads the pool's reward stream in slot `index` from a remaining account and brings its
+/// accumulator up to today. Callers must `exit` it to persist the update.
+fn load_reward_stream<'info>(
+    pool: &Pool,
+    index: usize,
+    account: Option<&'info AccountInfo<'info>>,
+) -> Result<Account<'info, RewardStream>> {
+    let account = account.ok_or(ErrorCode::MissingRewardStreamAccounts)?;
+    require_keys_eq!(account.key(), pool.reward_streams[index], ErrorCode::InvalidRewardStreamAccount);
+    let mut reward_stream = Account::<RewardStream>::try_from(account)?;
+    reward_stream.update_accumulator(pool, get_current_day(pool)?)?;
+    Ok(reward_stream)
+}
+
+/// Starts a fresh entry's checkpoint in every reward stream. `remaining_accounts` holds the
+/// pool's active `RewardStream`s in slot order. Must run before the entry is counted in
+/// `total_staked`, like `update_skipped_reward`.
+fn checkpoint_reward_streams<'info>(
+    pool: &Pool,
+    stake_entry: &mut NftStakeEntry,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let mut accounts = remaining_accounts.iter();
+    for index in pool.active_stream_slots() {
+        let reward_stream = load_reward_stream(pool, index, accounts.next())?;
+        stake_entry.stream_checkpoints[index] = StreamCheckpoint {
+            stream_id: reward_stream.id,
+            skipped_reward: reward_stream.cumulative_reward_per_nft,
+            pending_reward: 0,
+        };
+        reward_stream.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+/// The IOUs `pay_out_reward_streams` records to when a position leaves its owner and a stream
+/// cannot pay out right now. Each is only required once something is deferred to it.
+struct StreamRewardIous<'a, 'info> {
+    owner: Option<(&'a mut Account<'info, StreamRewardIou>, u8)>,
+    delegate: Option<(&'a mut Account<'info, StreamRewardIou>, u8)>,
+}
+
+/// Settles the entry in every reward stream and pays out everything pending there to
+/// `beneficiary`, split with the reward delegate. Returns whether anything was paid.
+///
+/// `remaining_accounts` holds, per active stream in slot order: the `RewardStream`, its vault, its
+/// reward mint, the mint's token program, the beneficiary's token account and, when the entry
+/// has a reward delegate, the delegate's token account. Stream vaults only ever pay out what
+/// was funded into them, so unlike the main vault they cannot run short. A stream can still be
+/// unable to pay (see `stream_payout_blocked`); a claim then leaves its reward pending on the
+/// entry, while a position leaving its owner passes `stream_reward_ious` to record it there.
+fn pay_out_reward_streams<'info>(
+    pool: &Account<'info, Pool>,
+    stake_entry: &mut NftStakeEntry,
+    beneficiary: Pubkey,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    mut stream_reward_ious: Option<StreamRewardIous<'_, 'info>>,
+    events: &mut Vec<PayoutEvent>,
+) -> Result<bool> {
+    let mut accounts = remaining_accounts.iter();
+    let mut paid_any = false;
+    for index in pool.active_stream_slots() {
+        let reward_stream = load_reward_stream(pool, index, accounts.next())?;
+        let mut next = || accounts.next().ok_or(error!(ErrorCode::MissingRewardStreamAccounts));
+        let stream_vault = InterfaceAccount::<TokenAccount>::try_from(next()?)?;
+        let stream_mint = InterfaceAccount::<Mint>::try_from(next()?)?;
+        let token_program = Interface::<TokenInterface>::try_from(next()?)?;
+        let beneficiary_token_account = InterfaceAccount::<TokenAccount>::try_from(next()?)?;
+        let delegate_token_account = if stake_entry.has_reward_delegate() {
+            Some(InterfaceAccount::<TokenAccount>::try_from(next()?)?)
+        } else {
+            None
+        };
+
+        require_keys_eq!(stream_vault.key(), reward_stream.reward_vault, ErrorCode::InvalidRewardStreamAccount);
+        require_keys_eq!(stream_mint.key(), reward_stream.reward_mint, ErrorCode::InvalidRewardStreamAccount);
+        require_keys_eq!(beneficiary_token_account.mint, reward_stream.reward_mint, ErrorCode::InvalidRewardStreamAccount);
+        require_keys_eq!(beneficiary_token_account.owner, beneficiary, ErrorCode::InvalidRewardStreamAccount);
+        if let Some(delegate_token_account) = &delegate_token_account {
+            require_keys_eq!(delegate_token_account.mint, reward_stream.reward_mint, ErrorCode::InvalidRewardStreamAccount);
+            require_keys_eq!(delegate_token_account.owner, stake_entry.reward_delegate, ErrorCode::InvalidRewardStreamAccount);
+        }
+
+        let checkpoint = &mut stake_entry.stream_checkpoints[index];
+        if checkpoint.stream_id != reward_stream.id {
+            // Left over from a closed stream in this slot; the entry predates this stream and
+            // has earned from its start
+            *checkpoint = StreamCheckpoint {
+                stream_id: reward_stream.id,
+                ..Default::default()
+            };
+        }
+        checkpoint.settle(reward_stream.cumulative_reward_per_nft)?;
+        let blocked = stream_payout_blocked(
+            &stream_vault,
+            &stream_mint,
+            &beneficiary_token_account,
+            delegate_token_account.as_ref(),
+        )?;
+        if blocked && stream_reward_ious.is_none() {
+            reward_stream.exit(&crate::ID)?;
+            continue;
+        }
+        let reward_amount = std::mem::take(&mut checkpoint.pending_reward);
+        reward_stream.exit(&crate::ID)?;
+        if reward_amount == 0 {
+            continue;
+        }
+        if let (true, Some(stream_reward_ious)) = (blocked, stream_reward_ious.as_mut()) {
+            defer_stream_reward(
+                pool.key(),
+                stream_reward_ious,
+                stake_entry,
+                beneficiary,
+                &reward_stream,
+                reward_amount,
+                events,
+            )?;
+            continue;
+        }
+
+        let (owner_amount, delegate_amount) = pay_reward_split(
+            pool,
+            stake_entry,
+            Some(&stream_vault),
+            Some(&stream_mint),
+            beneficiary_token_account.to_account_info(),
+            delegate_token_account.as_ref().map(|account| account.to_account_info()),
+            &token_program,
+            None,
+            reward_amount,
+        )?;
+        paid_any = true;
+
+        events.push(PayoutEvent::StreamRewardClaimed(StreamRewardClaimed {
+            pool: pool.key(),
+            user: beneficiary,
+            nft_mint: stake_entry.nft_mint,
+            reward_mint: reward_stream.reward_mint,
+            amount: owner_amount,
+        }));
+        if delegate_amount > 0 {
+            events.push(PayoutEvent::StreamRewardClaimed(StreamRewardClaimed {
+                pool: pool.key(),
+                user: stake_entry.reward_delegate,
+                nft_mint: stake_entry.nft_mint,
+                reward_mint: reward_stream.reward_mint,
+                amount: delegate_amount,
+            }));
+        }
+    }
+    Ok(paid_any)
+}
+
+/// Whether a stream payout would fail for reasons the staker cannot fix by passing other
+/// accounts: a frozen vault or destination, or a mint whose transfers need a hook or are
+/// disabled. The sponsor controls the mint, so this must not keep anyone from unstaking.
+fn stream_payout_blocked(
+    stream_vault: &InterfaceAccount<TokenAccount>,
+    stream_mint: &InterfaceAccount<Mint>,
+    beneficiary_token_account: &InterfaceAccount<TokenAccount>,
+    delegate_token_account: Option<&InterfaceAccount<TokenAccount>>,
+) -> Result<bool> {
+    let frozen = stream_vault.is_frozen()
+        || beneficiary_token_account.is_frozen()
+        || delegate_token_account.is_some_and(|account| account.is_frozen());
+    Ok(frozen || mint_blocks_transfers(&stream_mint.to_account_info())?)
+}
+
+/// Records a stream reward that cannot be paid right now, split like a payout: the delegate's
+/// share on the delegate's stream IOU and the rest on the owner's.
+fn defer_stream_reward<'info>(
+    pool: Pubkey,
+    stream_reward_ious: &mut StreamRewardIous<'_, 'info>,
+    stake_entry: &NftStakeEntry,
+    owner: Pubkey,
+    reward_stream: &Account<'info, RewardStream>,
+    amount: u64,
+    events: &mut Vec<PayoutEvent>,
+) -> Result<()> {
+    // A delegate that is also the owner would share the owner's IOU; there is nothing to split
+    let delegate_amount = if stake_entry.reward_delegate == owner {
+        0
+    } else {
+        stake_entry.delegate_share_of(amount)?
+    };
+    let owner_amount = amount - delegate_amount;
+
+    if delegate_amount > 0 {
+        let (stream_reward_iou, bump) = stream_reward_ious
+            .delegate
+            .as_mut()
+            .ok_or(ErrorCode::MissingStreamRewardIou)?;
+        record_stream_reward_owed(
+            pool,
+            stream_reward_iou,
+            stake_entry.reward_delegate,
+            *bump,
+            stake_entry.nft_mint,
+            reward_stream,
+            delegate_amount,
+            events,
+        )?;
+    }
+    if owner_amount > 0 {
+        let (stream_reward_iou, bump) = stream_reward_ious
+            .owner
+            .as_mut()
+            .ok_or(ErrorCode::MissingStreamRewardIou)?;
+        record_stream_reward_owed(
+            pool,
+            stream_reward_iou,
+            owner,
+            *bump,
+            stake_entry.nft_mint,
+            reward_stream,
+            owner_amount,
+            events,
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds `amount` of `reward_stream` to `user`'s stream IOU, initializing it on first use.
+#[allow(clippy::too_many_arguments)]
+fn record_stream_reward_owed<'info>(
+    pool: Pubkey,
+    stream_reward_iou: &mut StreamRewardIou,
+    user: Pubkey,
+    bump: u8,
+    nft_mint: Pubkey,
+    reward_stream: &Account<'info, RewardStream>,
+    amount: u64,
+    events: &mut Vec<PayoutEvent>,
+) -> Result<()> {
+    stream_reward_iou.user = user;
+    stream_reward_iou.bump = bump;
+    let owed = &mut stream_reward_iou.owed[reward_stream.index as usize];
+    if owed.stream_id != reward_stream.id {
+        // Left over from a stream that has since been closed, and its vault with it
+        *owed = StreamRewardOwed {
+            stream_id: reward_stream.id,
+            amount: 0,
+        };
+    }
+    owed.amount = owed
+        .amount
+        .checked_add(amount)
         .ok_or(ErrorCode::RewardCalculationError)?;
 
-    // Update the last calculated index
-    pool.last_update_calc_reward_nft_index = current_day;
+    events.push(PayoutEvent::StreamRewardDeferred(StreamRewardDeferred {
+        pool,
+        user,
+        nft_mint,
+        reward_stream: reward_stream.key(),
+        amount,
+        total_owed: owed.amount,
+    }));
+    Ok(())
+}
+
+/// Closes an optional stream IOU that ended up with nothing owed.
+fn close_empty_stream_reward_iou<'info>(
+    stream_reward_iou: Option<&Account<'info, StreamRewardIou>>,
+    rent_receiver: AccountInfo<'info>,
+) -> Result<()> {
+    match stream_reward_iou {
+        Some(stream_reward_iou) if stream_reward_iou.is_empty() => stream_reward_iou.close(rent_receiver),
+        _ => Ok(()),
+    }
+}
+
+/// CPI helper for programs that gate on staking (raffles, DAO voting, allowlists).
+#[cfg(feature = "cpi")]
+pub mod gating {
+    use super::*;
+
+    /// Calls `verify_stake` and returns its result; fails like it when the requirements are not
+    /// met. `stake_entries` are the user's `NftStakeEntry` accounts.
+    pub fn verify_stake<'info>(
+        nft_staking_program: AccountInfo<'info>,
+        pool: AccountInfo<'info>,
+        user: AccountInfo<'info>,
+        stake_entries: Vec<AccountInfo<'info>>,
+        requirements: StakeRequirements,
+    ) -> Result<StakeVerification> {
+        let cpi_ctx = CpiContext::new(nft_staking_program, crate::cpi::accounts::VerifyStake { pool, user })
+            .with_remaining_accounts(stake_entries);
+        Ok(crate::cpi::verify_stake(cpi_ctx, requirements)?.get())
+    }
+}
+
+// --- ACCOUNTS ---
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(init, payer = admin, space = Pool::INITIAL_ACCOUNT_SIZE, seeds = [b"pool"], bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    // Not needed for `RewardMode::Sol`
+    #[account(init, payer = admin, token::mint = reward_mint, token::authority = pool, seeds = [b"reward_vault"], bump)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    // Only needed for `RewardMode::Sol`
+    #[account(mut, seeds = [b"sol_vault"], bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddReward<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool"],
+        bump = pool.bump,
+        has_one = admin,
+        has_one = reward_mint,
+        realloc = 8 + Pool::ACCOUNT_SPACE,
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, associated_token::mint = reward_mint, associated_token::authority = admin, associated_token::token_program = token_program)]
+    pub admin_reward_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddSolReward<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool"],
+        bump = pool.bump,
+        has_one = admin,
+        realloc = 8 + Pool::ACCOUNT_SPACE,
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ScheduleEmission<'info> {
+    #[account(
+        mut,
+        seeds = [b"pool"],
+        bump = pool.bump,
+        has_one = admin,
+        realloc = 8 + Pool::ACCOUNT_SPACE,
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetEmissionCurve<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    pub admin: Signer<'info>,
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetUtilizationCurve<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetFixedRateTerms<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    pub admin: Signer<'info>,
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    // Only needed for `RewardMode::Sol`
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AddRewardStream<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + RewardStream::ACCOUNT_SPACE,
+        seeds = [b"reward_stream".as_ref(), &pool.next_reward_stream_id.to_le_bytes()],
+        bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    pub stream_reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = stream_reward_mint,
+        token::authority = pool,
+        token::token_program = token_program,
+        seeds = [b"stream_vault", reward_stream.key().as_ref()],
+        bump
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundRewardStream<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = reward_mint,
+        has_one = reward_vault,
+        seeds = [b"reward_stream".as_ref(), &reward_stream.id.to_le_bytes()],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    #[account(mut)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, associated_token::mint = reward_mint, associated_token::authority = admin, associated_token::token_program = token_program)]
+    pub admin_reward_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetCampaignMintsRestricted<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AllowCampaignMint<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CampaignMint::ACCOUNT_SPACE,
+        seeds = [b"campaign_mint", mint.key().as_ref()],
+        bump
+    )]
+    pub campaign_mint: Account<'info, CampaignMint>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DisallowCampaignMint<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"campaign_mint", campaign_mint.mint.as_ref()],
+        bump = campaign_mint.bump
+    )]
+    pub campaign_mint: Account<'info, CampaignMint>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CreateCampaign<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        init,
+        payer = funder,
+        space = 8 + RewardStream::ACCOUNT_SPACE,
+        seeds = [b"reward_stream".as_ref(), &pool.next_reward_stream_id.to_le_bytes()],
+        bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    // Only exists for mints the admin allowed; required if the pool restricts campaign mints
+    #[account(seeds = [b"campaign_mint", reward_mint.key().as_ref()], bump = campaign_mint.bump)]
+    pub campaign_mint: Option<Account<'info, CampaignMint>>,
+    #[account(
+        init,
+        payer = funder,
+        token::mint = reward_mint,
+        token::authority = pool,
+        token::token_program = token_program,
+        seeds = [b"stream_vault", reward_stream.key().as_ref()],
+        bump
+    )]
+    pub campaign_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = reward_mint, token::authority = funder, token::token_program = token_program)]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReclaimCampaignRewards<'info> {
+    pub funder: Signer<'info>,
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        has_one = funder,
+        has_one = reward_mint,
+        constraint = reward_stream.is_campaign() @ ErrorCode::NotACampaign,
+        constraint = reward_stream.reward_vault == campaign_vault.key() @ ErrorCode::InvalidRewardStreamAccount,
+        seeds = [b"reward_stream".as_ref(), &reward_stream.id.to_le_bytes()],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub campaign_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = reward_mint, token::authority = funder, token::token_program = token_program)]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CloseCampaign<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        close = funder,
+        has_one = funder,
+        has_one = reward_mint,
+        constraint = reward_stream.is_campaign() @ ErrorCode::NotACampaign,
+        constraint = reward_stream.reward_vault == campaign_vault.key() @ ErrorCode::InvalidRewardStreamAccount,
+        seeds = [b"reward_stream".as_ref(), &reward_stream.id.to_le_bytes()],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub campaign_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = reward_mint, token::authority = funder, token::token_program = token_program)]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ManageCollection<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [b"metadata", mpl_token_metadata::ID.as_ref(), nft_mint.key().as_ref()],
+        seeds::program = mpl_token_metadata::ID,
+        bump
+    )]
+    /// CHECK: We deserialize this manually and verify its properties in the instruction.
+    pub nft_metadata_account: UncheckedAccount<'info>,
+    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    #[account(mut, associated_token::mint = nft_mint, associated_token::authority = user, associated_token::token_program = token_program)]
+    pub user_nft_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(init, payer = user, token::mint = nft_mint, token::authority = stake_entry, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub nft_vault: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // Only needed for `RewardStrategy::FixedRate`, to check the vault covers the new position
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // Only needed for `RewardStrategy::FixedRate` pools in `RewardMode::Sol`
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, close = user, has_one = user, has_one = nft_mint, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump = stake_entry.bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    #[account(mut, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub nft_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(init_if_needed, payer = user, associated_token::mint = nft_mint, associated_token::authority = user, associated_token::token_program = token_program)]
+    pub user_nft_token_account: InterfaceAccount<'info, TokenAccount>, 
+    // Accounts for claiming rewards, left out with the reward vault when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)] // Add constraint to ensure it's the correct reward mint
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>, 
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint, // Reference the reward_mint account
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program
+    )]
+    pub user_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = pool.reward_vault)] // Add reward_vault to unstake context
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    // Receives the reward delegate's share, required when the entry has one
+    #[account(mut, token::mint = reward_mint, token::authority = stake_entry.reward_delegate)]
+    pub delegate_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The entry's reward delegate, paid its share directly when the pool pays in SOL.
+    #[account(mut, address = stake_entry.reward_delegate)]
+    pub reward_delegate: Option<UncheckedAccount<'info>>,
+    // Records rewards the vault could not cover; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub reward_iou: Account<'info, RewardIou>,
+    // The reward delegate's IOU, required when part of the delegate's share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_reward_iou: Option<Box<Account<'info, RewardIou>>>,
+    // Records stream rewards a stream cannot pay right now; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    // The reward delegate's stream IOU, required when part of its share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The reward mint's program, which can differ from the NFT's
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct StakeWithReceipt<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    pub nft_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [b"metadata", mpl_token_metadata::ID.as_ref(), nft_mint.key().as_ref()],
+        seeds::program = mpl_token_metadata::ID,
+        bump
+    )]
+    /// CHECK: We deserialize this manually and verify its properties in the instruction.
+    pub nft_metadata_account: UncheckedAccount<'info>,
+    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub stake_entry: Box<Account<'info, NftStakeEntry>>,
+    #[account(mut, associated_token::mint = nft_mint, associated_token::authority = user, associated_token::token_program = token_program)]
+    pub user_nft_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(init, payer = user, token::mint = nft_mint, token::authority = stake_entry, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub nft_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Created or validated in the instruction; one receipt mint per NFT mint.
+    #[account(mut, seeds = [b"receipt_mint", nft_mint.key().as_ref()], bump)]
+    pub receipt_mint: UncheckedAccount<'info>,
+    /// CHECK: The user's receipt ATA, created idempotently by the associated token program.
+    #[account(mut)]
+    pub user_receipt_token_account: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    // Only needed for `RewardStrategy::FixedRate`, to check the vault covers the new position
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // Only needed for `RewardStrategy::FixedRate` pools in `RewardMode::Sol`
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimRewardWithReceipt<'info> {
+    #[account(mut)]
+    pub holder: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)]
+    pub reward_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        mut,
+        seeds = [b"stake_entry", stake_entry.user.as_ref(), stake_entry.nft_mint.as_ref()],
+        bump = stake_entry.bump
+    )]
+    pub stake_entry: Box<Account<'info, NftStakeEntry>>,
+    #[account(address = stake_entry.receipt_mint @ ErrorCode::InvalidReceiptMint)]
+    pub receipt_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        token::mint = receipt_mint,
+        token::authority = holder,
+        constraint = holder_receipt_token_account.amount == 1 @ ErrorCode::NotReceiptHolder
+    )]
+    pub holder_receipt_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        associated_token::mint = reward_mint,
+        associated_token::authority = holder,
+        associated_token::token_program = token_program
+    )]
+    pub holder_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UnstakeWithReceipt<'info> {
+    #[account(mut)]
+    pub holder: Signer<'info>,
+    // The original staker, who gets back the rent of the stake entry and the vault
+    #[account(mut, address = stake_entry.user)]
+    pub user: SystemAccount<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    pub nft_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, close = user, has_one = nft_mint, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump = stake_entry.bump)]
+    pub stake_entry: Box<Account<'info, NftStakeEntry>>,
+    #[account(mut, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub nft_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(init_if_needed, payer = holder, associated_token::mint = nft_mint, associated_token::authority = holder, associated_token::token_program = token_program)]
+    pub holder_nft_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = stake_entry.receipt_mint @ ErrorCode::InvalidReceiptMint)]
+    pub receipt_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+        token::authority = holder,
+        constraint = holder_receipt_token_account.amount == 1 @ ErrorCode::NotReceiptHolder
+    )]
+    pub holder_receipt_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)]
+    pub reward_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        associated_token::mint = reward_mint,
+        associated_token::authority = holder,
+        associated_token::token_program = reward_token_program
+    )]
+    pub holder_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = holder,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", holder.key().as_ref()],
+        bump
+    )]
+    pub reward_iou: Box<Account<'info, RewardIou>>,
+    // Records stream rewards a stream cannot pay right now; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = holder,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", holder.key().as_ref()],
+        bump
+    )]
+    pub stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The reward mint's program, which can differ from the NFT's
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub receipt_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct StakeInPlace<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [b"metadata", mpl_token_metadata::ID.as_ref(), nft_mint.key().as_ref()],
+        seeds::program = mpl_token_metadata::ID,
+        bump
+    )]
+    /// CHECK: We deserialize this manually and verify its properties in the instruction.
+    pub nft_metadata_account: UncheckedAccount<'info>,
+    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    // The NFT stays here, frozen, for the whole stake
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+        constraint = user_nft_token_account.amount == 1 @ ErrorCode::NotAssetOwner
+    )]
+    pub user_nft_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // Only needed for `RewardStrategy::FixedRate`, to check the vault covers the new position
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // Only needed for `RewardStrategy::FixedRate` pools in `RewardMode::Sol`
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UnstakeInPlace<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    pub nft_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, close = user, has_one = user, has_one = nft_mint, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump = stake_entry.bump)]
+    pub stake_entry: Box<Account<'info, NftStakeEntry>>,
+    #[account(mut, associated_token::mint = nft_mint, associated_token::authority = user, associated_token::token_program = token_program)]
+    pub user_nft_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)]
+    pub reward_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program
+    )]
+    pub user_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(mut, token::mint = reward_mint, token::authority = stake_entry.reward_delegate)]
+    pub delegate_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: The entry's reward delegate, paid its share directly when the pool pays in SOL.
+    #[account(mut, address = stake_entry.reward_delegate)]
+    pub reward_delegate: Option<UncheckedAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub reward_iou: Box<Account<'info, RewardIou>>,
+    // The reward delegate's IOU, required when part of the delegate's share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_reward_iou: Option<Box<Account<'info, RewardIou>>>,
+    // Records stream rewards a stream cannot pay right now; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    // The reward delegate's stream IOU, required when part of its share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The reward mint's program, which can differ from the NFT's
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
 
-    Ok(())
+#[event_cpi]
+#[derive(Accounts)]
+pub struct StakeCore<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    /// CHECK: Deserialized as a Core asset in the instruction.
+    #[account(mut, owner = MPL_CORE_ID)]
+    pub asset: UncheckedAccount<'info>,
+    /// CHECK: Must be the asset's collection, checked in the instruction.
+    #[account(owner = MPL_CORE_ID)]
+    pub collection: UncheckedAccount<'info>,
+    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", user.key().as_ref(), asset.key().as_ref()], bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    /// CHECK: Address-checked Metaplex Core program.
+    #[account(address = MPL_CORE_ID)]
+    pub mpl_core_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    // Only needed for `RewardStrategy::FixedRate`, to check the vault covers the new position
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    // Only needed for `RewardStrategy::FixedRate` pools in `RewardMode::Sol`
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UnstakeCore<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    /// CHECK: The staked asset, enforced by the `stake_entry` constraint.
+    #[account(mut, owner = MPL_CORE_ID)]
+    pub asset: UncheckedAccount<'info>,
+    /// CHECK: The asset's collection; Metaplex Core validates it during the transfer.
+    #[account(owner = MPL_CORE_ID)]
+    pub collection: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = user,
+        has_one = user,
+        constraint = stake_entry.nft_mint == asset.key() @ ErrorCode::WrongCustody,
+        seeds = [b"stake_entry", user.key().as_ref(), asset.key().as_ref()],
+        bump = stake_entry.bump
+    )]
+    pub stake_entry: Box<Account<'info, NftStakeEntry>>,
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)]
+    pub reward_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(mut, token::mint = reward_mint, token::authority = stake_entry.reward_delegate)]
+    pub delegate_reward_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: The entry's reward delegate, paid its share directly when the pool pays in SOL.
+    #[account(mut, address = stake_entry.reward_delegate)]
+    pub reward_delegate: Option<UncheckedAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub reward_iou: Box<Account<'info, RewardIou>>,
+    // The reward delegate's IOU, required when part of the delegate's share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_reward_iou: Option<Box<Account<'info, RewardIou>>>,
+    // Records stream rewards a stream cannot pay right now; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    // The reward delegate's stream IOU, required when part of its share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    /// CHECK: Address-checked Metaplex Core program.
+    #[account(address = MPL_CORE_ID)]
+    pub mpl_core_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct TransferStake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Any wallet can receive a position; it only seeds the new PDAs.
+    pub new_owner: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    pub nft_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, close = user, has_one = user, has_one = nft_mint, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump = stake_entry.bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    #[account(mut, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub nft_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", new_owner.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub new_stake_entry: Account<'info, NftStakeEntry>,
+    #[account(init, payer = user, token::mint = nft_mint, token::authority = new_stake_entry, seeds = [b"nft_vault", new_owner.key().as_ref(), nft_mint.key().as_ref()], bump)]
+    pub new_nft_vault: InterfaceAccount<'info, TokenAccount>,
+    // Accounts for paying out the rewards earned by the current owner, left out with the
+    // reward vault when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)]
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program
+    )]
+    pub user_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = stake_entry.reward_delegate,
+        token::token_program = reward_token_program
+    )]
+    pub delegate_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The entry's reward delegate, paid its share directly when the pool pays in SOL.
+    #[account(mut, address = stake_entry.reward_delegate)]
+    pub reward_delegate: Option<UncheckedAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub reward_iou: Account<'info, RewardIou>,
+    // The reward delegate's IOU, required when part of the delegate's share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RewardIou::ACCOUNT_SPACE,
+        seeds = [b"reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_reward_iou: Option<Box<Account<'info, RewardIou>>>,
+    // Records stream rewards a stream cannot pay right now; closed again if nothing is owed
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", user.key().as_ref()],
+        bump
+    )]
+    pub stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    // The reward delegate's stream IOU, required when part of its share is deferred
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StreamRewardIou::ACCOUNT_SPACE,
+        seeds = [b"stream_reward_iou", stake_entry.reward_delegate.as_ref()],
+        bump,
+        constraint = stake_entry.reward_delegate != user.key() @ ErrorCode::InvalidRewardDelegate
+    )]
+    pub delegate_stream_reward_iou: Option<Box<Account<'info, StreamRewardIou>>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The reward mint's program, which can differ from the NFT's
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimReward<'info> {
+    // Either the entry's owner or its reward delegate
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: The stake entry's owner, enforced by `has_one = user` on `stake_entry`. Paid
+    /// directly when the pool pays in SOL.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    // The Mint account for the reward token, required for init_if_needed on user_reward_token_account.
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(mut, address = pool.reward_mint)] // Add constraint to ensure it's the correct reward mint
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>, 
+    /// CHECK: The staked item (NFT mint or Core asset), enforced by `has_one = nft_mint` on `stake_entry`.
+    pub nft_mint: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        has_one = user,
+        has_one = nft_mint, // Ensure this stake_entry belongs to this user and NFT
+        seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()],
+        bump = stake_entry.bump
+    )]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    // The lamport vault, required when the pool pays its rewards in SOL
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = reward_mint, // Reference the reward_mint account
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    // Optional destination for the claim, e.g. a cold wallet or an exchange deposit account
+    #[account(mut, token::mint = reward_mint)]
+    pub recipient_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    // Receives the reward delegate's share, required when the entry has one
+    #[account(mut, token::mint = reward_mint, token::authority = stake_entry.reward_delegate)]
+    pub delegate_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The entry's reward delegate, paid its share directly when the pool pays in SOL.
+    #[account(mut, address = stake_entry.reward_delegate)]
+    pub reward_delegate: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct GetPendingRewards<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    #[account(seeds = [b"stake_entry", stake_entry.user.as_ref(), stake_entry.nft_mint.as_ref()], bump = stake_entry.bump)]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+}
+
+#[derive(Accounts)]
+pub struct GetPoolStats<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    #[account(address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigratePool<'info> {
+    /// CHECK: Still in an old layout, so parsed by the instruction.
+    #[account(mut, seeds = [b"pool"], bump, owner = crate::ID)]
+    pub pool: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MigrateStakeEntry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    /// CHECK: Still in an old layout, so parsed and checked against its PDA by the instruction.
+    #[account(mut, owner = crate::ID)]
+    pub stake_entry: UncheckedAccount<'info>,
+    /// CHECK: Metaplex metadata of the staked NFT; checked against the entry's mint by the
+    /// instruction.
+    pub nft_metadata_account: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyStake<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Box<Account<'info, Pool>>,
+    /// CHECK: Only compared against the stake entries' owner.
+    pub user: UncheckedAccount<'info>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ConfigureVoterWeight<'info> {
+    #[account(seeds = [b"pool"], bump = pool.bump, has_one = admin)]
+    pub pool: Box<Account<'info, Pool>>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + VoterWeightConfig::ACCOUNT_SPACE,
+        seeds = [b"voter_weight_config"],
+        bump
+    )]
+    pub voter_weight_config: Box<Account<'info, VoterWeightConfig>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(governing_token_owner: Pubkey)]
+pub struct CreateVoterWeightRecord<'info> {
+    #[account(seeds = [b"voter_weight_config"], bump = voter_weight_config.bump)]
+    pub voter_weight_config: Box<Account<'info, VoterWeightConfig>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VoterWeightRecord::ACCOUNT_SPACE,
+        seeds = [
+            b"voter-weight-record".as_ref(),
+            voter_weight_config.settings.realm.as_ref(),
+            voter_weight_config.settings.governing_token_mint.as_ref(),
+            governing_token_owner.as_ref(),
+        ],
+        bump
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct UpdateVoterWeightRecord<'info> {
+    #[account(seeds = [b"voter_weight_config"], bump = voter_weight_config.bump)]
+    pub voter_weight_config: Box<Account<'info, VoterWeightConfig>>,
+    #[account(
+        mut,
+        seeds = [
+            b"voter-weight-record".as_ref(),
+            voter_weight_config.settings.realm.as_ref(),
+            voter_weight_config.settings.governing_token_mint.as_ref(),
+            governing_token_owner.key().as_ref(),
+        ],
+        bump
+    )]
+    pub voter_weight_record: Account<'info, VoterWeightRecord>,
+    /// Pays for the `NftVoteRecord`s when casting a vote.
+    #[account(mut)]
+    pub governing_token_owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetRewardDelegate<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        has_one = user, // Only the owner can change the delegate or the split
+        seeds = [b"stake_entry", user.key().as_ref(), stake_entry.nft_mint.as_ref()],
+        bump = stake_entry.bump
+    )]
+    pub stake_entry: Account<'info, NftStakeEntry>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimOwed<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    // Left out, with the reward vault and token accounts, when the pool pays in SOL
+    #[account(address = pool.reward_mint)]
+    pub reward_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut, has_one = user, seeds = [b"reward_iou", user.key().as_ref()], bump = reward_iou.bump)]
+    pub reward_iou: Account<'info, RewardIou>,
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: Option<SystemAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_reward_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ClaimOwedStreamReward<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut, has_one = user, seeds = [b"stream_reward_iou", user.key().as_ref()], bump = stream_reward_iou.bump)]
+    pub stream_reward_iou: Account<'info, StreamRewardIou>,
+    #[account(
+        has_one = reward_mint,
+        constraint = reward_stream.reward_vault == stream_vault.key() @ ErrorCode::InvalidRewardStreamAccount,
+        seeds = [b"reward_stream".as_ref(), &reward_stream.id.to_le_bytes()],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Box<Account<'info, RewardStream>>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Accounts for `admin_claim`.
+#[event_cpi]
+#[derive(Accounts)]
+pub struct AdminClaim<'info> {
+    /// The pool, naming its admin and reward vault.
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin, has_one = reward_mint)]
+    pub pool: Account<'info, Pool>,
+    /// The pool's admin, who must sign.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// The pool's reward vault.
+    #[account(mut, address = pool.reward_vault)]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    /// The pool's reward mint.
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    /// The admin's reward token account, which receives the withdrawal.
+    #[account(mut, associated_token::mint = reward_mint, associated_token::authority = admin, associated_token::token_program = token_program)]
+    pub admin_reward_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// The reward mint's token program.
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
-
-// --- ACCOUNTS ---
-
+#[event_cpi]
 #[derive(Accounts)]
-pub struct InitializePool<'info> {
-    #[account(init, payer = admin, space = 8 + Pool::ACCOUNT_SPACE, seeds = [b"pool"], bump)]
+pub struct AdminClaimSol<'info> {
+    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
     pub pool: Account<'info, Pool>,
     #[account(mut)]
     pub admin: Signer<'info>,
-    pub reward_mint: InterfaceAccount<'info, Mint>,
-    #[account(init, payer = admin, token::mint = reward_mint, token::authority = pool, seeds = [b"reward_vault"], bump)]
-    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, seeds = [b"sol_vault"], bump = pool.sol_vault_bump)]
+    pub sol_vault: SystemAccount<'info>,
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
 }
 
-#[derive(Accounts)]
-pub struct AddReward<'info> {
-    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin, has_one = reward_mint)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut, address = pool.reward_vault)]
-    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
-    pub reward_mint: InterfaceAccount<'info, Mint>,
-    #[account(mut, associated_token::mint = reward_mint, associated_token::authority = admin)]
-    pub admin_reward_token_account: InterfaceAccount<'info, TokenAccount>,
-    pub token_program: Interface<'info, TokenInterface>,
+// --- DATA STRUCTS ---
+
+#[account]
+pub struct Pool {
+    pub version: u8, // Layout version, see `migrate_pool`
+    pub admin: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub fixed_rate_terms: FixedRateTerms, // Only used by `RewardStrategy::FixedRate`
+    pub reward_strategy: RewardStrategy, // Fixed at initialize_pool
+    pub total_shares: u64, // Sum of every staked NFT's shares under `reward_strategy`
+    pub accrued_until: i64, // How far `TimeWeighted` and `FixedRate` accrual has got
+    pub allowed_collections: Vec<AllowedCollection>,
+    pub total_staked: u64,
+    pub total_funded: u64, // Rewards ever deposited (or scheduled in `Mint` mode)
+    pub total_distributed: u64, // Rewards ever handed to stakers through the accumulator
+    pub total_claimed: u64, // Rewards ever paid out to stakers
+    pub total_admin_withdrawn: u64,
+    pub stake_seconds: u64, // Integral of total_staked over time, in NFT-seconds
+    pub stake_seconds_updated_at: i64,
+    pub epoch_duration: i64,
+    pub rewards_per_epoch: Vec<u64>,
+    pub bump: u8,
+    pub start_staking_timestamp: i64, // When staking officially started; day 0 begins here
+    pub cumulative_reward_per_nft: u64, // Total reward per share accrued up to the last update
+    pub last_update_calc_reward_nft_index: u64, // Day up to which cumulative_reward_per_nft is accrued
+    pub reward_streams: [Pubkey; 4], // Active extra reward streams and campaigns by slot; default = free
+    pub next_reward_stream_id: u64, // Seeds the next `RewardStream`; ids are never reused
+    pub campaign_mints_restricted: bool, // Campaigns only in mints allowed with `allow_campaign_mint`
+    pub reward_mode: RewardMode, // Fixed at initialize_pool
+    pub emission_cap: u64, // Most the pool may ever mint in Mint mode
+    pub total_scheduled: u64, // Sum of the schedule in Mint mode, never above emission_cap
+    pub total_minted: u64,
+    pub sol_vault_bump: u8, // System-owned PDA holding the lamports in Sol mode
+    pub emission_schedule: Option<EmissionSchedule>, // Curve paid on top of rewards_per_epoch
+    pub outstanding_rewards: u64, // Accrued to stakers but not yet paid out
+    pub utilization_curve: Option<UtilizationCurve>, // Scales daily emissions by staked share of supply
+    pub surplus_rewards: u64, // Offered by the schedule and curve but never handed to stakers
+}
+impl Pool {
+    pub const MAX_EPOCHS: usize = 1200;
+    pub const MAX_COLLECTIONS: usize = 2; // Increased to 2 for example
+    pub const MAX_REWARD_STREAMS: usize = 4;
+    /// Bumped whenever the layout changes; `migrate_pool` converts older accounts.
+    pub const VERSION: u8 = 1;
+    /// `initialize_pool` allocates at most what a CPI may; the instructions that extend the
+    /// reward table grow the pool to its full size.
+    pub const INITIAL_ACCOUNT_SIZE: usize = if 8 + Self::ACCOUNT_SPACE < MAX_PERMITTED_DATA_INCREASE {
+        8 + Self::ACCOUNT_SPACE
+    } else {
+        MAX_PERMITTED_DATA_INCREASE
+    };
+    pub const ACCOUNT_SPACE: usize = 8
+        + 1  // version
+        + 32 // admin
+        + 32 // reward_mint
+        + 32 // reward_vault
+        + FixedRateTerms::SPACE // fixed_rate_terms
+        + RewardStrategy::SPACE // reward_strategy
+        + 8  // total_shares
+        + 8  // accrued_until
+        + (4 + AllowedCollection::SPACE * Self::MAX_COLLECTIONS) // allowed_collections
+        + 8  // total_staked
+        + 8  // total_funded
+        + 8  // total_distributed
+        + 8  // total_claimed
+        + 8  // total_admin_withdrawn
+        + 8  // stake_seconds
+        + 8  // stake_seconds_updated_at
+        + 8  // epoch_duration
+        + (4 + 8 * Self::MAX_EPOCHS) // rewards_per_epoch
+        + 1  // bump
+        + 8  // start_staking_timestamp
+        + 8  // cumulative_reward_per_nft
+        + 8  // last_update_calc_reward_nft_index
+        + 32 * Self::MAX_REWARD_STREAMS // reward_streams
+        + 8  // next_reward_stream_id
+        + 1  // campaign_mints_restricted
+        + 1  // reward_mode
+        + 8  // emission_cap
+        + 8  // total_scheduled
+        + 8  // total_minted
+        + 1  // sol_vault_bump
+        + (1 + EmissionSchedule::SPACE) // emission_schedule
+        + 8  // outstanding_rewards
+        + (1 + UtilizationCurve::SPACE) // utilization_curve
+        + 8; // surplus_rewards
+}
+impl Pool {
+    /// Slots of `reward_streams` holding an active stream, in order.
+    pub fn active_stream_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::MAX_REWARD_STREAMS).filter(|&index| self.reward_streams[index] != Pubkey::default())
+    }
+
+    /// Books rewards paid through `pay_from_reward_vault`: they are no longer outstanding and,
+    /// in `Mint` mode, count against the emission cap.
+    pub fn record_payout(&mut self, amount: u64) -> Result<()> {
+        // Rewards accrued before the counter existed were never added to it
+        self.outstanding_rewards = self.outstanding_rewards.saturating_sub(amount);
+        self.total_claimed = self
+            .total_claimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        if self.reward_mode == RewardMode::Mint {
+            self.total_minted = self
+                .total_minted
+                .checked_add(amount)
+                .ok_or(ErrorCode::RewardCalculationError)?;
+            require_gte!(self.emission_cap, self.total_minted, ErrorCode::EmissionCapExceeded);
+        }
+        Ok(())
+    }
+
+    /// What the pool owes or has promised from `current_day` on: rewards accrued but not paid,
+    /// the rest of the day-by-day schedule and the rest of the emission curve. Under
+    /// `RewardStrategy::FixedRate` the promise is the fixed rates instead.
+    pub fn committed_rewards(&self, current_day: u64) -> Result<u64> {
+        if let RewardStrategy::FixedRate { .. } = self.reward_strategy {
+            return self.fixed_rate_liabilities();
+        }
+        let scheduled: u128 = self
+            .rewards_per_epoch
+            .iter()
+            .skip(current_day as usize)
+            .map(|&reward| reward as u128)
+            .sum();
+        let curve = match &self.emission_schedule {
+            Some(schedule) => schedule.total_between(current_day, u64::MAX)?,
+            None => 0,
+        };
+        u64::try_from(scheduled + curve as u128 + self.outstanding_rewards as u128)
+            .map_err(|_| error!(ErrorCode::RewardCalculationError))
+    }
+
+    /// Counts a new position in the pool and in its collection.
+    pub fn record_stake(&mut self, collection: &Pubkey, now: i64) -> Result<()> {
+        // Catch-up shares each day by what is staked over it, so it runs before that changes
+        accrue_pool_until(self, now)?;
+        self.accrue_stake_seconds(now);
+        self.total_staked = self
+            .total_staked
+            .checked_add(1)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.total_shares = self
+            .total_shares
+            .checked_add(self.shares_of(collection))
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        if let Some(c) = self.allowed_collections.iter_mut().find(|c| c.collection == *collection) {
+            c.staked += 1;
+        }
+        Ok(())
+    }
+
+    /// Reverses `record_stake`. Positions of a since-removed collection only leave the total.
+    pub fn record_unstake(&mut self, collection: &Pubkey, now: i64) -> Result<()> {
+        accrue_pool_until(self, now)?;
+        self.accrue_stake_seconds(now);
+        self.total_staked = self
+            .total_staked
+            .checked_sub(1)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.total_shares = self
+            .total_shares
+            .checked_sub(self.shares_of(collection))
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        if let Some(c) = self.allowed_collections.iter_mut().find(|c| c.collection == *collection) {
+            c.staked = c.staked.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Shares one staked NFT of `collection` holds under `reward_strategy`.
+    pub fn shares_of(&self, collection: &Pubkey) -> u64 {
+        match &self.reward_strategy {
+            RewardStrategy::EqualShare | RewardStrategy::TimeWeighted => 1,
+            RewardStrategy::FixedRate { reward_per_day } => self
+                .fixed_rate_terms
+                .collection_rates
+                .iter()
+                .find(|r| r.collection == *collection && *collection != Pubkey::default())
+                .map_or(*reward_per_day, |r| r.reward_per_day),
+            RewardStrategy::WeightedShare { weights } => weights
+                .iter()
+                .find(|w| w.collection == *collection && *collection != Pubkey::default())
+                .map_or(1, |w| w.weight as u64),
+        }
+    }
+
+    /// What `shares` earn for a `per_share` increase of `cumulative_reward_per_nft`. Under
+    /// `RewardStrategy::FixedRate` the accumulator counts seconds, at one token per share per day.
+    pub fn reward_for(&self, per_share: u64, shares: u64) -> Result<u64> {
+        match self.reward_strategy {
+            RewardStrategy::FixedRate { .. } => fixed_rate_reward(per_share, shares, false),
+            _ => per_share
+                .checked_mul(shares)
+                .ok_or(error!(ErrorCode::RewardCalculationError)),
+        }
+    }
+
+    /// Under `RewardStrategy::FixedRate`, what the pool owes plus what the NFTs staked now will
+    /// earn from `accrued_until` until the campaign ends, rounded up to the token.
+    pub fn fixed_rate_liabilities(&self) -> Result<u64> {
+        let remaining_seconds =
+            (self.fixed_rate_end_time() - self.accrued_until.max(self.start_staking_timestamp)).max(0) as u64;
+        fixed_rate_reward(remaining_seconds, self.total_shares, true)?
+            .checked_add(self.outstanding_rewards)
+            .ok_or(error!(ErrorCode::RewardCalculationError))
+    }
+
+    /// When a `RewardStrategy::FixedRate` campaign stops accruing: the start of its `end_day`.
+    pub fn fixed_rate_end_time(&self) -> i64 {
+        self.start_staking_timestamp + self.fixed_rate_terms.end_day as i64 * 86400
+    }
+
+    /// Brings `stake_seconds` up to `now`; called before every change to `total_staked`.
+    pub fn accrue_stake_seconds(&mut self, now: i64) {
+        let elapsed = now.saturating_sub(self.stake_seconds_updated_at).max(0) as u64;
+        self.stake_seconds = self
+            .stake_seconds
+            .saturating_add(self.total_staked.saturating_mul(elapsed));
+        self.stake_seconds_updated_at = now;
+    }
+
+    fn add_funding(&mut self, amount: u64) -> Result<()> {
+        self.total_funded = self
+            .total_funded
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        Ok(())
+    }
+
+    fn add_surplus(&mut self, amount: u128) -> Result<()> {
+        self.surplus_rewards = u64::try_from(amount)
+            .ok()
+            .and_then(|amount| self.surplus_rewards.checked_add(amount))
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        Ok(())
+    }
+
+    pub fn is_collection_allowed(&self, collection: &Pubkey, kind: CollectionKind) -> bool {
+        self.allowed_collections
+            .iter()
+            .any(|c| c.collection == *collection && c.kind == kind)
+    }
+}
+
+/// Where the pool's own reward comes from.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RewardMode {
+    /// Paid from `reward_vault`, which the admin pre-funds with `add_reward`.
+    Vault,
+    /// Minted on payout with the pool as mint authority, following `schedule_emission`.
+    Mint,
+    /// Lamports paid from the `sol_vault` PDA, funded with `add_sol_reward`.
+    Sol,
+}
+
+/// How the pool turns what it offers into per-NFT rewards. Fixed at `initialize_pool`;
+/// `accrue_pool_until` dispatches on it and `settle_stake_entry` pays each entry by its
+/// shares. Reward streams split each day equally among the NFTs staked at its end, so only
+/// `EqualShare` pools take them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RewardStrategy {
+    /// Each day's rewards are split equally among the NFTs staked at its end.
+    EqualShare,
+    /// Each day's rewards are released evenly over its seconds and split among the NFTs
+    /// staked during each of them.
+    TimeWeighted,
+    /// Every staked NFT earns a fixed amount per day, accrued by the second, however many are
+    /// staked: its collection's rate from `Pool::fixed_rate_terms`, or else `reward_per_day`.
+    /// One share is one token of daily rate.
+    FixedRate { reward_per_day: u64 },
+    /// Like `EqualShare`, but an NFT holds its collection's weight in shares instead of one.
+    /// Collections without a weight count one share per NFT.
+    WeightedShare { weights: [CollectionWeight; 2] },
+}
+impl RewardStrategy {
+    pub const SPACE: usize = 1 + CollectionWeight::SPACE * Pool::MAX_COLLECTIONS;
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::EqualShare | Self::TimeWeighted => {}
+            Self::FixedRate { reward_per_day } => {
+                require_gt!(*reward_per_day, 0, ErrorCode::InvalidRewardStrategy);
+            }
+            Self::WeightedShare { weights } => {
+                let used: Vec<_> = weights.iter().filter(|w| w.collection != Pubkey::default()).collect();
+                require!(used.iter().all(|w| w.weight > 0), ErrorCode::InvalidRewardStrategy);
+                require!(
+                    used.len() < 2 || used[0].collection != used[1].collection,
+                    ErrorCode::InvalidRewardStrategy
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Terms of a `RewardStrategy::FixedRate` campaign, set with `set_fixed_rate_terms`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FixedRateTerms {
+    pub collection_rates: [CollectionRate; 2], // Override `reward_per_day` for these collections
+    pub liability_cap: u64, // Most the pool may owe and promise at once; 0 for no cap beyond the vault
+    pub end_day: u64, // Rewards accrue until this pool day; stakes are refused from then on
+}
+impl FixedRateTerms {
+    pub const SPACE: usize = CollectionRate::SPACE * Pool::MAX_COLLECTIONS + 8 + 8;
+
+    pub fn validate(&self) -> Result<()> {
+        let used: Vec<_> = self
+            .collection_rates
+            .iter()
+            .filter(|r| r.collection != Pubkey::default())
+            .collect();
+        require!(used.iter().all(|r| r.reward_per_day > 0), ErrorCode::InvalidFixedRateTerms);
+        require!(
+            used.len() < 2 || used[0].collection != used[1].collection,
+            ErrorCode::InvalidFixedRateTerms
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CollectionRate {
+    pub collection: Pubkey, // Default for an unused slot
+    pub reward_per_day: u64,
+}
+impl CollectionRate {
+    pub const SPACE: usize = 32 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct CollectionWeight {
+    pub collection: Pubkey, // Default for an unused slot
+    pub weight: u16,
+}
+impl CollectionWeight {
+    pub const SPACE: usize = 32 + 2;
+}
+
+/// An emission curve active on the pool days `[start_day, end_day)`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct EmissionSchedule {
+    pub curve: EmissionCurve,
+    pub start_day: u64,
+    pub end_day: u64,
+}
+impl EmissionSchedule {
+    pub const SPACE: usize = EmissionCurve::SPACE + 8 + 8;
+
+    fn validate(&self, current_day: u64) -> Result<()> {
+        require_gte!(self.start_day, current_day, ErrorCode::InvalidEmissionCurve);
+        require_gt!(self.end_day, self.start_day, ErrorCode::InvalidEmissionCurve);
+        match &self.curve {
+            EmissionCurve::Halving { halving_interval, .. } => {
+                require_gt!(*halving_interval, 0, ErrorCode::InvalidEmissionCurve);
+            }
+            EmissionCurve::Piecewise { segments } => {
+                require!(
+                    !segments.is_empty() && segments.len() <= EmissionCurve::MAX_SEGMENTS,
+                    ErrorCode::InvalidEmissionCurve
+                );
+                require_eq!(segments[0].start_offset, 0, ErrorCode::InvalidEmissionCurve);
+                require!(
+                    segments.windows(2).all(|pair| pair[0].start_offset < pair[1].start_offset),
+                    ErrorCode::InvalidEmissionCurve
+                );
+            }
+            EmissionCurve::Constant { .. } | EmissionCurve::LinearDecay { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Total emitted on the pool days `[from_day, to_day)`.
+    pub fn total_between(&self, from_day: u64, to_day: u64) -> Result<u64> {
+        let from_day = from_day.max(self.start_day);
+        let to_day = to_day.min(self.end_day);
+        if from_day >= to_day {
+            return Ok(0);
+        }
+        self.curve
+            .total_between(from_day - self.start_day, to_day - self.start_day)
+    }
+}
+
+/// Daily emission as a function of days since the curve started.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub enum EmissionCurve {
+    /// `daily` every day.
+    Constant { daily: u64 },
+    /// `start_daily`, then `decay_per_day` less each day until it reaches zero.
+    LinearDecay { start_daily: u64, decay_per_day: u64 },
+    /// `start_daily`, halved every `halving_interval` days.
+    Halving { start_daily: u64, halving_interval: u64 },
+    /// Each segment's `daily` from its `start_offset` until the next segment starts.
+    Piecewise { segments: Vec<CurveSegment> },
+}
+impl EmissionCurve {
+    pub const MAX_SEGMENTS: usize = 4;
+    // The largest variant is `Piecewise` with all its segments
+    pub const SPACE: usize = 1 + 4 + CurveSegment::SPACE * Self::MAX_SEGMENTS;
+
+    /// Total emitted on the curve days `[from, to)`, in closed form.
+    pub fn total_between(&self, from: u64, to: u64) -> Result<u64> {
+        if from >= to {
+            return Ok(0);
+        }
+        let total: u128 = match self {
+            EmissionCurve::Constant { daily } => *daily as u128 * (to - from) as u128,
+            EmissionCurve::LinearDecay { start_daily, decay_per_day } => {
+                let zero_at = match *decay_per_day {
+                    0 => u64::MAX,
+                    decay => start_daily.div_ceil(decay),
+                };
+                let to = to.min(zero_at);
+                if from >= to {
+                    0
+                } else {
+                    // Arithmetic series: n days starting at `from`
+                    let days = (to - from) as u128;
+                    let day_sum = (from as u128 + to as u128 - 1) * days / 2;
+                    *start_daily as u128 * days - *decay_per_day as u128 * day_sum
+                }
+            }
+            EmissionCurve::Halving { start_daily, halving_interval } => {
+                let mut total = 0u128;
+                let mut day = from;
+                while day < to {
+                    let period = day / halving_interval;
+                    if period >= u64::BITS as u64 {
+                        break;
+                    }
+                    let daily = start_daily >> period;
+                    if daily == 0 {
+                        break;
+                    }
+                    let period_end = (period + 1).saturating_mul(*halving_interval).min(to);
+                    total += daily as u128 * (period_end - day) as u128;
+                    day = period_end;
+                }
+                total
+            }
+            EmissionCurve::Piecewise { segments } => {
+                let mut total = 0u128;
+                for (index, segment) in segments.iter().enumerate() {
+                    let segment_end = segments
+                        .get(index + 1)
+                        .map_or(u64::MAX, |next| next.start_offset);
+                    let start = from.max(segment.start_offset);
+                    let end = to.min(segment_end);
+                    if start < end {
+                        total += segment.daily as u128 * (end - start) as u128;
+                    }
+                }
+                total
+            }
+        };
+        u64::try_from(total).map_err(|_| error!(ErrorCode::RewardCalculationError))
+    }
+}
+
+/// Share of the daily budget emitted (`emission_bps`) as a function of the share of a
+/// collection's supply that is staked (`utilization_bps`), interpolated linearly between points
+/// and flat beyond the first and last.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct UtilizationCurve {
+    pub points: Vec<UtilizationPoint>,
+}
+impl UtilizationCurve {
+    pub const MAX_POINTS: usize = 4;
+    pub const SPACE: usize = 4 + UtilizationPoint::SPACE * Self::MAX_POINTS;
+
+    fn validate(&self) -> Result<()> {
+        require!(
+            !self.points.is_empty() && self.points.len() <= Self::MAX_POINTS,
+            ErrorCode::InvalidUtilizationCurve
+        );
+        require!(
+            self.points.windows(2).all(|pair| pair[0].utilization_bps < pair[1].utilization_bps),
+            ErrorCode::InvalidUtilizationCurve
+        );
+        // Never emit more than the budget, so the solvency of the schedule still holds
+        require!(
+            self.points.iter().all(|point| {
+                point.utilization_bps as u64 <= BPS_DENOMINATOR && point.emission_bps as u64 <= BPS_DENOMINATOR
+            }),
+            ErrorCode::InvalidBasisPoints
+        );
+        Ok(())
+    }
+
+    pub fn emission_bps_at(&self, utilization_bps: u64) -> u64 {
+        let first = self.points[0];
+        if utilization_bps <= first.utilization_bps as u64 {
+            return first.emission_bps as u64;
+        }
+        for pair in self.points.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if utilization_bps <= high.utilization_bps as u64 {
+                let (x0, y0) = (low.utilization_bps as i64, low.emission_bps as i64);
+                let (x1, y1) = (high.utilization_bps as i64, high.emission_bps as i64);
+                return (y0 + (y1 - y0) * (utilization_bps as i64 - x0) / (x1 - x0)) as u64;
+            }
+        }
+        self.points[self.points.len() - 1].emission_bps as u64
+    }
+
+    /// The pool-wide emission rate: each collection's rate at its own utilization, weighted by
+    /// how many of the staked NFTs it holds. NFTs of collections without a registered supply
+    /// earn the full budget.
+    pub fn weighted_emission_bps(&self, collections: &[AllowedCollection], total_staked: u64) -> u64 {
+        if total_staked == 0 {
+            return BPS_DENOMINATOR;
+        }
+        let mut weighted: u128 = 0;
+        let mut counted: u64 = 0;
+        for collection in collections.iter().filter(|c| c.total_supply > 0) {
+            let utilization_bps =
+                (collection.staked as u128 * BPS_DENOMINATOR as u128 / collection.total_supply as u128)
+                    .min(BPS_DENOMINATOR as u128) as u64;
+            weighted += collection.staked as u128 * self.emission_bps_at(utilization_bps) as u128;
+            counted += collection.staked;
+        }
+        weighted += total_staked.saturating_sub(counted) as u128 * BPS_DENOMINATOR as u128;
+        (weighted / total_staked as u128) as u64
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UtilizationPoint {
+    pub utilization_bps: u16,
+    pub emission_bps: u16,
+}
+impl UtilizationPoint {
+    pub const SPACE: usize = 2 + 2;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CurveSegment {
+    pub start_offset: u64,
+    pub daily: u64,
+}
+impl CurveSegment {
+    pub const SPACE: usize = 8 + 8;
+}
+
+/// How membership in an allowed collection is proven at stake time.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollectionKind {
+    /// Verified `collection` in the NFT's Metaplex metadata; keyed by the collection mint.
+    Metaplex,
+    /// Token-2022 `TokenGroupMember` extension on the NFT mint; keyed by the group address.
+    TokenGroup,
+    /// Metaplex Core asset whose update authority is the collection; keyed by the collection.
+    MplCore,
+}
+
+/// Who holds the staked item while a position is open.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StakeCustody {
+    /// SPL NFT held in the entry's `nft_vault`.
+    #[default]
+    Vault,
+    /// Metaplex Core asset owned by the stake entry PDA.
+    CoreAsset,
+    /// SPL NFT left in the user's ATA, which the pool keeps frozen.
+    Frozen,
+}
+
+/// The fixed-size head of a Metaplex Core `AssetV1` account. The name, uri and plugin data
+/// that follow are not needed here and are left unread.
+#[derive(AnchorDeserialize)]
+pub struct CoreAsset {
+    pub key: u8,
+    pub owner: Pubkey,
+    pub update_authority: CoreUpdateAuthority,
+}
+
+#[derive(AnchorDeserialize, PartialEq, Eq, Debug)]
+pub enum CoreUpdateAuthority {
+    None,
+    Address(Pubkey),
+    Collection(Pubkey),
+}
+
+impl CoreAsset {
+    /// `Key::AssetV1` in Metaplex Core.
+    pub const ASSET_V1_KEY: u8 = 1;
+    /// `TransferV1` in Metaplex Core's instruction enum.
+    pub const TRANSFER_V1_DISCRIMINATOR: u8 = 14;
+
+    pub fn try_from_account(account: &AccountInfo) -> Result<Self> {
+        let data = account.try_borrow_data()?;
+        let asset = CoreAsset::deserialize(&mut &data[..])
+            .map_err(|_| error!(ErrorCode::InvalidCoreAsset))?;
+        require_eq!(asset.key, Self::ASSET_V1_KEY, ErrorCode::InvalidCoreAsset);
+        Ok(asset)
+    }
+
+    /// The collection the asset belongs to. Core only lets the collection's authority add an
+    /// asset to it, so membership needs no separate verification.
+    pub fn collection(&self) -> Option<Pubkey> {
+        match self.update_authority {
+            CoreUpdateAuthority::Collection(collection) => Some(collection),
+            _ => None,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AllowedCollection {
+    pub collection: Pubkey,
+    pub kind: CollectionKind,
+    pub total_supply: u64, // Registered by the admin for utilization scaling; 0 if unknown
+    pub staked: u64,
+}
+impl AllowedCollection {
+    pub const SPACE: usize = 32 + 1 + 8 + 8;
+}
+
+/// `Pool` before accounts were versioned. Some deployments followed the same fields with a
+/// `staked_counts` window, which is ignored when migrating.
+#[derive(AnchorDeserialize)]
+#[allow(dead_code)] // Some fields are only read to get past them
+struct LegacyPool {
+    admin: Pubkey,
+    reward_mint: Pubkey,
+    reward_vault: Pubkey,
+    allowed_collections: Vec<Pubkey>,
+    total_staked: u64,
+    current_epoch: u64,
+    last_update_time: i64,
+    epoch_duration: i64,
+    rewards_per_epoch: Vec<u64>,
+    bump: u8,
+    start_staking_timestamp: i64,
+    cumulative_reward_per_nft: u64,
+    last_update_calc_reward_nft_index: u64,
+}
+impl LegacyPool {
+    const ACCOUNT_SPACE: usize = 8
+        + 32 * 3
+        + (4 + 32 * Pool::MAX_COLLECTIONS)
+        + 8 * 4
+        + (4 + 8 * Pool::MAX_EPOCHS)
+        + 1
+        + 8 * 3;
+    /// The windowed layout additionally held `staked_counts_start_day`,
+    /// `total_staked_at_window_start` and up to 365 daily `staked_counts`.
+    const WINDOWED_ACCOUNT_SPACE: usize = Self::ACCOUNT_SPACE + 8 + 8 + (4 + 4 * 365);
+    /// Allocated sizes, discriminator included.
+    const ACCOUNT_SIZES: [usize; 2] = [8 + Self::ACCOUNT_SPACE, 8 + Self::WINDOWED_ACCOUNT_SPACE];
+
+    fn into_pool(self, now: i64) -> Pool {
+        Pool {
+            version: Pool::VERSION,
+            admin: self.admin,
+            reward_mint: self.reward_mint,
+            reward_vault: self.reward_vault,
+            fixed_rate_terms: FixedRateTerms::default(),
+            reward_strategy: RewardStrategy::EqualShare,
+            total_shares: self.total_staked,
+            accrued_until: now,
+            allowed_collections: self
+                .allowed_collections
+                .into_iter()
+                .map(|collection| AllowedCollection {
+                    collection,
+                    kind: CollectionKind::Metaplex,
+                    total_supply: 0,
+                    staked: 0,
+                })
+                .collect(),
+            total_staked: self.total_staked,
+            total_funded: 0,
+            total_distributed: 0,
+            total_claimed: 0,
+            total_admin_withdrawn: 0,
+            stake_seconds: 0,
+            stake_seconds_updated_at: now,
+            epoch_duration: self.epoch_duration,
+            rewards_per_epoch: self.rewards_per_epoch,
+            bump: self.bump,
+            start_staking_timestamp: self.start_staking_timestamp,
+            cumulative_reward_per_nft: self.cumulative_reward_per_nft,
+            last_update_calc_reward_nft_index: self.last_update_calc_reward_nft_index,
+            reward_streams: [Pubkey::default(); Pool::MAX_REWARD_STREAMS],
+            next_reward_stream_id: 1,
+            campaign_mints_restricted: false,
+            reward_mode: RewardMode::Vault,
+            emission_cap: 0,
+            total_scheduled: 0,
+            total_minted: 0,
+            sol_vault_bump: Pubkey::find_program_address(&[b"sol_vault"], &crate::ID).1,
+            emission_schedule: None,
+            outstanding_rewards: 0,
+            utilization_curve: None,
+            surplus_rewards: 0,
+        }
+    }
+}
+
+/// `NftStakeEntry` before accounts were versioned.
+#[derive(AnchorDeserialize)]
+#[allow(dead_code)] // Some fields are only read to get past them
+struct LegacyNftStakeEntry {
+    user: Pubkey,
+    nft_mint: Pubkey,
+    staked_at: i64,
+    last_claimed_epoch: u64,
+    skipped_reward: u64,
+    bump: u8,
+}
+impl LegacyNftStakeEntry {
+    const ACCOUNT_SPACE: usize = 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+#[account]
+pub struct NftStakeEntry {
+    pub version: u8, // Layout version, see `migrate_stake_entry`
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub staked_at: i64,
+    pub lifetime_claimed: u64, // Pool rewards ever paid out for this position, delegate share included
+    pub last_claimed_at: i64, // 0 until the first payout
+    pub skipped_reward: u64, // The cumulative_reward_per_nft value when this NFT was staked/last claimed
+    pub bump: u8,
+    pub pending_reward: u64, // Settled but not yet claimed rewards (e.g. the remainder of a partial claim)
+    pub reward_delegate: Pubkey, // Wallet that receives a share of the rewards; Pubkey::default() if none
+    pub delegate_share_bps: u16, // Share of every payout sent to reward_delegate, in basis points
+    pub receipt_mint: Pubkey, // Receipt token representing this position; Pubkey::default() if none
+    pub custody: StakeCustody, // How the staked item is held; nft_mint is the Core asset for CoreAsset
+    pub stream_checkpoints: [StreamCheckpoint; 4], // Per reward stream, indexed like the streams
+    pub collection: Pubkey, // The allowed collection the item was staked under
+}
+impl NftStakeEntry {
+    pub const VERSION: u8 = 1;
+    pub const ACCOUNT_SPACE: usize = 1 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 8 + 32 + 2 + 32 + 1
+        + StreamCheckpoint::SPACE * Pool::MAX_REWARD_STREAMS
+        + 32;
+
+    /// Books a payout of the pool's reward in the lifetime counters.
+    pub fn record_claim(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.lifetime_claimed = self
+            .lifetime_claimed
+            .checked_add(amount)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.last_claimed_at = now;
+        Ok(())
+    }
+
+    pub fn has_reward_delegate(&self) -> bool {
+        self.reward_delegate != Pubkey::default()
+    }
+
+    pub fn has_receipt(&self) -> bool {
+        self.receipt_mint != Pubkey::default()
+    }
+
+    /// The reward delegate's cut of `amount`, rounded down in the owner's favour.
+    pub fn delegate_share_of(&self, amount: u64) -> Result<u64> {
+        if !self.has_reward_delegate() {
+            return Ok(0);
+        }
+        let share = (amount as u128)
+            .checked_mul(self.delegate_share_bps as u128)
+            .ok_or(ErrorCode::RewardCalculationError)?
+            / BPS_DENOMINATOR as u128;
+        Ok(share as u64)
+    }
+}
+
+/// An entry's position in one reward stream, mirroring `skipped_reward` / `pending_reward`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct StreamCheckpoint {
+    pub stream_id: u64, // The stream this checkpoint belongs to; slots are reused after a campaign closes
+    pub skipped_reward: u64,
+    pub pending_reward: u64,
+}
+impl StreamCheckpoint {
+    pub const SPACE: usize = 8 + 8 + 8;
+
+    /// Moves everything accrued since the checkpoint into `pending_reward`.
+    pub fn settle(&mut self, cumulative_reward_per_nft: u64) -> Result<()> {
+        let accrued = cumulative_reward_per_nft
+            .checked_sub(self.skipped_reward)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.pending_reward = self
+            .pending_reward
+            .checked_add(accrued)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.skipped_reward = cumulative_reward_per_nft;
+        Ok(())
+    }
+}
+
+/// An additional reward paid to every staked NFT next to the pool's own reward, with its own
+/// vault, day-indexed schedule and per-NFT accumulator. Either an open-ended stream run by the
+/// admin or a sponsored campaign with a fixed end.
+#[account]
+pub struct RewardStream {
+    pub id: u64,
+    pub index: u8, // Slot in `pool.reward_streams` and `NftStakeEntry::stream_checkpoints`
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub funder: Pubkey, // The admin for open-ended streams, the sponsor for campaigns
+    pub end_day: u64, // First day after a campaign; 0 for open-ended streams
+    pub rewards_per_epoch: Vec<u64>,
+    pub cumulative_reward_per_nft: u64,
+    pub last_update_calc_reward_nft_index: u64,
+    pub total_funded: u64,
+    pub total_allocated: u64, // Handed to stakers through the accumulator
+    pub total_reclaimed: u64,
+    pub bump: u8,
+}
+impl RewardStream {
+    pub const ACCOUNT_SPACE: usize =
+        8 + 1 + 32 + 32 + 32 + 8 + (4 + 8 * Pool::MAX_EPOCHS) + 8 + 8 + 8 + 8 + 8 + 1;
+    /// How long stakers can still claim after a campaign ends before it can be closed.
+    pub const CAMPAIGN_CLAIM_WINDOW_DAYS: u64 = 30;
+
+    pub fn is_campaign(&self) -> bool {
+        self.end_day != 0
+    }
+
+    /// Funded rewards that were never handed to stakers and have not been reclaimed.
+    pub fn unallocated(&self) -> Result<u64> {
+        self.total_funded
+            .checked_sub(self.total_allocated)
+            .and_then(|amount| amount.checked_sub(self.total_reclaimed))
+            .ok_or(error!(ErrorCode::RewardCalculationError))
+    }
+
+    /// The stream's counterpart of `update_skipped_reward`.
+    pub fn update_accumulator(&mut self, pool: &Pool, current_day: u64) -> Result<()> {
+        if self.last_update_calc_reward_nft_index >= current_day {
+            return Ok(());
+        }
+        // Streams are brought up to date before every change to what is staked
+        let staked = pool.total_staked;
+        let plus_reward = reward_per_nft_between(
+            &self.rewards_per_epoch,
+            staked,
+            self.last_update_calc_reward_nft_index,
+            current_day,
+        )?;
+        self.cumulative_reward_per_nft = self
+            .cumulative_reward_per_nft
+            .checked_add(plus_reward)
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        // Every staked NFT is owed `plus_reward`; rounding dust stays unallocated
+        self.total_allocated = plus_reward
+            .checked_mul(staked)
+            .and_then(|allocated| allocated.checked_add(self.total_allocated))
+            .ok_or(ErrorCode::RewardCalculationError)?;
+        self.last_update_calc_reward_nft_index = current_day;
+        Ok(())
+    }
+}
+
+/// Returned by `get_pending_rewards`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PendingRewards {
+    pub claimable: u64,
+    pub claimable_next_day: u64,
+    pub staked_seconds: i64,
+    pub current_day: u64,
+    pub next_day_starts_at: i64,
+}
+
+/// What `verify_stake` checks. Every position counted must satisfy all of the set filters.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct StakeRequirements {
+    pub min_count: u32, // Treated as 1 when 0
+    pub collection: Option<Pubkey>,
+    pub min_stake_age_seconds: i64,
+}
+impl StakeRequirements {
+    pub fn matches(&self, stake_entry: &NftStakeEntry, now: i64) -> bool {
+        self.collection.is_none_or(|collection| stake_entry.collection == collection)
+            && now - stake_entry.staked_at >= self.min_stake_age_seconds
+    }
+}
+
+/// Voting power granted by staked NFTs: `weight_per_nft`, scaled by the multiplier of the NFT's
+/// collection and by the highest duration tier its stake age has reached (both in basis points,
+/// 1x when none applies).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct VoterWeightSettings {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub weight_per_nft: u64,
+    pub collection_multipliers: Vec<CollectionMultiplier>,
+    pub duration_multipliers: Vec<DurationMultiplier>,
+}
+impl VoterWeightSettings {
+    pub const MAX_DURATION_TIERS: usize = 4;
+    pub const SPACE: usize = 32
+        + 32
+        + 8
+        + (4 + CollectionMultiplier::SPACE * Pool::MAX_COLLECTIONS)
+        + (4 + DurationMultiplier::SPACE * Self::MAX_DURATION_TIERS);
+
+    fn validate(&self) -> Result<()> {
+        require!(
+            self.collection_multipliers.len() <= Pool::MAX_COLLECTIONS
+                && self.duration_multipliers.len() <= Self::MAX_DURATION_TIERS,
+            ErrorCode::InvalidVoterWeightSettings
+        );
+        require!(
+            self.duration_multipliers
+                .windows(2)
+                .all(|pair| pair[0].min_staked_seconds < pair[1].min_staked_seconds),
+            ErrorCode::InvalidVoterWeightSettings
+        );
+        Ok(())
+    }
+
+    pub fn weight_of(&self, stake_entry: &NftStakeEntry, now: i64) -> Result<u64> {
+        let collection_bps = self
+            .collection_multipliers
+            .iter()
+            .find(|multiplier| multiplier.collection == stake_entry.collection)
+            .map_or(BPS_DENOMINATOR, |multiplier| multiplier.multiplier_bps as u64);
+        let staked_seconds = now - stake_entry.staked_at;
+        let duration_bps = self
+            .duration_multipliers
+            .iter()
+            .rev()
+            .find(|tier| staked_seconds >= tier.min_staked_seconds)
+            .map_or(BPS_DENOMINATOR, |tier| tier.multiplier_bps as u64);
+        let weight = (self.weight_per_nft as u128)
+            .checked_mul(collection_bps as u128 * duration_bps as u128)
+            .ok_or(ErrorCode::RewardCalculationError)?
+            / (BPS_DENOMINATOR as u128 * BPS_DENOMINATOR as u128);
+        u64::try_from(weight).map_err(|_| error!(ErrorCode::RewardCalculationError))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CollectionMultiplier {
+    pub collection: Pubkey,
+    pub multiplier_bps: u16,
+}
+impl CollectionMultiplier {
+    pub const SPACE: usize = 32 + 2;
+}
+
+/// Applies once a position has been staked for `min_staked_seconds`. Tiers are sorted.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct DurationMultiplier {
+    pub min_staked_seconds: i64,
+    pub multiplier_bps: u16,
+}
+impl DurationMultiplier {
+    pub const SPACE: usize = 8 + 2;
+}
+
+/// Governance action a `VoterWeightRecord` is valid for, as in the SPL Governance addin API.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoterWeightAction {
+    CastVote,
+    CommentProposal,
+    CreateGovernance,
+    CreateProposal,
+    SignOffProposal,
+}
+
+/// Returned by `verify_stake`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct StakeVerification {
+    pub user: Pubkey,
+    pub matching_count: u32,
+    pub oldest_staked_at: Option<i64>,
 }
 
-#[derive(Accounts)]
-pub struct ManageCollection<'info> {
-    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
+/// Returned by `get_pool_stats`. Amounts are in the pool's reward token (lamports in `Sol` mode).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PoolStats {
+    pub reward_strategy: RewardStrategy,
+    pub total_staked: u64,
+    pub collections: Vec<CollectionStats>,
+    pub current_day: u64,
+    pub last_updated_day: u64, // Accumulator state on-chain; may lag `current_day` until the next stake or claim
+    pub todays_budget: u64, // Offered by the schedule and curve today
+    pub todays_emission: u64, // Handed to stakers today after utilization scaling and rounding
+    pub daily_yield_per_nft: u64, // Per share under `WeightedShare`; `reward_per_day` under `FixedRate`
+    pub remaining_funded_days: u64, // Until the day-by-day schedule and the curve both run out
+    pub vault_balance: u64, // Payable now; the remaining emission cap in `Mint` mode
+    pub outstanding_rewards: u64, // Accrued to stakers, not yet paid
+    pub committed_rewards: u64, // Outstanding plus everything still scheduled
+    pub surplus_rewards: u64,
+    pub total_funded: u64,
+    pub total_distributed: u64, // Including today's accrual so far
+    pub total_claimed: u64,
+    pub total_admin_withdrawn: u64,
+    pub stake_seconds: u64, // Up to now
 }
 
-#[derive(Accounts)]
-pub struct Stake<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
-    pub pool: Account<'info, Pool>,
-    pub nft_mint: InterfaceAccount<'info, Mint>,
-    #[account(
-        seeds = [b"metadata", mpl_token_metadata::ID.as_ref(), nft_mint.key().as_ref()],
-        seeds::program = mpl_token_metadata::ID,
-        bump
-    )]
-    /// CHECK: We deserialize this manually and verify its properties in the instruction.
-    pub nft_metadata_account: UncheckedAccount<'info>,
-    #[account(init, payer = user, space = 8 + NftStakeEntry::ACCOUNT_SPACE, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
-    pub stake_entry: Account<'info, NftStakeEntry>,
-    #[account(mut, associated_token::mint = nft_mint, associated_token::authority = user)]
-    pub user_nft_token_account: InterfaceAccount<'info, TokenAccount>,
-    #[account(init, payer = user, token::mint = nft_mint, token::authority = stake_entry, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
-    pub nft_vault: InterfaceAccount<'info, TokenAccount>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CollectionStats {
+    pub collection: Pubkey,
+    pub kind: CollectionKind,
+    pub total_supply: u64,
+    pub staked: u64,
 }
 
-#[derive(Accounts)]
-pub struct Unstake<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
-    pub pool: Account<'info, Pool>,
-    pub nft_mint: InterfaceAccount<'info, Mint>,
-    #[account(mut, close = user, has_one = user, has_one = nft_mint, seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()], bump = stake_entry.bump)]
-    pub stake_entry: Account<'info, NftStakeEntry>,
-    #[account(mut, seeds = [b"nft_vault", user.key().as_ref(), nft_mint.key().as_ref()], bump)]
-    pub nft_vault: InterfaceAccount<'info, TokenAccount>,
-    #[account(init_if_needed, payer = user, associated_token::mint = nft_mint, associated_token::authority = user)]
-    pub user_nft_token_account: InterfaceAccount<'info, TokenAccount>, 
-    // Accounts for claiming rewards
-    #[account(address = pool.reward_mint)] // Add constraint to ensure it's the correct reward mint
-    pub reward_mint: InterfaceAccount<'info, Mint>, 
-    #[account(
-        init_if_needed,
-        payer = user,
-        associated_token::mint = reward_mint, // Reference the reward_mint account
-        associated_token::authority = user
-    )]
-    pub user_reward_token_account: InterfaceAccount<'info, TokenAccount>,
-    #[account(mut, address = pool.reward_vault)] // Add reward_vault to unstake context
-    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+/// Rewards owed to a user that the vault could not pay out at unstake time.
+#[account]
+pub struct RewardIou {
+    pub user: Pubkey,
+    pub amount_owed: u64,
+    pub bump: u8,
+}
+impl RewardIou {
+    pub const ACCOUNT_SPACE: usize = 32 + 8 + 1;
 }
 
-#[derive(Accounts)]
-pub struct ClaimReward<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [b"pool"], bump = pool.bump)]
-    pub pool: Account<'info, Pool>,
-    // The Mint account for the reward token, required for init_if_needed on user_reward_token_account
-    #[account(address = pool.reward_mint)] // Add constraint to ensure it's the correct reward mint
-    pub reward_mint: InterfaceAccount<'info, Mint>, 
-    // The NFT mint associated with the stake entry being claimed
-    pub nft_mint: InterfaceAccount<'info, Mint>,
-    #[account(
-        mut,
-        has_one = user,
-        has_one = nft_mint, // Ensure this stake_entry belongs to this user and NFT
-        seeds = [b"stake_entry", user.key().as_ref(), nft_mint.key().as_ref()],
-        bump = stake_entry.bump
-    )]
-    pub stake_entry: Account<'info, NftStakeEntry>,
-    #[account(mut, address = pool.reward_vault)]
-    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
-    #[account(
-        init_if_needed,
-        payer = user,
-        associated_token::mint = reward_mint, // Reference the reward_mint account
-        associated_token::authority = user
-    )]
-    pub user_reward_token_account: InterfaceAccount<'info, TokenAccount>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+/// Stream rewards owed to a user that a stream could not pay out when a position left them,
+/// e.g. because a token account was frozen. Claimed per stream with `claim_owed_stream_reward`
+/// within the stream's claim window.
+#[account]
+pub struct StreamRewardIou {
+    pub user: Pubkey,
+    pub owed: [StreamRewardOwed; 4], // Per reward stream, indexed like the streams
+    pub bump: u8,
 }
+impl StreamRewardIou {
+    pub const ACCOUNT_SPACE: usize = 32 + StreamRewardOwed::SPACE * Pool::MAX_REWARD_STREAMS + 1;
 
-/// Cấu trúc tài khoản cho lệnh `admin_claim`.
-#[derive(Accounts)]
-pub struct AdminClaim<'info> {
-    /// Tài khoản Pool, chứa thông tin quản trị viên và vault.
-    #[account(mut, seeds = [b"pool"], bump = pool.bump, has_one = admin, has_one = reward_mint)]
-    pub pool: Account<'info, Pool>,
-    /// Tài khoản quản trị viên, phải là người ký.
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// Vault chứa token phần thưởng của pool.
-    #[account(mut, address = pool.reward_vault)]
-    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
-    /// Mint của token phần thưởng, để kiểm tra tính hợp lệ.
-    pub reward_mint: InterfaceAccount<'info, Mint>,
-    /// Tài khoản token của quản trị viên nơi tiền sẽ được chuyển đến.
-    #[account(mut, associated_token::mint = reward_mint, associated_token::authority = admin)]
-    pub admin_reward_token_account: InterfaceAccount<'info, TokenAccount>,
-    /// Chương trình token để thực hiện chuyển khoản.
-    pub token_program: Interface<'info, TokenInterface>,
+    pub fn is_empty(&self) -> bool {
+        self.owed.iter().all(|owed| owed.amount == 0)
+    }
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct StreamRewardOwed {
+    pub stream_id: u64, // Slots are reused after a campaign closes, like `StreamCheckpoint`
+    pub amount: u64,
+}
+impl StreamRewardOwed {
+    pub const SPACE: usize = 8 + 8;
+}
+
+/// A mint the admin allows sponsored campaigns to be paid in.
+#[account]
+pub struct CampaignMint {
+    pub mint: Pubkey,
+    pub min_amount: u64, // Smallest amount a campaign in this mint can be funded with
+    pub bump: u8,
+}
+impl CampaignMint {
+    pub const ACCOUNT_SPACE: usize = 32 + 8 + 1;
+}
 
-// --- DATA STRUCTS ---
 
 #[account]
-pub struct Pool {
-    pub admin: Pubkey,
-    pub reward_mint: Pubkey,
-    pub reward_vault: Pubkey,
-    pub allowed_collections: Vec<Pubkey>,
-    pub total_staked: u64,
-    pub current_epoch: u64,
-    pub last_update_time: i64,
-    pub epoch_duration: i64,
-    pub rewards_per_epoch: Vec<u64>,
+pub struct VoterWeightConfig {
+    pub settings: VoterWeightSettings,
     pub bump: u8,
-    pub start_staking_timestamp: i64, // ✅ Thời điểm bắt đầu staking chính thức
-    pub cumulative_reward_per_nft: u64, // ✅ Tổng phần thưởng bỏ lỡ mỗi NFT tính đến thời điểm cuối - Renamed
-    pub last_update_calc_reward_nft_index: u64, // ✅ Ngày cuối cùng đã update cumulative reward
-    // Removed staked_counts, staked_counts_start_day, total_staked_at_window_start
 }
-impl Pool {
-    pub const MAX_EPOCHS: usize = 1200;
-    pub const MAX_COLLECTIONS: usize = 2; // Increased to 2 for example
-    // Removed MAX_STAKED_COUNTS_WINDOW_DAYS
-    pub const ACCOUNT_SPACE: usize = 8
-        + 32 // admin
-        + 32 // reward_mint
-        + 32 // reward_vault
-        + (4 + 32 * Self::MAX_COLLECTIONS) // allowed_collections
-        + 8  // total_staked
-        + 8  // current_epoch
-        + 8  // last_update_time
-        + 8  // epoch_duration
-        + (4 + 8 * Self::MAX_EPOCHS) // rewards_per_epoch
-        + 1  // bump
-        + 8  // start_staking_timestamp
-        + 8  // cumulative_reward_per_nft
-        + 8; // last_update_calc_reward_nft_index
-        // Removed space for staked_counts, staked_counts_start_day, total_staked_at_window_start
+impl VoterWeightConfig {
+    pub const ACCOUNT_SPACE: usize = VoterWeightSettings::SPACE + 1;
+}
+
+/// `VoterWeightRecord` in the SPL Governance addin layout, read by the governance program when
+/// this program is the realm's voter-weight addin. The addin discriminator is Anchor's default
+/// for this name.
+#[account]
+pub struct VoterWeightRecord {
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub governing_token_owner: Pubkey,
+    pub voter_weight: u64,
+    pub voter_weight_expiry: Option<u64>, // Slot the weight is valid in
+    pub weight_action: Option<VoterWeightAction>,
+    pub weight_action_target: Option<Pubkey>,
+    pub reserved: [u8; 8],
 }
+impl VoterWeightRecord {
+    pub const ACCOUNT_SPACE: usize = 32 + 32 + 32 + 8 + (1 + 8) + (1 + 1) + (1 + 32) + 8;
+}
+
+/// Marks an NFT as counted for a proposal's vote.
 #[account]
-pub struct NftStakeEntry {
-    pub user: Pubkey,
+pub struct NftVoteRecord {
+    pub proposal: Pubkey,
     pub nft_mint: Pubkey,
-    pub staked_at: i64,
-    pub last_claimed_epoch: u64, // This field might become less relevant with cumulative_reward
-    pub skipped_reward: u64, // The cumulative_reward_per_nft value when this NFT was staked/last claimed
+    pub governing_token_owner: Pubkey,
     pub bump: u8,
 }
-impl NftStakeEntry {
-    pub const ACCOUNT_SPACE: usize = 32 + 32 + 8 + 8 + 8 + 1;
+impl NftVoteRecord {
+    pub const ACCOUNT_SPACE: usize = 32 + 32 + 32 + 1;
 }
 
-
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub admin: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub reward_mode: RewardMode,
+    pub emission_cap: u64,
+    pub reward_strategy: RewardStrategy,
+    pub start_staking_timestamp: i64,
+}
+#[event]
+pub struct CollectionAdded {
+    pub pool: Pubkey,
+    pub collection: Pubkey,
+    pub kind: CollectionKind,
+    pub collection_count: u8,
+}
+#[event]
+pub struct CollectionRemoved {
+    pub pool: Pubkey,
+    pub collection: Pubkey,
+    pub staked: u64, // Positions of the collection still staked when it was removed
+    pub collection_count: u8,
+}
+#[event]
+pub struct CollectionSupplySet {
+    pub pool: Pubkey,
+    pub collection: Pubkey,
+    pub old_total_supply: u64,
+    pub new_total_supply: u64,
+}
+#[event]
+pub struct UtilizationCurveSet {
+    pub pool: Pubkey,
+    pub old_curve: Option<UtilizationCurve>,
+    pub new_curve: Option<UtilizationCurve>,
+    pub cumulative_reward_per_nft: u64,
+}
+#[event]
+pub struct FixedRateTermsSet {
+    pub pool: Pubkey,
+    pub old_terms: FixedRateTerms,
+    pub new_terms: FixedRateTerms,
+    pub liabilities: u64,
+    pub available_rewards: u64,
+}
+#[event]
+pub struct AdminClaimed {
+    pub pool: Pubkey,
+    pub admin: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+}
+#[event]
+pub struct VoterWeightConfigured {
+    pub pool: Pubkey,
+    pub old_settings: VoterWeightSettings,
+    pub new_settings: VoterWeightSettings,
+}
+#[event]
+pub struct VoterWeightRecordCreated {
+    pub voter_weight_record: Pubkey,
+    pub realm: Pubkey,
+    pub governing_token_mint: Pubkey,
+    pub governing_token_owner: Pubkey,
+}
+#[event]
+pub struct VoterWeightRecordUpdated {
+    pub voter_weight_record: Pubkey,
+    pub governing_token_owner: Pubkey,
+    pub old_voter_weight: u64,
+    pub voter_weight: u64,
+    pub weight_action: VoterWeightAction,
+    pub weight_action_target: Pubkey,
+    pub nft_count: u32,
+}
+#[event]
+pub struct AccountMigrated {
+    pub pool: Pubkey,
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
 #[event]
 pub struct RewardAdded {
+    pub pool: Pubkey,
     pub funder: Pubkey,
     pub total_amount: u64,
     pub epochs_funded: u64,
+    pub cumulative_reward_per_nft: u64,
+    pub total_staked: u64,
 }
 #[event]
 pub struct RewardClaimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub collection: Pubkey,
+    pub amount: u64,
+    pub cumulative_reward_per_nft: u64, // Pool accumulator after the claim
+    pub total_staked: u64,
+}
+#[event]
+pub struct EmissionCurveSet {
+    pub pool: Pubkey,
+    pub old_schedule: Option<EmissionSchedule>,
+    pub new_schedule: Option<EmissionSchedule>,
+    pub committed_rewards: u64,
+    pub available_rewards: u64,
+}
+#[event]
+pub struct RewardStreamAdded {
+    pub pool: Pubkey,
+    pub reward_stream: Pubkey,
+    pub index: u8,
+    pub reward_mint: Pubkey,
+}
+#[event]
+pub struct RewardStreamFunded {
+    pub pool: Pubkey,
+    pub reward_stream: Pubkey,
+    pub funder: Pubkey,
+    pub total_amount: u64,
+    pub epochs_funded: u64,
+    pub previous_total_funded: u64,
+    pub total_funded: u64,
+}
+#[event]
+pub struct CampaignCreated {
+    pub pool: Pubkey,
+    pub reward_stream: Pubkey,
+    pub funder: Pubkey,
+    pub reward_mint: Pubkey,
+    pub start_day: u64,
+    pub end_day: u64,
+    pub amount: u64,
+}
+#[event]
+pub struct CampaignRewardsReclaimed {
+    pub pool: Pubkey,
+    pub reward_stream: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+}
+#[event]
+pub struct CampaignClosed {
+    pub pool: Pubkey,
+    pub reward_stream: Pubkey,
+    pub funder: Pubkey,
+    pub returned_amount: u64,
+}
+#[event]
+pub struct CampaignMintsRestrictedSet {
+    pub pool: Pubkey,
+    pub previous_restricted: bool,
+    pub restricted: bool,
+}
+#[event]
+pub struct CampaignMintAllowed {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub previous_min_amount: u64, // Zero when the mint was not allowed before
+    pub min_amount: u64,
+}
+#[event]
+pub struct CampaignMintDisallowed {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub min_amount: u64,
+}
+#[event]
+pub struct StreamRewardDeferred {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub reward_stream: Pubkey,
+    pub amount: u64,
+    pub total_owed: u64,
+}
+#[event]
+pub struct OwedStreamRewardClaimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub reward_stream: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+}
+#[event]
+pub struct StreamRewardClaimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub reward_mint: Pubkey,
+    pub amount: u64,
+}
+#[event]
+pub struct RewardDeferred {
     pub user: Pubkey,
     pub nft_mint: Pubkey,
     pub amount: u64,
+    pub total_owed: u64,
+}
+#[event]
+pub struct OwedRewardClaimed {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub remaining_owed: u64,
+}
+#[event]
+pub struct RewardDelegateSet {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub previous_reward_delegate: Pubkey, // Default when the position had no delegate
+    pub previous_delegate_share_bps: u16,
+    pub reward_delegate: Pubkey,
+    pub delegate_share_bps: u16,
+}
+#[event]
+pub struct ReceiptMinted {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub nft_mint: Pubkey,
+    pub receipt_mint: Pubkey,
+    pub soulbound: bool,
+}
+#[event]
+pub struct StakeTransferred {
+    pub pool: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub nft_mint: Pubkey,
+    pub staked_at: i64,
 }
 #[event]
 pub struct StakeEvent {
+    pub pool: Pubkey,
     pub user: Pubkey,
     pub nft_mint: Pubkey,
+    pub collection: Pubkey,
     pub staked_at: i64,
+    pub cumulative_reward_per_nft: u64,
+    pub total_staked: u64, // Including this NFT
 }
 #[event]
 pub struct UnstakeEvent {
+    pub pool: Pubkey,
     pub user: Pubkey,
     pub nft_mint: Pubkey,
+    pub collection: Pubkey,
     pub unstaked_at: i64,
+    pub cumulative_reward_per_nft: u64,
+    pub total_staked: u64, // Without this NFT
 }
 #[error_code]
 pub enum ErrorCode {
@@ -679,6 +6127,199 @@ pub enum ErrorCode {
     RewardCalculationError, // New error code for math operations
     #[msg("Insufficient balance in the vault to perform this operation.")]
     InsufficientVaultBalance, // New error code for insufficient funds
-    #[msg("Staked counts window exceeded maximum capacity. Please update rewards more frequently.")]
-    MaxStakedCountsExceeded, // New error code for staked_counts window - REMOVED, but kept for reference if needed
+    #[msg("No owed rewards to claim.")]
+    NoOwedRewards,
+    #[msg("Requested amount exceeds the entry's pending rewards.")]
+    ClaimExceedsPendingReward,
+    #[msg("Basis points must not exceed 10000.")]
+    InvalidBasisPoints,
+    #[msg("The reward delegate must be a valid wallet.")]
+    InvalidRewardDelegate,
+    #[msg("The reward delegate's token account is required for this payout.")]
+    MissingDelegateTokenAccount,
+    #[msg("A position cannot be transferred to its current owner.")]
+    InvalidNewOwner,
+    #[msg("This position is represented by a receipt token; use the receipt instructions.")]
+    PositionHasReceipt,
+    #[msg("The receipt mint does not match this position or the requested receipt kind.")]
+    InvalidReceiptMint,
+    #[msg("Signer does not hold the receipt for this position.")]
+    NotReceiptHolder,
+    #[msg("The account is not a valid Metaplex Core asset.")]
+    InvalidCoreAsset,
+    #[msg("Signer does not own this asset.")]
+    NotAssetOwner,
+    #[msg("This position is held differently; use the matching unstake instruction.")]
+    WrongCustody,
+    #[msg("In-place staking requires the pool to be the mint's freeze authority.")]
+    FreezeAuthorityNotPool,
+    #[msg("The pool already has the maximum number of reward streams.")]
+    MaxRewardStreamsExceeded,
+    #[msg("Every reward stream's accounts must be passed as remaining accounts.")]
+    MissingRewardStreamAccounts,
+    #[msg("A remaining account does not match the pool's reward streams.")]
+    InvalidRewardStreamAccount,
+    #[msg("Open-ended streams only; campaigns are funded when they are created.")]
+    NotOpenEndedStream,
+    #[msg("This reward stream is not a campaign.")]
+    NotACampaign,
+    #[msg("A campaign must start today or later and end after its first day.")]
+    InvalidCampaignWindow,
+    #[msg("The campaign has not ended yet.")]
+    CampaignNotEnded,
+    #[msg("Minting mode requires the pool to be the reward mint's mint authority.")]
+    MintAuthorityNotPool,
+    #[msg("This instruction is not available in the pool's reward mode.")]
+    WrongRewardMode,
+    #[msg("This would exceed the pool's emission cap.")]
+    EmissionCapExceeded,
+    #[msg("The SOL vault is required for pools that pay rewards in SOL.")]
+    MissingSolVault,
+    #[msg("The emission curve is malformed or starts in the past.")]
+    InvalidEmissionCurve,
+    #[msg("The vault does not cover the rewards owed and scheduled.")]
+    EmissionsNotCovered,
+    #[msg("Utilization curve points must be sorted and there must be between 1 and 4 of them.")]
+    InvalidUtilizationCurve,
+    #[msg("A stake entry is duplicated or does not belong to the user.")]
+    InvalidStakeEntry,
+    #[msg("The user does not have the required positions staked.")]
+    StakeRequirementsNotMet,
+    #[msg("Too many multipliers, or duration tiers not sorted.")]
+    InvalidVoterWeightSettings,
+    #[msg("The NFT vote record account does not match the proposal and NFT.")]
+    InvalidNftVoteRecord,
+    #[msg("This NFT has already voted on the proposal.")]
+    NftAlreadyVoted,
+    #[msg("The account is already in the current layout.")]
+    AlreadyMigrated,
+    #[msg("The account is not in a layout this program can migrate.")]
+    UnknownAccountLayout,
+    #[msg("The metadata account is not the NFT's Metaplex metadata PDA.")]
+    InvalidNftMetadata,
+    #[msg("Fixed rates must be positive and collection weights positive and unique.")]
+    InvalidRewardStrategy,
+    #[msg("Rates must be positive and unique per collection and can only change while nothing is staked; the end day cannot be in the past.")]
+    InvalidFixedRateTerms,
+    #[msg("This instruction is not available with the pool's reward strategy.")]
+    WrongRewardStrategy,
+    #[msg("The fixed-rate campaign has ended or has not been set up.")]
+    FixedRateCampaignEnded,
+    #[msg("This would take the pool's liabilities over its cap.")]
+    LiabilityCapExceeded,
+    #[msg("The reward vault is required for pools paying in tokens.")]
+    MissingRewardVault,
+    #[msg("The reward delegate's IOU is required when part of its share is deferred.")]
+    MissingDelegateRewardIou,
+    #[msg("Campaigns cannot be paid in a mint with a transfer hook or without transfers.")]
+    UnsupportedCampaignMint,
+    #[msg("The campaign is funded with less than its mint's minimum amount.")]
+    CampaignBelowMinimum,
+    #[msg("A stream reward IOU is required when a stream cannot pay out right now.")]
+    MissingStreamRewardIou,
+    #[msg("Token group members must be recorded on the NFT mint itself.")]
+    ExternalGroupMember,
+    #[msg("A token group member must have a supply of 1 and no decimals to be staked.")]
+    GroupMemberNotAnNft,
+    #[msg("The pool only takes campaigns in mints the admin allowed.")]
+    CampaignMintNotAllowed,
+    #[msg("Pools paying in tokens need the reward mint and the reward token accounts.")]
+    MissingRewardTokenAccounts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pool that started at timestamp 0 with `shares` shares over `staked` NFTs.
+    fn pool(reward_strategy: RewardStrategy, rewards_per_epoch: Vec<u64>, staked: u64, shares: u64) -> Pool {
+        Pool {
+            version: Pool::VERSION,
+            admin: Pubkey::default(),
+            reward_mint: Pubkey::default(),
+            reward_vault: Pubkey::default(),
+            fixed_rate_terms: FixedRateTerms::default(),
+            reward_strategy,
+            total_shares: shares,
+            accrued_until: 0,
+            allowed_collections: Vec::new(),
+            total_staked: staked,
+            total_funded: 0,
+            total_distributed: 0,
+            total_claimed: 0,
+            total_admin_withdrawn: 0,
+            stake_seconds: 0,
+            stake_seconds_updated_at: 0,
+            epoch_duration: 86400,
+            rewards_per_epoch,
+            bump: 0,
+            start_staking_timestamp: 0,
+            cumulative_reward_per_nft: 0,
+            last_update_calc_reward_nft_index: 0,
+            reward_streams: [Pubkey::default(); Pool::MAX_REWARD_STREAMS],
+            next_reward_stream_id: 1,
+            campaign_mints_restricted: false,
+            reward_mode: RewardMode::Vault,
+            emission_cap: 0,
+            total_scheduled: 0,
+            total_minted: 0,
+            sol_vault_bump: 0,
+            emission_schedule: None,
+            outstanding_rewards: 0,
+            utilization_curve: None,
+            surplus_rewards: 0,
+        }
+    }
+
+    #[test]
+    fn shared_between_splits_each_day_by_the_shares_staked() {
+        let equal = pool(RewardStrategy::EqualShare, vec![100, 100, 100], 3, 3);
+        let accrual = shared_between(&equal, 0, 2).unwrap();
+        assert_eq!(accrual.per_share, 33 + 33);
+        assert_eq!(accrual.accrued, 198);
+        assert_eq!(accrual.budget, 200);
+
+        // Three NFTs holding five shares between them
+        let weights = Default::default();
+        let weighted = pool(RewardStrategy::WeightedShare { weights }, vec![100, 100, 100], 3, 5);
+        let accrual = shared_between(&weighted, 1, 3).unwrap();
+        assert_eq!(accrual.per_share, 20 + 20);
+        assert_eq!(accrual.accrued, 200);
+    }
+
+    #[test]
+    fn shared_between_leaves_days_with_nothing_staked_to_the_surplus() {
+        let pool = pool(RewardStrategy::EqualShare, vec![100, 100, 100], 0, 0);
+        let accrual = shared_between(&pool, 0, 3).unwrap();
+        assert_eq!((accrual.per_share, accrual.accrued), (0, 0));
+        assert_eq!(accrual.budget, 300);
+    }
+
+    #[test]
+    fn time_weighted_between_releases_each_day_by_the_second() {
+        let pool = pool(RewardStrategy::TimeWeighted, vec![86_400, 172_800], 2, 2);
+        // The second half of day 0 and the first half of day 1
+        let accrual = time_weighted_between(&pool, 43_200, 129_600).unwrap();
+        assert_eq!(accrual.budget, 43_200 + 86_400);
+        assert_eq!(accrual.per_share, 21_600 + 43_200);
+        assert_eq!(accrual.accrued, 129_600);
+    }
+
+    #[test]
+    fn fixed_rate_between_pays_by_the_second_until_the_campaign_ends() {
+        let mut pool = pool(RewardStrategy::FixedRate { reward_per_day: 10 }, vec![86_400; 3], 3, 30);
+        pool.fixed_rate_terms.end_day = 2;
+        // From half way through day 0 to half way through day 2, a day after the campaign ended
+        let accrual = fixed_rate_between(&pool, 43_200, 216_000).unwrap();
+        assert_eq!(accrual.per_share, 129_600);
+        assert_eq!(accrual.accrued, 45);
+        // The schedule's offer is released by the second, ending or not
+        assert_eq!(accrual.budget, 43_200 + 86_400 + 43_200);
+
+        let accrual = fixed_rate_between(&pool, 0, 3_600).unwrap();
+        assert_eq!(accrual.per_share, 3_600);
+        // 30 shares for an hour, rounded up
+        assert_eq!(accrual.accrued, 2);
+        assert_eq!(accrual.budget, 3_600);
+    }
 }
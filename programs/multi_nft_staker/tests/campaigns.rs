@@ -0,0 +1,197 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::spl_token_2022::{
+    self,
+    extension::{transfer_hook, ExtensionType},
+    instruction::{freeze_account, thaw_account},
+};
+use common::*;
+use nft_staking::{
+    CampaignCreated, CampaignMint, CampaignMintAllowed, CampaignMintsRestrictedSet, CollectionKind, ErrorCode, OwedStreamRewardClaimed, RewardStrategy, StreamRewardDeferred,
+    StreamRewardIou,
+};
+
+#[test]
+fn create_campaign_takes_any_mint_unless_the_pool_restricts_them() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let sponsor = env.sponsor(10_000_000);
+    let reward_stream = env.create_campaign(&sponsor, 10, 1).unwrap();
+    assert_eq!(env.token_balance(&stream_vault_address(&reward_stream)), 1);
+
+    let stranger = TestPool { admin: env.wallet(LAMPORTS_PER_SOL), ..pool };
+    assert!(env.set_campaign_mints_restricted(&stranger, true).is_err());
+    env.set_campaign_mints_restricted(&pool, true).unwrap();
+    let set = env.events::<CampaignMintsRestrictedSet>();
+    assert_eq!(set.len(), 1);
+    assert_eq!(set[0].pool, pool_address());
+    assert!(!set[0].previous_restricted && set[0].restricted);
+
+    let sponsor = env.sponsor(10_000_000);
+    assert_eq!(
+        env.create_campaign(&sponsor, 10, 1_000_000),
+        Err(program_error(ErrorCode::CampaignMintNotAllowed))
+    );
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 1).unwrap();
+    env.create_campaign(&sponsor, 10, 1_000_000).unwrap();
+}
+
+#[test]
+fn campaigns_need_an_equal_share_pool() {
+    let mut env = TestEnv::new();
+    env.setup_pool(RewardStrategy::TimeWeighted, 0);
+    let sponsor = env.sponsor(10_000_000);
+
+    // Streams split each day equally, which would not match how the pool shares its own reward
+    assert_eq!(
+        env.create_campaign(&sponsor, 10, 1_000_000),
+        Err(program_error(ErrorCode::WrongRewardStrategy))
+    );
+}
+
+#[test]
+fn create_campaign_requires_an_allowed_mint_and_its_minimum() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    env.set_campaign_mints_restricted(&pool, true).unwrap();
+    let sponsor = env.sponsor(10_000_000);
+
+    assert_eq!(
+        env.create_campaign(&sponsor, 10, 1_000_000),
+        Err(program_error(ErrorCode::CampaignMintNotAllowed))
+    );
+
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 1_000_000).unwrap();
+    let allowed: CampaignMint = env.fetch(&campaign_mint_address(&sponsor.reward_mint));
+    assert_eq!(allowed.mint, sponsor.reward_mint);
+    assert_eq!(allowed.min_amount, 1_000_000);
+
+    assert_eq!(
+        env.create_campaign(&sponsor, 10, 999_999),
+        Err(program_error(ErrorCode::CampaignBelowMinimum))
+    );
+    let reward_stream = env.create_campaign(&sponsor, 10, 1_000_000).unwrap();
+    assert_eq!(env.token_balance(&stream_vault_address(&reward_stream)), 1_000_000);
+}
+
+#[test]
+fn allow_campaign_mint_rejects_a_transfer_hook_mint() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let authority = env.wallet(LAMPORTS_PER_SOL);
+    let token_program = spl_token_2022::ID;
+    let mint = env.create_mint_with_extensions(&token_program, 6, &authority, &[ExtensionType::TransferHook], |mint| {
+        vec![transfer_hook::instruction::initialize(&token_program, mint, Some(authority), Some(Pubkey::new_unique()))
+            .unwrap()]
+    });
+
+    assert_eq!(
+        env.allow_campaign_mint(&pool, &mint, 1),
+        Err(program_error(ErrorCode::UnsupportedCampaignMint))
+    );
+    assert!(env.account(&campaign_mint_address(&mint)).is_none());
+}
+
+#[test]
+fn unstake_defers_a_frozen_stream_payout_until_it_is_claimed() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+    let sponsor = env.sponsor(1_000_000);
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 1_000_000).unwrap();
+    let reward_stream = env.create_campaign(&sponsor, 10, 1_000_000).unwrap();
+    let user_campaign_rewards = env.create_ata(&user, &sponsor.reward_mint, &spl_token::ID);
+    env.warp(2 * DAY);
+
+    // The sponsor controls the mint and freezes the staker's account
+    let freeze = freeze_account(&spl_token::ID, &user_campaign_rewards, &sponsor.reward_mint, &sponsor.funder, &[]);
+    env.process(&freeze.unwrap()).unwrap();
+    let stream_accounts = env.stream_payout_accounts(&reward_stream, &user);
+
+    assert_eq!(
+        env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, false, &stream_accounts),
+        Err(program_error(ErrorCode::MissingStreamRewardIou))
+    );
+    env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, true, &stream_accounts).unwrap();
+
+    let user_nft = get_associated_token_address_with_program_id(&user, &nft, &spl_token::ID);
+    assert_eq!(env.token_balance(&user_nft), 1);
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+    let deferred = env.events::<StreamRewardDeferred>();
+    assert_eq!(deferred.len(), 1);
+    assert_eq!((deferred[0].pool, deferred[0].reward_stream), (pool_address(), reward_stream));
+    let owed = deferred[0].amount;
+    assert_eq!(owed, 200_000);
+    let stream_reward_iou: StreamRewardIou = env.fetch(&stream_reward_iou_address(&user));
+    assert_eq!(stream_reward_iou.owed[0].amount, owed);
+
+    // Still frozen, so the claim fails and nothing is lost
+    assert!(env.claim_owed_stream_reward(&user, &reward_stream).is_err());
+    let thaw = thaw_account(&spl_token::ID, &user_campaign_rewards, &sponsor.reward_mint, &sponsor.funder, &[]);
+    env.process(&thaw.unwrap()).unwrap();
+    env.claim_owed_stream_reward(&user, &reward_stream).unwrap();
+
+    assert_eq!(env.token_balance(&user_campaign_rewards), owed);
+    assert_eq!(env.token_balance(&stream_vault_address(&reward_stream)), 1_000_000 - owed);
+    let claimed = env.events::<OwedStreamRewardClaimed>();
+    assert_eq!((claimed[0].pool, claimed[0].amount), (pool_address(), owed));
+    assert!(env.account(&stream_reward_iou_address(&user)).is_none());
+    assert_eq!(
+        env.claim_owed_stream_reward(&user, &reward_stream),
+        Err(program_error(anchor_lang::error::ErrorCode::AccountNotInitialized))
+    );
+}
+
+#[test]
+fn unstake_closes_an_unused_stream_reward_iou() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+    let sponsor = env.sponsor(1_000_000);
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 1_000_000).unwrap();
+    let reward_stream = env.create_campaign(&sponsor, 10, 1_000_000).unwrap();
+    let user_campaign_rewards = env.create_ata(&user, &sponsor.reward_mint, &spl_token::ID);
+    env.warp(2 * DAY);
+
+    let stream_accounts = env.stream_payout_accounts(&reward_stream, &user);
+    env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, true, &stream_accounts).unwrap();
+
+    assert_eq!(env.token_balance(&user_campaign_rewards), 200_000);
+    assert!(env.events::<StreamRewardDeferred>().is_empty());
+    assert!(env.account(&stream_reward_iou_address(&user)).is_none());
+}
+
+#[test]
+fn campaign_events_carry_the_pool_and_are_only_delivered_through_event_cpi() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let sponsor = env.sponsor(10_000_000);
+
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 1_000_000).unwrap();
+    env.allow_campaign_mint(&pool, &sponsor.reward_mint, 2_000_000).unwrap();
+    let allowed = env.events::<CampaignMintAllowed>();
+    assert_eq!(allowed.len(), 1);
+    assert_eq!(allowed[0].pool, pool_address());
+    assert_eq!(allowed[0].previous_min_amount, 1_000_000);
+    assert_eq!(allowed[0].min_amount, 2_000_000);
+
+    let reward_stream = env.create_campaign(&sponsor, 10, 2_000_000).unwrap();
+    let created = env.events::<CampaignCreated>();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].pool, pool_address());
+    assert_eq!(created[0].reward_stream, reward_stream);
+    assert!(!env.logs().iter().any(|log| log.starts_with("Program data:")));
+}
@@ -0,0 +1,1572 @@
+//! Harness for the integration tests, over `solana-program-test`. The staking program runs as a
+//! builtin (or from its compiled `.so` under `cargo test-sbf`), next to the System program, SPL
+//! Token, Token-2022 and the associated token program the runtime ships. Metaplex Core is
+//! loaded from `tests/fixtures/mpl_core.so` when it has been dumped there; otherwise a builtin
+//! stand-in performs the one instruction the program calls, `TransferV1`.
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+};
+use anchor_lang::system_program;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id, spl_associated_token_account,
+};
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::spl_token_2022::{
+    self,
+    extension::{ExtensionType, StateWithExtensions},
+};
+use solana_program_test::{processor, tokio, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    commitment_config::CommitmentLevel,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+/// Unix time every test starts at; day 0 of pools created before the clock moves.
+pub const START_TIME: i64 = 1_700_000_000;
+pub const DAY: i64 = 86_400;
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// The outcome of one instruction. Errors are the runtime's, so failures it raises itself,
+/// such as a CPI escalating privileges, can be told apart from program errors too.
+pub type TestResult = std::result::Result<(), InstructionError>;
+
+#[derive(Clone, Debug, Default)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+impl From<Account> for TestAccount {
+    fn from(account: Account) -> Self {
+        Self {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+        }
+    }
+}
+
+impl From<TestAccount> for AccountSharedData {
+    fn from(account: TestAccount) -> Self {
+        Account {
+            lamports: account.lamports,
+            data: account.data,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: u64::MAX,
+        }
+        .into()
+    }
+}
+
+/// The program's entrypoint as a builtin. `entry` ties the account infos to the slice's
+/// lifetime, which the builtin signature leaves apart.
+fn process_nft_staking<'a, 'b>(program_id: &Pubkey, accounts: &'a [AccountInfo<'b>], data: &[u8]) -> ProgramResult {
+    let accounts = unsafe { std::mem::transmute::<&'a [AccountInfo<'b>], &'a [AccountInfo<'a>]>(accounts) };
+    nft_staking::entry(program_id, accounts, data)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> std::result::Result<Pubkey, ProgramError> {
+    data.get(offset..offset + 32)
+        .map(|bytes| Pubkey::try_from(bytes).unwrap())
+        .ok_or(ProgramError::InvalidAccountData)
+}
+
+/// Stands in for Metaplex Core when `mpl_core.so` is not in `tests/fixtures`. Handles
+/// `TransferV1` for uncompressed assets: the owner signs and the asset's collection must be
+/// the one passed.
+fn process_core_transfer(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.first() != Some(&nft_staking::CoreAsset::TRANSFER_V1_DISCRIMINATOR) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let account = |index: usize| accounts.get(index).ok_or(ProgramError::NotEnoughAccountKeys);
+    let (asset, collection, authority, new_owner) = (account(0)?, account(1)?, account(3)?, account(4)?);
+    let mut asset_data = asset.try_borrow_mut_data()?;
+    if read_pubkey(&asset_data, 1)? != *authority.key || !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if asset_data.get(33) != Some(&2) || read_pubkey(&asset_data, 34)? != *collection.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    asset_data[1..33].copy_from_slice(new_owner.key.as_ref());
+    Ok(())
+}
+
+pub struct TestEnv {
+    runtime: tokio::runtime::Runtime,
+    context: ProgramTestContext,
+    /// Keypairs of every account the harness created that may sign.
+    signers: HashMap<Pubkey, Keypair>,
+    /// Transactions that landed, to refresh the blockhash before sending an identical one.
+    landed: HashSet<Signature>,
+    clock: Clock,
+    pub payer: Pubkey,
+    events: Vec<Vec<u8>>,
+    logs: Vec<String>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+}
+
+impl TestEnv {
+    pub fn new() -> Self {
+        Self::with_programs(|_| {})
+    }
+
+    /// Starts the runtime after `add_programs` has added programs of the test's own.
+    pub fn with_programs(add_programs: impl FnOnce(&mut ProgramTest)) -> Self {
+        let mut program_test = ProgramTest::new("nft_staking", nft_staking::ID, processor!(process_nft_staking));
+        if solana_program_test::find_file("mpl_core.so").is_some() {
+            program_test.add_upgradeable_program_to_genesis("mpl_core", &nft_staking::MPL_CORE_ID);
+        } else {
+            // The stand-in only exists natively, also under `cargo test-sbf`
+            program_test.prefer_bpf(false);
+            program_test.add_program("mpl_core", nft_staking::MPL_CORE_ID, processor!(process_core_transfer));
+        }
+        add_programs(&mut program_test);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let context = runtime.block_on(program_test.start_with_context());
+        let clock = runtime.block_on(context.banks_client.get_sysvar::<Clock>()).unwrap();
+        let payer = context.payer.pubkey();
+        let mut env = Self {
+            runtime,
+            context,
+            signers: HashMap::new(),
+            landed: HashSet::new(),
+            clock,
+            payer,
+            events: Vec::new(),
+            logs: Vec::new(),
+            return_data: None,
+        };
+        env.set_time(START_TIME);
+        env
+    }
+
+    pub fn now(&self) -> i64 {
+        self.clock.unix_timestamp
+    }
+
+    pub fn set_time(&mut self, unix_timestamp: i64) {
+        self.clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&self.clock);
+    }
+
+    pub fn warp(&mut self, seconds: i64) {
+        self.set_time(self.now() + seconds);
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.clock.slot
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: TestAccount) {
+        self.context.set_account(&key, &account.into());
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<TestAccount> {
+        self.runtime
+            .block_on(
+                self.context
+                    .banks_client
+                    .get_account_with_commitment(*key, CommitmentLevel::Processed),
+            )
+            .unwrap()
+            .map(TestAccount::from)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        let mut account = self.account(key).unwrap_or(TestAccount {
+            owner: system_program::ID,
+            ..TestAccount::default()
+        });
+        account.lamports += lamports;
+        self.set_account(*key, account);
+    }
+
+    /// A new keypair the harness signs with whenever an instruction marks it as signer.
+    pub fn signer(&mut self) -> Pubkey {
+        let keypair = Keypair::new();
+        let key = keypair.pubkey();
+        self.signers.insert(key, keypair);
+        key
+    }
+
+    /// A new system account holding `lamports`, to act as a wallet.
+    pub fn wallet(&mut self, lamports: u64) -> Pubkey {
+        let key = self.signer();
+        self.airdrop(&key, lamports);
+        key
+    }
+
+    /// Deserializes an Anchor account, checking its discriminator.
+    pub fn fetch<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self.account(key).unwrap_or_else(|| panic!("account {key} does not exist"));
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        let account = self.account(key).unwrap_or_else(|| panic!("token account {key} does not exist"));
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    /// Overwrites the balance of a token account, e.g. to leave a vault short of what it owes.
+    pub fn set_token_balance(&mut self, key: &Pubkey, amount: u64) {
+        let mut account = self.account(key).unwrap_or_else(|| panic!("token account {key} does not exist"));
+        // `amount` follows the mint and the owner in both token programs' account layout
+        account.data[64..72].copy_from_slice(&amount.to_le_bytes());
+        self.set_account(*key, account);
+    }
+
+    /// Events delivered through event CPIs by the last successful instruction.
+    pub fn events<E: anchor_lang::Event>(&self) -> Vec<E> {
+        self.events
+            .iter()
+            .filter(|data| data.starts_with(E::DISCRIMINATOR))
+            .map(|data| E::deserialize(&mut &data[E::DISCRIMINATOR.len()..]).unwrap())
+            .collect()
+    }
+
+    /// Log messages of the last instruction, whether it succeeded or not.
+    pub fn logs(&self) -> &[String] {
+        &self.logs
+    }
+
+    pub fn return_data(&self) -> Option<&(Pubkey, Vec<u8>)> {
+        self.return_data.as_ref()
+    }
+
+    /// Runs a program instruction built from its Anchor argument and account structs.
+    pub fn run(
+        &mut self,
+        args: impl InstructionData,
+        accounts: impl ToAccountMetas,
+        remaining_accounts: &[AccountMeta],
+    ) -> TestResult {
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend_from_slice(remaining_accounts);
+        self.process(&Instruction {
+            program_id: nft_staking::ID,
+            accounts: metas,
+            data: args.data(),
+        })
+    }
+
+    /// Sends `instruction` in a transaction of its own, paid by `payer` and signed by every
+    /// account its metas mark as signer. The transaction is simulated first, for the event
+    /// CPIs among its inner instructions, and only sent if that succeeds.
+    pub fn process(&mut self, instruction: &Instruction) -> TestResult {
+        let mut transaction = self.sign(instruction);
+        if self.landed.contains(&transaction.signatures[0]) {
+            self.runtime.block_on(self.context.get_new_latest_blockhash()).unwrap();
+            transaction = self.sign(instruction);
+        }
+
+        let simulation = self
+            .runtime
+            .block_on(
+                self.context
+                    .banks_client
+                    .simulate_transaction_with_commitment(transaction.clone(), CommitmentLevel::Processed),
+            )
+            .unwrap();
+        let details = simulation.simulation_details.unwrap_or_else(|| {
+            panic!("transaction was not simulated: {:?}", simulation.result)
+        });
+        self.logs = details.logs;
+        match simulation.result.unwrap() {
+            Ok(()) => {}
+            Err(TransactionError::InstructionError(_, error)) => return Err(error),
+            Err(error) => panic!("transaction failed outside its instruction: {error}"),
+        }
+
+        self.runtime
+            .block_on(
+                self.context
+                    .banks_client
+                    .process_transaction_with_commitment(transaction.clone(), CommitmentLevel::Processed),
+            )
+            .unwrap();
+        self.landed.insert(transaction.signatures[0]);
+
+        let account_keys = &transaction.message.account_keys;
+        self.events = details
+            .inner_instructions
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .map(|inner| inner.instruction)
+            .filter(|inner| {
+                account_keys[inner.program_id_index as usize] == nft_staking::ID
+                    && inner.data.starts_with(EVENT_IX_TAG_LE)
+            })
+            .map(|inner| inner.data[EVENT_IX_TAG_LE.len()..].to_vec())
+            .collect();
+        self.return_data = details
+            .return_data
+            .filter(|return_data| !return_data.data.is_empty())
+            .map(|return_data| (return_data.program_id, return_data.data));
+        Ok(())
+    }
+
+    fn sign(&self, instruction: &Instruction) -> Transaction {
+        let mut signers: Vec<&Keypair> = vec![&self.context.payer];
+        for meta in instruction.accounts.iter().filter(|meta| meta.is_signer) {
+            if meta.pubkey != self.payer && !signers.iter().any(|signer| signer.pubkey() == meta.pubkey) {
+                let keypair = self
+                    .signers
+                    .get(&meta.pubkey)
+                    .unwrap_or_else(|| panic!("no keypair for signer {}", meta.pubkey));
+                signers.push(keypair);
+            }
+        }
+        Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.payer),
+            &signers,
+            self.context.last_blockhash,
+        )
+    }
+
+    pub fn create_mint(&mut self, token_program: &Pubkey, decimals: u8, authority: &Pubkey) -> Pubkey {
+        self.create_mint_with_extensions(token_program, decimals, authority, &[], |_| Vec::new())
+    }
+
+    /// Creates a mint with room for `extensions`, running the instructions `init_extensions`
+    /// returns for the new mint before initializing it.
+    pub fn create_mint_with_extensions(
+        &mut self,
+        token_program: &Pubkey,
+        decimals: u8,
+        authority: &Pubkey,
+        extensions: &[ExtensionType],
+        init_extensions: impl FnOnce(&Pubkey) -> Vec<Instruction>,
+    ) -> Pubkey {
+        let mint = self.signer();
+        let space = if *token_program == spl_token::ID {
+            spl_token::state::Mint::LEN
+        } else {
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap()
+        };
+        let create = solana_system_interface::instruction::create_account(
+            &self.payer,
+            &mint,
+            Rent::default().minimum_balance(space),
+            space as u64,
+            token_program,
+        );
+        self.process(&create).unwrap();
+        for instruction in init_extensions(&mint) {
+            self.process(&instruction).unwrap();
+        }
+        let initialize = spl_token_2022::instruction::initialize_mint2(
+            token_program,
+            &mint,
+            authority,
+            Some(authority),
+            decimals,
+        )
+        .unwrap();
+        self.process(&initialize).unwrap();
+        mint
+    }
+
+    /// Creates `owner`'s associated token account for `mint`.
+    pub fn create_ata(&mut self, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        let create = spl_associated_token_account::instruction::create_associated_token_account(
+            &self.payer,
+            owner,
+            mint,
+            token_program,
+        );
+        self.process(&create).unwrap();
+        get_associated_token_address_with_program_id(owner, mint, token_program)
+    }
+
+    pub fn mint_to(
+        &mut self,
+        token_program: &Pubkey,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) {
+        let mint_to =
+            spl_token_2022::instruction::mint_to(token_program, mint, destination, authority, &[], amount)
+                .unwrap();
+        self.process(&mint_to).unwrap();
+    }
+}
+
+/// The `InstructionError` a failed instruction returns for `error`.
+pub fn program_error(error: impl Into<anchor_lang::error::Error>) -> InstructionError {
+    let error: ProgramError = error.into().into();
+    InstructionError::from(u64::from(error))
+}
+
+pub fn pool_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"pool"], &nft_staking::ID).0
+}
+
+pub fn reward_vault_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"reward_vault"], &nft_staking::ID).0
+}
+
+pub fn sol_vault_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"sol_vault"], &nft_staking::ID).0
+}
+
+pub fn event_authority_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &nft_staking::ID).0
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", mpl_token_metadata::ID.as_ref(), mint.as_ref()],
+        &mpl_token_metadata::ID,
+    )
+    .0
+}
+
+pub fn stake_entry_address(user: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stake_entry", user.as_ref(), mint.as_ref()], &nft_staking::ID).0
+}
+
+pub fn nft_vault_address(user: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"nft_vault", user.as_ref(), mint.as_ref()], &nft_staking::ID).0
+}
+
+pub fn reward_iou_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"reward_iou", owner.as_ref()], &nft_staking::ID).0
+}
+
+pub fn stream_reward_iou_address(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stream_reward_iou", owner.as_ref()], &nft_staking::ID).0
+}
+
+pub fn campaign_mint_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"campaign_mint", mint.as_ref()], &nft_staking::ID).0
+}
+
+pub fn reward_stream_address(id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"reward_stream", &id.to_le_bytes()], &nft_staking::ID).0
+}
+
+pub fn receipt_mint_address(nft_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"receipt_mint", nft_mint.as_ref()], &nft_staking::ID).0
+}
+
+pub fn stream_vault_address(reward_stream: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"stream_vault", reward_stream.as_ref()], &nft_staking::ID).0
+}
+
+/// Borsh encoding of Metaplex `Metadata` naming `collection`, with no creators or uses.
+pub fn metaplex_metadata(mint: &Pubkey, collection: &Pubkey, verified: bool) -> Vec<u8> {
+    let mut data = vec![4]; // Key::MetadataV1
+    data.extend_from_slice(Pubkey::new_unique().as_ref()); // update authority
+    data.extend_from_slice(mint.as_ref());
+    for field in ["Staked NFT", "NFT", ""] {
+        data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        data.extend_from_slice(field.as_bytes());
+    }
+    data.extend_from_slice(&0u16.to_le_bytes()); // seller fee
+    data.extend_from_slice(&[0, 0, 1, 0]); // creators, primary sale, mutable, edition nonce
+    data.extend_from_slice(&[1, 0]); // token standard: NonFungible
+    data.extend_from_slice(&[1, verified as u8]);
+    data.extend_from_slice(collection.as_ref());
+    data.extend_from_slice(&[0, 0, 0]); // uses, collection details, programmable config
+    data
+}
+
+/// A pool paying rewards in `reward_mint` as `reward_mode` says, whose authority is `admin`.
+pub struct TestPool {
+    pub admin: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_token_program: Pubkey,
+    pub reward_mode: nft_staking::RewardMode,
+}
+
+impl TestPool {
+    pub fn pays_in_sol(&self) -> bool {
+        self.reward_mode == nft_staking::RewardMode::Sol
+    }
+
+    /// The reward mint as an optional payout account, left out when the pool pays in SOL.
+    pub fn token_reward_mint(&self) -> Option<Pubkey> {
+        (!self.pays_in_sol()).then_some(self.reward_mint)
+    }
+
+    /// The reward vault as an optional payout account, left out when the pool pays in SOL.
+    pub fn token_reward_vault(&self) -> Option<Pubkey> {
+        (!self.pays_in_sol()).then(reward_vault_address)
+    }
+
+    /// `owner`'s reward token account as an optional payout account, left out when the pool
+    /// pays in SOL.
+    pub fn token_rewards_of(&self, owner: &Pubkey) -> Option<Pubkey> {
+        (!self.pays_in_sol()).then(|| self.rewards_of(owner))
+    }
+
+    /// The SOL vault, only passed when the pool pays in SOL.
+    pub fn sol_vault(&self) -> Option<Pubkey> {
+        self.pays_in_sol().then(sol_vault_address)
+    }
+
+    pub fn admin_rewards(&self) -> Pubkey {
+        get_associated_token_address_with_program_id(&self.admin, &self.reward_mint, &self.reward_token_program)
+    }
+
+    pub fn rewards_of(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.reward_mint, &self.reward_token_program)
+    }
+}
+
+impl TestEnv {
+    /// Initializes a vault-mode pool over a new SPL Token reward mint, minting the admin
+    /// `admin_balance` of it.
+    pub fn setup_pool(&mut self, reward_strategy: nft_staking::RewardStrategy, admin_balance: u64) -> TestPool {
+        let admin = self.wallet(100 * LAMPORTS_PER_SOL);
+        let reward_mint = self.create_mint(&spl_token::ID, 6, &admin);
+        let pool = TestPool {
+            admin,
+            reward_mint,
+            reward_token_program: spl_token::ID,
+            reward_mode: nft_staking::RewardMode::Vault,
+        };
+        self.create_ata(&admin, &reward_mint, &spl_token::ID);
+        if admin_balance > 0 {
+            self.mint_to(&spl_token::ID, &reward_mint, &pool.admin_rewards(), &admin, admin_balance);
+        }
+        self.initialize_pool(&pool, reward_strategy).unwrap();
+        pool
+    }
+
+    /// Initializes a minting pool over a new SPL Token reward mint whose mint authority is the
+    /// pool, capped at `emission_cap`.
+    pub fn setup_minting_pool(&mut self, reward_strategy: nft_staking::RewardStrategy, emission_cap: u64) -> TestPool {
+        let admin = self.wallet(100 * LAMPORTS_PER_SOL);
+        let reward_mint = self.create_mint(&spl_token::ID, 6, &pool_address());
+        let pool = TestPool {
+            admin,
+            reward_mint,
+            reward_token_program: spl_token::ID,
+            reward_mode: nft_staking::RewardMode::Mint,
+        };
+        self.create_ata(&admin, &reward_mint, &spl_token::ID);
+        self.initialize_pool_capped(&pool, emission_cap, reward_strategy).unwrap();
+        pool
+    }
+
+    /// Initializes a pool paying SOL from its SOL vault.
+    pub fn setup_sol_pool(&mut self, reward_strategy: nft_staking::RewardStrategy) -> TestPool {
+        let pool = TestPool {
+            admin: self.wallet(100 * LAMPORTS_PER_SOL),
+            reward_mint: spl_token::native_mint::ID,
+            reward_token_program: spl_token::ID,
+            reward_mode: nft_staking::RewardMode::Sol,
+        };
+        self.initialize_pool(&pool, reward_strategy).unwrap();
+        pool
+    }
+
+    pub fn initialize_pool(&mut self, pool: &TestPool, reward_strategy: nft_staking::RewardStrategy) -> TestResult {
+        self.initialize_pool_capped(pool, 0, reward_strategy)
+    }
+
+    /// Initializes the pool in `pool.reward_mode`, capping minted rewards at `emission_cap`.
+    pub fn initialize_pool_capped(
+        &mut self,
+        pool: &TestPool,
+        emission_cap: u64,
+        reward_strategy: nft_staking::RewardStrategy,
+    ) -> TestResult {
+        self.run(
+            nft_staking::instruction::InitializePool {
+                reward_mode: pool.reward_mode,
+                emission_cap,
+                reward_strategy,
+            },
+            nft_staking::accounts::InitializePool {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_mint: pool.reward_mint,
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                system_program: system_program::ID,
+                token_program: pool.reward_token_program,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn add_reward(&mut self, pool: &TestPool, amount: u64, epochs: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::AddReward {
+                total_reward_amount: amount,
+                num_epochs: epochs,
+            },
+            nft_staking::accounts::AddReward {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_vault: reward_vault_address(),
+                reward_mint: pool.reward_mint,
+                admin_reward_token_account: pool.admin_rewards(),
+                token_program: pool.reward_token_program,
+                system_program: system_program::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn schedule_emission(&mut self, pool: &TestPool, amount: u64, epochs: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::ScheduleEmission {
+                total_reward_amount: amount,
+                num_epochs: epochs,
+            },
+            nft_staking::accounts::ScheduleEmission {
+                pool: pool_address(),
+                admin: pool.admin,
+                system_program: system_program::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn add_sol_reward(&mut self, pool: &TestPool, lamports: u64, epochs: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::AddSolReward {
+                total_lamports: lamports,
+                num_epochs: epochs,
+            },
+            nft_staking::accounts::AddSolReward {
+                pool: pool_address(),
+                admin: pool.admin,
+                sol_vault: sol_vault_address(),
+                system_program: system_program::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn admin_claim_sol(&mut self, pool: &TestPool, amount: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::AdminClaimSol { amount },
+            nft_staking::accounts::AdminClaimSol {
+                pool: pool_address(),
+                admin: pool.admin,
+                sol_vault: sol_vault_address(),
+                system_program: system_program::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn admin_claim(&mut self, pool: &TestPool, amount: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::AdminClaim { amount },
+            nft_staking::accounts::AdminClaim {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_vault: reward_vault_address(),
+                reward_mint: pool.reward_mint,
+                admin_reward_token_account: pool.admin_rewards(),
+                token_program: pool.reward_token_program,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn add_collection(&mut self, pool: &TestPool, collection: &Pubkey, kind: nft_staking::CollectionKind) -> TestResult {
+        self.run(
+            nft_staking::instruction::AddCollection {
+                collection_mint: *collection,
+                kind,
+            },
+            nft_staking::accounts::ManageCollection {
+                pool: pool_address(),
+                admin: pool.admin,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn set_collection_supply(&mut self, pool: &TestPool, collection: &Pubkey, total_supply: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetCollectionSupply {
+                collection_mint: *collection,
+                total_supply,
+            },
+            nft_staking::accounts::ManageCollection {
+                pool: pool_address(),
+                admin: pool.admin,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn set_utilization_curve(&mut self, pool: &TestPool, curve: Option<nft_staking::UtilizationCurve>) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetUtilizationCurve { curve },
+            nft_staking::accounts::SetUtilizationCurve {
+                pool: pool_address(),
+                admin: pool.admin,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn set_fixed_rate_terms(&mut self, pool: &TestPool, terms: nft_staking::FixedRateTerms) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetFixedRateTerms { terms },
+            nft_staking::accounts::SetFixedRateTerms {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn set_emission_curve(&mut self, pool: &TestPool, schedule: Option<nft_staking::EmissionSchedule>) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetEmissionCurve { schedule },
+            nft_staking::accounts::SetEmissionCurve {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Reads the pool's statistics through `get_pool_stats`.
+    pub fn pool_stats(&mut self, pool: &TestPool) -> nft_staking::PoolStats {
+        self.run(
+            nft_staking::instruction::GetPoolStats {},
+            nft_staking::accounts::GetPoolStats {
+                pool: pool_address(),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+            },
+            &[],
+        )
+        .unwrap();
+        let (program_id, data) = self.return_data().unwrap();
+        assert_eq!(*program_id, nft_staking::ID);
+        nft_staking::PoolStats::try_from_slice(data).unwrap()
+    }
+
+    /// An SPL Token NFT held by `owner`, with Metaplex metadata naming `collection`.
+    pub fn create_metaplex_nft(&mut self, owner: &Pubkey, collection: &Pubkey, verified: bool) -> Pubkey {
+        let authority = self.signer();
+        let mint = self.create_mint(&spl_token::ID, 0, &authority);
+        let token_account = self.create_ata(owner, &mint, &spl_token::ID);
+        self.mint_to(&spl_token::ID, &mint, &token_account, &authority, 1);
+        let data = metaplex_metadata(&mint, collection, verified);
+        self.set_account(
+            metadata_address(&mint),
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: mpl_token_metadata::ID,
+                executable: false,
+            },
+        );
+        mint
+    }
+
+    /// Like `create_metaplex_nft`, with the pool as the mint's freeze authority so that it can
+    /// be staked in place.
+    pub fn create_freezable_nft(&mut self, owner: &Pubkey, collection: &Pubkey) -> Pubkey {
+        let mint = self.create_metaplex_nft(owner, collection, true);
+        let authority = spl_token::state::Mint::unpack(&self.account(&mint).unwrap().data)
+            .unwrap()
+            .mint_authority
+            .unwrap();
+        let set_authority = spl_token::instruction::set_authority(
+            &spl_token::ID,
+            &mint,
+            Some(&pool_address()),
+            spl_token::instruction::AuthorityType::FreezeAccount,
+            &authority,
+            &[],
+        );
+        self.process(&set_authority.unwrap()).unwrap();
+        mint
+    }
+
+    /// A Token-2022 group mint, pointing at itself for its `TokenGroup`. The payer is its mint
+    /// and update authority.
+    pub fn create_token_group(&mut self) -> Pubkey {
+        use spl_token_2022::extension::group_pointer;
+
+        let payer = self.payer;
+        let group = self.create_mint_with_extensions(
+            &spl_token_2022::ID,
+            0,
+            &payer,
+            &[ExtensionType::GroupPointer],
+            |mint| vec![group_pointer::instruction::initialize(&spl_token_2022::ID, mint, None, Some(*mint)).unwrap()],
+        );
+        self.fund_extensions(&group, &[ExtensionType::GroupPointer, ExtensionType::TokenGroup]);
+        let initialize = spl_token_group_interface::instruction::initialize_group(
+            &spl_token_2022::ID,
+            &group,
+            &group,
+            &payer,
+            Some(payer),
+            1_000,
+        );
+        self.process(&initialize).unwrap();
+        group
+    }
+
+    /// A Token-2022 NFT held by `owner`, a member of `group` through a `TokenGroupMember` on
+    /// the mint itself.
+    pub fn create_group_member_nft(&mut self, owner: &Pubkey, group: &Pubkey) -> Pubkey {
+        let payer = self.payer;
+        let mint = self.create_group_member_mint(group, |mint| mint);
+        let token_account = self.create_ata(owner, &mint, &spl_token_2022::ID);
+        self.mint_to(&spl_token_2022::ID, &mint, &token_account, &payer, 1);
+        mint
+    }
+
+    /// A Token-2022 mint with no supply whose `GroupMemberPointer` points at `member_of(mint)`,
+    /// made a member of `group` if that is the mint itself. The payer is its mint authority.
+    pub fn create_group_member_mint(&mut self, group: &Pubkey, member_of: impl FnOnce(Pubkey) -> Pubkey) -> Pubkey {
+        use spl_token_2022::extension::group_member_pointer;
+
+        let payer = self.payer;
+        let mut member = None;
+        let mint = self.create_mint_with_extensions(
+            &spl_token_2022::ID,
+            0,
+            &payer,
+            &[ExtensionType::GroupMemberPointer],
+            |mint| {
+                member = Some(member_of(*mint));
+                vec![group_member_pointer::instruction::initialize(&spl_token_2022::ID, mint, None, member).unwrap()]
+            },
+        );
+        if member == Some(mint) {
+            self.fund_extensions(&mint, &[ExtensionType::GroupMemberPointer, ExtensionType::TokenGroupMember]);
+            let initialize = spl_token_group_interface::instruction::initialize_member(
+                &spl_token_2022::ID,
+                &mint,
+                &mint,
+                &payer,
+                group,
+                &payer,
+            );
+            self.process(&initialize).unwrap();
+        }
+        mint
+    }
+
+    /// Tops `mint` up to stay rent exempt once Token-2022 has grown it to hold `extensions`.
+    fn fund_extensions(&mut self, mint: &Pubkey, extensions: &[ExtensionType]) {
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let shortfall = Rent::default().minimum_balance(space) - self.lamports(mint);
+        self.airdrop(mint, shortfall);
+    }
+
+    pub fn stake(&mut self, user: &Pubkey, nft_mint: &Pubkey, nft_token_program: &Pubkey) -> TestResult {
+        self.stake_with_streams(user, nft_mint, nft_token_program, &[])
+    }
+
+    /// Stakes with the pool's active `reward_streams`, in slot order, to checkpoint the entry in.
+    pub fn stake_with_streams(
+        &mut self,
+        user: &Pubkey,
+        nft_mint: &Pubkey,
+        nft_token_program: &Pubkey,
+        reward_streams: &[Pubkey],
+    ) -> TestResult {
+        let pays_in_sol = self.fetch::<nft_staking::Pool>(&pool_address()).reward_mode == nft_staking::RewardMode::Sol;
+        self.run(
+            nft_staking::instruction::Stake {},
+            nft_staking::accounts::Stake {
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                nft_metadata_account: metadata_address(nft_mint),
+                stake_entry: stake_entry_address(user, nft_mint),
+                user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, nft_token_program),
+                nft_vault: nft_vault_address(user, nft_mint),
+                system_program: system_program::ID,
+                token_program: *nft_token_program,
+                reward_vault: (!pays_in_sol).then(reward_vault_address),
+                sol_vault: pays_in_sol.then(sol_vault_address),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &reward_streams.iter().map(|key| AccountMeta::new(*key, false)).collect::<Vec<_>>(),
+        )
+    }
+
+    pub fn set_reward_delegate(&mut self, user: &Pubkey, nft_mint: &Pubkey, reward_delegate: Option<Pubkey>, delegate_share_bps: u16) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetRewardDelegate {
+                reward_delegate,
+                delegate_share_bps,
+            },
+            nft_staking::accounts::SetRewardDelegate {
+                user: *user,
+                pool: pool_address(),
+                stake_entry: stake_entry_address(user, nft_mint),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Claims `amount` (everything with `None`) of what `user`'s position earned into their own
+    /// reward token account.
+    pub fn claim_reward(&mut self, pool: &TestPool, user: &Pubkey, nft_mint: &Pubkey, amount: Option<u64>) -> TestResult {
+        self.claim_reward_to(pool, user, user, nft_mint, amount, None)
+    }
+
+    /// Claims as `authority`, the owner or the reward delegate, paying the owner's share to
+    /// `recipient` if given and the delegate's share to its own reward token account.
+    pub fn claim_reward_to(
+        &mut self,
+        pool: &TestPool,
+        authority: &Pubkey,
+        user: &Pubkey,
+        nft_mint: &Pubkey,
+        amount: Option<u64>,
+        recipient: Option<Pubkey>,
+    ) -> TestResult {
+        self.claim_reward_with_streams(pool, authority, user, nft_mint, amount, recipient, &[])
+    }
+
+    /// Claims like `claim_reward_to`, also paying out the pool's reward streams through
+    /// `stream_accounts`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn claim_reward_with_streams(
+        &mut self,
+        pool: &TestPool,
+        authority: &Pubkey,
+        user: &Pubkey,
+        nft_mint: &Pubkey,
+        amount: Option<u64>,
+        recipient: Option<Pubkey>,
+        stream_accounts: &[AccountMeta],
+    ) -> TestResult {
+        let entry: nft_staking::NftStakeEntry = self.fetch(&stake_entry_address(user, nft_mint));
+        let reward_delegate = (entry.reward_delegate != Pubkey::default()).then_some(entry.reward_delegate);
+        let delegate_reward_token_account = reward_delegate.and_then(|delegate| pool.token_rewards_of(&delegate));
+        self.run(
+            nft_staking::instruction::ClaimReward { amount },
+            nft_staking::accounts::ClaimReward {
+                authority: *authority,
+                user: *user,
+                pool: pool_address(),
+                reward_mint: pool.token_reward_mint(),
+                nft_mint: *nft_mint,
+                stake_entry: stake_entry_address(user, nft_mint),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                recipient_token_account: recipient,
+                delegate_reward_token_account,
+                reward_delegate: reward_delegate.filter(|_| pool.pays_in_sol()),
+                system_program: system_program::ID,
+                token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            stream_accounts,
+        )
+    }
+
+    pub fn unstake(&mut self, pool: &TestPool, user: &Pubkey, nft_mint: &Pubkey, nft_token_program: &Pubkey) -> TestResult {
+        self.unstake_with_streams(pool, user, nft_mint, nft_token_program, false, &[])
+    }
+
+    /// Unstakes with the `stream_accounts` of the pool's active reward streams, passing the
+    /// user's stream reward IOU if `with_stream_reward_iou`.
+    pub fn unstake_with_streams(
+        &mut self,
+        pool: &TestPool,
+        user: &Pubkey,
+        nft_mint: &Pubkey,
+        nft_token_program: &Pubkey,
+        with_stream_reward_iou: bool,
+        stream_accounts: &[AccountMeta],
+    ) -> TestResult {
+        self.run(
+            nft_staking::instruction::Unstake {},
+            nft_staking::accounts::Unstake {
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                stake_entry: stake_entry_address(user, nft_mint),
+                nft_vault: nft_vault_address(user, nft_mint),
+                user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, nft_token_program),
+                reward_mint: pool.token_reward_mint(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                delegate_reward_token_account: None,
+                reward_delegate: None,
+                reward_iou: reward_iou_address(user),
+                delegate_reward_iou: None,
+                stream_reward_iou: with_stream_reward_iou.then(|| stream_reward_iou_address(user)),
+                delegate_stream_reward_iou: None,
+                system_program: system_program::ID,
+                token_program: *nft_token_program,
+                reward_token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            stream_accounts,
+        )
+    }
+}
+
+impl TestEnv {
+    /// Stakes an SPL Token NFT by freezing it in `user`'s own token account.
+    pub fn stake_in_place(&mut self, user: &Pubkey, nft_mint: &Pubkey) -> TestResult {
+        let pays_in_sol = self.fetch::<nft_staking::Pool>(&pool_address()).reward_mode == nft_staking::RewardMode::Sol;
+        self.run(
+            nft_staking::instruction::StakeInPlace {},
+            nft_staking::accounts::StakeInPlace {
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                nft_metadata_account: metadata_address(nft_mint),
+                stake_entry: stake_entry_address(user, nft_mint),
+                user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, &spl_token::ID),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                reward_vault: (!pays_in_sol).then(reward_vault_address),
+                sol_vault: pays_in_sol.then(sol_vault_address),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn unstake_in_place(&mut self, pool: &TestPool, user: &Pubkey, nft_mint: &Pubkey) -> TestResult {
+        self.run(
+            nft_staking::instruction::UnstakeInPlace {},
+            nft_staking::accounts::UnstakeInPlace {
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                stake_entry: stake_entry_address(user, nft_mint),
+                user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, &spl_token::ID),
+                reward_mint: pool.token_reward_mint(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                delegate_reward_token_account: None,
+                reward_delegate: None,
+                reward_iou: reward_iou_address(user),
+                delegate_reward_iou: None,
+                stream_reward_iou: None,
+                delegate_stream_reward_iou: None,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                reward_token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Moves `user`'s vault position to `new_owner`, paying out what it earned so far.
+    pub fn transfer_stake(
+        &mut self,
+        pool: &TestPool,
+        user: &Pubkey,
+        new_owner: &Pubkey,
+        nft_mint: &Pubkey,
+        nft_token_program: &Pubkey,
+    ) -> TestResult {
+        self.run(
+            nft_staking::instruction::TransferStake {},
+            nft_staking::accounts::TransferStake {
+                user: *user,
+                new_owner: *new_owner,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                stake_entry: stake_entry_address(user, nft_mint),
+                nft_vault: nft_vault_address(user, nft_mint),
+                new_stake_entry: stake_entry_address(new_owner, nft_mint),
+                new_nft_vault: nft_vault_address(new_owner, nft_mint),
+                reward_mint: pool.token_reward_mint(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                delegate_reward_token_account: None,
+                reward_delegate: None,
+                reward_iou: reward_iou_address(user),
+                delegate_reward_iou: None,
+                stream_reward_iou: None,
+                delegate_stream_reward_iou: None,
+                system_program: system_program::ID,
+                token_program: *nft_token_program,
+                reward_token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+}
+
+impl TestEnv {
+    /// The receipt program for a plain receipt or, with `soulbound`, a non-transferable one.
+    fn receipt_token_program(soulbound: bool) -> Pubkey {
+        if soulbound {
+            spl_token_2022::ID
+        } else {
+            spl_token::ID
+        }
+    }
+
+    /// `holder`'s token account for the receipt of `nft_mint`'s position.
+    pub fn receipt_of(holder: &Pubkey, nft_mint: &Pubkey, soulbound: bool) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            holder,
+            &receipt_mint_address(nft_mint),
+            &Self::receipt_token_program(soulbound),
+        )
+    }
+
+    /// Stakes an SPL Token NFT and mints its receipt to `user`.
+    pub fn stake_with_receipt(&mut self, user: &Pubkey, nft_mint: &Pubkey, soulbound: bool) -> TestResult {
+        let pays_in_sol = self.fetch::<nft_staking::Pool>(&pool_address()).reward_mode == nft_staking::RewardMode::Sol;
+        self.run(
+            nft_staking::instruction::StakeWithReceipt { soulbound },
+            nft_staking::accounts::StakeWithReceipt {
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                nft_metadata_account: metadata_address(nft_mint),
+                stake_entry: stake_entry_address(user, nft_mint),
+                user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, &spl_token::ID),
+                nft_vault: nft_vault_address(user, nft_mint),
+                receipt_mint: receipt_mint_address(nft_mint),
+                user_receipt_token_account: Self::receipt_of(user, nft_mint, soulbound),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                receipt_token_program: Self::receipt_token_program(soulbound),
+                associated_token_program: spl_associated_token_account::ID,
+                reward_vault: (!pays_in_sol).then(reward_vault_address),
+                sol_vault: pays_in_sol.then(sol_vault_address),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Claims `amount` (everything with `None`) of the position `user` staked, as the holder
+    /// of its plain receipt.
+    pub fn claim_reward_with_receipt(
+        &mut self,
+        pool: &TestPool,
+        holder: &Pubkey,
+        user: &Pubkey,
+        nft_mint: &Pubkey,
+        amount: Option<u64>,
+    ) -> TestResult {
+        self.run(
+            nft_staking::instruction::ClaimRewardWithReceipt { amount },
+            nft_staking::accounts::ClaimRewardWithReceipt {
+                holder: *holder,
+                pool: pool_address(),
+                reward_mint: pool.token_reward_mint(),
+                stake_entry: stake_entry_address(user, nft_mint),
+                receipt_mint: receipt_mint_address(nft_mint),
+                holder_receipt_token_account: Self::receipt_of(holder, nft_mint, false),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                holder_reward_token_account: pool.token_rewards_of(holder),
+                system_program: system_program::ID,
+                token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Redeems the position `user` staked as the holder of its plain receipt.
+    pub fn unstake_with_receipt(&mut self, pool: &TestPool, holder: &Pubkey, user: &Pubkey, nft_mint: &Pubkey) -> TestResult {
+        self.run(
+            nft_staking::instruction::UnstakeWithReceipt {},
+            nft_staking::accounts::UnstakeWithReceipt {
+                holder: *holder,
+                user: *user,
+                pool: pool_address(),
+                nft_mint: *nft_mint,
+                stake_entry: stake_entry_address(user, nft_mint),
+                nft_vault: nft_vault_address(user, nft_mint),
+                holder_nft_token_account: get_associated_token_address_with_program_id(holder, nft_mint, &spl_token::ID),
+                receipt_mint: receipt_mint_address(nft_mint),
+                holder_receipt_token_account: Self::receipt_of(holder, nft_mint, false),
+                reward_mint: pool.token_reward_mint(),
+                holder_reward_token_account: pool.token_rewards_of(holder),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                reward_iou: reward_iou_address(holder),
+                stream_reward_iou: None,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                reward_token_program: pool.reward_token_program,
+                receipt_token_program: spl_token::ID,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+}
+
+impl TestEnv {
+    /// A Metaplex Core collection account, laid out as `BaseCollectionV1` with no plugins.
+    pub fn create_core_collection(&mut self) -> Pubkey {
+        let collection = Pubkey::new_unique();
+        let mut data = vec![5]; // Key::CollectionV1
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        for field in ["Staked collection", ""] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.extend_from_slice(&[0; 8]); // num_minted, current_size
+        self.set_account(
+            collection,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: nft_staking::MPL_CORE_ID,
+                executable: false,
+            },
+        );
+        collection
+    }
+
+    /// A Metaplex Core asset owned by `owner` in `collection`.
+    pub fn create_core_asset(&mut self, owner: &Pubkey, collection: &Pubkey) -> Pubkey {
+        let asset = Pubkey::new_unique();
+        let mut data = vec![nft_staking::CoreAsset::ASSET_V1_KEY];
+        data.extend_from_slice(owner.as_ref());
+        data.push(2); // UpdateAuthority::Collection
+        data.extend_from_slice(collection.as_ref());
+        for field in ["Staked asset", ""] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.push(0); // seq
+        self.set_account(
+            asset,
+            TestAccount {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: nft_staking::MPL_CORE_ID,
+                executable: false,
+            },
+        );
+        asset
+    }
+
+    /// The owner recorded in a Core asset.
+    pub fn core_asset_owner(&self, asset: &Pubkey) -> Pubkey {
+        Pubkey::try_from(&self.account(asset).unwrap().data[1..33]).unwrap()
+    }
+
+    pub fn stake_core(&mut self, user: &Pubkey, asset: &Pubkey, collection: &Pubkey) -> TestResult {
+        let pays_in_sol = self.fetch::<nft_staking::Pool>(&pool_address()).reward_mode == nft_staking::RewardMode::Sol;
+        self.run(
+            nft_staking::instruction::StakeCore {},
+            nft_staking::accounts::StakeCore {
+                user: *user,
+                pool: pool_address(),
+                asset: *asset,
+                collection: *collection,
+                stake_entry: stake_entry_address(user, asset),
+                mpl_core_program: nft_staking::MPL_CORE_ID,
+                system_program: system_program::ID,
+                reward_vault: (!pays_in_sol).then(reward_vault_address),
+                sol_vault: pays_in_sol.then(sol_vault_address),
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn unstake_core(&mut self, pool: &TestPool, user: &Pubkey, asset: &Pubkey, collection: &Pubkey) -> TestResult {
+        self.run(
+            nft_staking::instruction::UnstakeCore {},
+            nft_staking::accounts::UnstakeCore {
+                user: *user,
+                pool: pool_address(),
+                asset: *asset,
+                collection: *collection,
+                stake_entry: stake_entry_address(user, asset),
+                reward_mint: pool.token_reward_mint(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                delegate_reward_token_account: None,
+                reward_delegate: None,
+                reward_iou: reward_iou_address(user),
+                delegate_reward_iou: None,
+                stream_reward_iou: None,
+                delegate_stream_reward_iou: None,
+                mpl_core_program: nft_staking::MPL_CORE_ID,
+                system_program: system_program::ID,
+                token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+}
+
+/// A sponsor holding `reward_mint`, an SPL Token mint it is also the mint and freeze
+/// authority of.
+pub struct TestSponsor {
+    pub funder: Pubkey,
+    pub reward_mint: Pubkey,
+}
+
+impl TestSponsor {
+    pub fn rewards_of(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.reward_mint, &spl_token::ID)
+    }
+}
+
+impl TestEnv {
+    pub fn sponsor(&mut self, balance: u64) -> TestSponsor {
+        let funder = self.wallet(10 * LAMPORTS_PER_SOL);
+        let reward_mint = self.create_mint(&spl_token::ID, 6, &funder);
+        let funder_rewards = self.create_ata(&funder, &reward_mint, &spl_token::ID);
+        self.mint_to(&spl_token::ID, &reward_mint, &funder_rewards, &funder, balance);
+        TestSponsor { funder, reward_mint }
+    }
+
+    pub fn allow_campaign_mint(&mut self, pool: &TestPool, mint: &Pubkey, min_amount: u64) -> TestResult {
+        self.run(
+            nft_staking::instruction::AllowCampaignMint { min_amount },
+            nft_staking::accounts::AllowCampaignMint {
+                pool: pool_address(),
+                admin: pool.admin,
+                mint: *mint,
+                campaign_mint: campaign_mint_address(mint),
+                system_program: system_program::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn set_campaign_mints_restricted(&mut self, pool: &TestPool, restricted: bool) -> TestResult {
+        self.run(
+            nft_staking::instruction::SetCampaignMintsRestricted { restricted },
+            nft_staking::accounts::SetCampaignMintsRestricted {
+                pool: pool_address(),
+                admin: pool.admin,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// Creates a campaign paying `amount` over `days` days from now and returns its
+    /// `RewardStream`. The mint's `CampaignMint` is passed if the admin allowed it.
+    pub fn create_campaign(&mut self, sponsor: &TestSponsor, days: i64, amount: u64) -> std::result::Result<Pubkey, InstructionError> {
+        let pool: nft_staking::Pool = self.fetch(&pool_address());
+        let reward_stream = reward_stream_address(pool.next_reward_stream_id);
+        let campaign_mint = Some(campaign_mint_address(&sponsor.reward_mint)).filter(|key| self.account(key).is_some());
+        self.run(
+            nft_staking::instruction::CreateCampaign {
+                start_time: self.now(),
+                end_time: self.now() + days * DAY,
+                amount,
+            },
+            nft_staking::accounts::CreateCampaign {
+                funder: sponsor.funder,
+                pool: pool_address(),
+                reward_stream,
+                reward_mint: sponsor.reward_mint,
+                campaign_mint,
+                campaign_vault: stream_vault_address(&reward_stream),
+                funder_token_account: sponsor.rewards_of(&sponsor.funder),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )?;
+        Ok(reward_stream)
+    }
+
+    /// Opens a reward stream paying `reward_mint` and returns its `RewardStream`.
+    pub fn add_reward_stream(&mut self, pool: &TestPool, reward_mint: &Pubkey) -> std::result::Result<Pubkey, InstructionError> {
+        let state: nft_staking::Pool = self.fetch(&pool_address());
+        let reward_stream = reward_stream_address(state.next_reward_stream_id);
+        self.run(
+            nft_staking::instruction::AddRewardStream {},
+            nft_staking::accounts::AddRewardStream {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_stream,
+                stream_reward_mint: *reward_mint,
+                stream_vault: stream_vault_address(&reward_stream),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )?;
+        Ok(reward_stream)
+    }
+
+    /// Funds `reward_stream` with `amount` over `days` days from the admin's token account.
+    pub fn fund_reward_stream(&mut self, pool: &TestPool, reward_stream: &Pubkey, amount: u64, days: u64) -> TestResult {
+        let stream: nft_staking::RewardStream = self.fetch(reward_stream);
+        self.run(
+            nft_staking::instruction::FundRewardStream {
+                total_reward_amount: amount,
+                num_epochs: days,
+            },
+            nft_staking::accounts::FundRewardStream {
+                pool: pool_address(),
+                admin: pool.admin,
+                reward_stream: *reward_stream,
+                reward_vault: stream.reward_vault,
+                reward_mint: stream.reward_mint,
+                admin_reward_token_account: get_associated_token_address_with_program_id(&pool.admin, &stream.reward_mint, &spl_token::ID),
+                token_program: spl_token::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    /// The remaining accounts paying out `reward_stream` to `beneficiary`, who has no reward
+    /// delegate.
+    pub fn stream_payout_accounts(&self, reward_stream: &Pubkey, beneficiary: &Pubkey) -> Vec<AccountMeta> {
+        let stream: nft_staking::RewardStream = self.fetch(reward_stream);
+        vec![
+            AccountMeta::new(*reward_stream, false),
+            AccountMeta::new(stream.reward_vault, false),
+            AccountMeta::new_readonly(stream.reward_mint, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new(
+                get_associated_token_address_with_program_id(beneficiary, &stream.reward_mint, &spl_token::ID),
+                false,
+            ),
+        ]
+    }
+
+    pub fn claim_owed(&mut self, pool: &TestPool, user: &Pubkey) -> TestResult {
+        self.run(
+            nft_staking::instruction::ClaimOwed {},
+            nft_staking::accounts::ClaimOwed {
+                user: *user,
+                pool: pool_address(),
+                reward_mint: pool.token_reward_mint(),
+                reward_iou: reward_iou_address(user),
+                reward_vault: pool.token_reward_vault(),
+                sol_vault: pool.sol_vault(),
+                user_reward_token_account: pool.token_rewards_of(user),
+                system_program: system_program::ID,
+                token_program: pool.reward_token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+
+    pub fn claim_owed_stream_reward(&mut self, user: &Pubkey, reward_stream: &Pubkey) -> TestResult {
+        let stream: nft_staking::RewardStream = self.fetch(reward_stream);
+        self.run(
+            nft_staking::instruction::ClaimOwedStreamReward {},
+            nft_staking::accounts::ClaimOwedStreamReward {
+                user: *user,
+                pool: pool_address(),
+                stream_reward_iou: stream_reward_iou_address(user),
+                reward_stream: *reward_stream,
+                reward_mint: stream.reward_mint,
+                stream_vault: stream.reward_vault,
+                user_token_account: get_associated_token_address_with_program_id(user, &stream.reward_mint, &spl_token::ID),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority_address(),
+                program: nft_staking::ID,
+            },
+            &[],
+        )
+    }
+}
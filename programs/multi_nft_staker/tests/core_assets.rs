@@ -0,0 +1,111 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, NftStakeEntry, Pool, RewardStrategy, StakeCustody};
+
+fn core_pool(env: &mut TestEnv) -> (TestPool, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = env.create_core_collection();
+    env.add_collection(&pool, &collection, CollectionKind::MplCore).unwrap();
+    (pool, collection)
+}
+
+#[test]
+fn core_asset_round_trips_through_its_stake_entry() {
+    let mut env = TestEnv::new();
+    let (pool, collection) = core_pool(&mut env);
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let asset = env.create_core_asset(&user, &collection);
+
+    env.stake_core(&user, &asset, &collection).unwrap();
+
+    let stake_entry = stake_entry_address(&user, &asset);
+    assert_eq!(env.core_asset_owner(&asset), stake_entry);
+    let entry: NftStakeEntry = env.fetch(&stake_entry);
+    assert_eq!(entry.custody, StakeCustody::CoreAsset);
+    assert_eq!(entry.nft_mint, asset);
+    assert_eq!(entry.collection, collection);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 1);
+
+    env.warp(2 * DAY);
+    env.unstake_core(&pool, &user, &asset, &collection).unwrap();
+
+    assert_eq!(env.core_asset_owner(&asset), user);
+    assert!(env.account(&stake_entry).is_none());
+    let paid = env.token_balance(&pool.rewards_of(&user));
+    assert!(paid > 0);
+    assert_eq!(env.token_balance(&reward_vault_address()), 1_000_000 - paid);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 0);
+}
+
+#[test]
+fn stake_core_requires_the_assets_own_collection() {
+    let mut env = TestEnv::new();
+    let (_pool, collection) = core_pool(&mut env);
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let other_collection = env.create_core_collection();
+    let asset = env.create_core_asset(&user, &other_collection);
+
+    // Passing an allowed collection the asset is not part of
+    assert_eq!(
+        env.stake_core(&user, &asset, &collection),
+        Err(program_error(ErrorCode::NotPartOfCollection))
+    );
+    // Passing the asset's real collection, which is not allowed
+    assert_eq!(
+        env.stake_core(&user, &asset, &other_collection),
+        Err(program_error(ErrorCode::CollectionNotAllowed))
+    );
+    assert_eq!(env.core_asset_owner(&asset), user);
+}
+
+#[test]
+fn stake_core_only_accepts_collections_allowed_as_core() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = env.create_core_collection();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let asset = env.create_core_asset(&user, &collection);
+
+    assert_eq!(
+        env.stake_core(&user, &asset, &collection),
+        Err(program_error(ErrorCode::CollectionNotAllowed))
+    );
+}
+
+#[test]
+fn stake_core_rejects_someone_elses_asset() {
+    let mut env = TestEnv::new();
+    let (_pool, collection) = core_pool(&mut env);
+    let owner = env.wallet(10 * LAMPORTS_PER_SOL);
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let asset = env.create_core_asset(&owner, &collection);
+
+    assert_eq!(
+        env.stake_core(&user, &asset, &collection),
+        Err(program_error(ErrorCode::NotAssetOwner))
+    );
+}
+
+#[test]
+fn unstake_core_rejects_a_position_held_in_a_token_vault() {
+    let mut env = TestEnv::new();
+    let (pool, collection) = core_pool(&mut env);
+    let nft_collection = Pubkey::new_unique();
+    env.add_collection(&pool, &nft_collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &nft_collection, true);
+    env.stake(&user, &nft, &anchor_spl::token::ID).unwrap();
+
+    // An SPL mint is not owned by Metaplex Core, so it never passes for an asset
+    assert_eq!(
+        env.unstake_core(&pool, &user, &nft, &collection),
+        Err(program_error(anchor_lang::error::ErrorCode::ConstraintOwner))
+    );
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_some());
+}
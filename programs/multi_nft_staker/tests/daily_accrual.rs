@@ -0,0 +1,70 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, Pool, RewardStrategy};
+
+/// A pool paying `daily` for `days` days, with `count` NFTs of one collection minted to `user`.
+fn funded_pool(env: &mut TestEnv, daily: u64, days: u64, count: usize) -> (TestPool, Pubkey, Vec<Pubkey>) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, daily * days);
+    env.add_reward(&pool, daily * days, days).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nfts = (0..count).map(|_| env.create_metaplex_nft(&user, &collection, true)).collect();
+    (pool, user, nfts)
+}
+
+#[test]
+fn accrual_shares_each_day_by_the_nfts_staked_over_it() {
+    let mut env = TestEnv::new();
+    let (pool, user, nfts) = funded_pool(&mut env, 100_000, 10, 5);
+
+    // One change on each of days 0 to 5
+    for nft in &nfts {
+        env.stake(&user, nft, &spl_token::ID).unwrap();
+        env.warp(DAY);
+    }
+    env.unstake(&pool, &user, &nfts[0], &spl_token::ID).unwrap();
+
+    // Days 0 to 4 were shared by 1, 2, 3, 4 and 5 NFTs
+    let first_payout = 100_000 + 50_000 + 33_333 + 25_000 + 20_000;
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), first_payout);
+
+    // The last NFT, staked on day 4, shares day 4 by 5 and days 5 and 6 by 4
+    env.warp(2 * DAY);
+    env.unstake(&pool, &user, &nfts[4], &spl_token::ID).unwrap();
+    assert_eq!(
+        env.token_balance(&pool.rewards_of(&user)),
+        first_payout + 20_000 + 25_000 + 25_000
+    );
+}
+
+#[test]
+fn a_multi_day_gap_accrues_at_the_count_that_held_over_it() {
+    let mut env = TestEnv::new();
+    let (pool, user, nfts) = funded_pool(&mut env, 100_000, 20, 5);
+
+    // Stake one NFT a day, then leave the pool alone for ten days
+    for nft in &nfts {
+        env.stake(&user, nft, &spl_token::ID).unwrap();
+        env.warp(DAY);
+    }
+    env.warp(9 * DAY);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.last_update_calc_reward_nft_index, 4);
+
+    // Days 4 to 13 were all shared by the 5 NFTs
+    env.unstake(&pool, &user, &nfts[4], &spl_token::ID).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 10 * 20_000);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.last_update_calc_reward_nft_index, 14);
+    assert_eq!(state.total_staked, 4);
+
+    // The NFT staked on day 3 shares day 3 by 4, the gap by 5 and the days since by 4
+    env.warp(5 * DAY);
+    env.unstake(&pool, &user, &nfts[3], &spl_token::ID).unwrap();
+    let second_payout = 25_000 + 10 * 20_000 + 5 * 25_000;
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 10 * 20_000 + second_payout);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, CurveSegment, EmissionCurve, EmissionCurveSet, EmissionSchedule, ErrorCode, RewardStrategy};
+
+/// A pool with no day-by-day schedule and `funded` put straight into its vault, with one NFT of
+/// an allowed collection minted to a new user.
+fn curve_pool(env: &mut TestEnv, funded: u64) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    env.mint_to(&spl_token::ID, &pool.reward_mint, &reward_vault_address(), &pool.admin, funded);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, nft)
+}
+
+fn schedule(curve: EmissionCurve, start_day: u64, end_day: u64) -> Option<EmissionSchedule> {
+    Some(EmissionSchedule { curve, start_day, end_day })
+}
+
+#[test]
+fn stakers_earn_the_integral_of_each_curve() {
+    let segments = vec![
+        CurveSegment { start_offset: 0, daily: 100_000 },
+        CurveSegment { start_offset: 1, daily: 10_000 },
+        CurveSegment { start_offset: 3, daily: 0 },
+    ];
+    let curves = [
+        (EmissionCurve::Constant { daily: 100_000 }, 400_000),
+        (EmissionCurve::LinearDecay { start_daily: 100_000, decay_per_day: 25_000 }, 250_000),
+        (EmissionCurve::Halving { start_daily: 100_000, halving_interval: 2 }, 300_000),
+        (EmissionCurve::Piecewise { segments }, 120_000),
+    ];
+    for (curve, earned) in curves {
+        let mut env = TestEnv::new();
+        let (pool, user, nft) = curve_pool(&mut env, 1_000_000);
+        env.set_emission_curve(&pool, schedule(curve.clone(), 0, 10)).unwrap();
+        env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+        env.warp(4 * DAY);
+        env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+        assert_eq!(env.token_balance(&pool.rewards_of(&user)), earned, "{curve:?}");
+    }
+}
+
+#[test]
+fn the_vault_must_cover_the_rest_of_the_curve() {
+    let mut env = TestEnv::new();
+    let (pool, _, _) = curve_pool(&mut env, 300_000);
+    let constant = EmissionCurve::Constant { daily: 100_000 };
+
+    assert_eq!(
+        env.set_emission_curve(&pool, schedule(constant.clone(), 0, 4)),
+        Err(program_error(ErrorCode::EmissionsNotCovered))
+    );
+    env.set_emission_curve(&pool, schedule(constant.clone(), 0, 3)).unwrap();
+    let set = env.events::<EmissionCurveSet>();
+    assert_eq!((set[0].pool, set[0].old_schedule.clone()), (pool_address(), None));
+    assert_eq!(set[0].new_schedule, schedule(constant.clone(), 0, 3));
+    assert_eq!((set[0].committed_rewards, set[0].available_rewards), (300_000, 300_000));
+
+    // Funding the vault is all it takes to extend it
+    env.mint_to(&spl_token::ID, &pool.reward_mint, &reward_vault_address(), &pool.admin, 100_000);
+    env.set_emission_curve(&pool, schedule(constant, 0, 4)).unwrap();
+
+    let stranger = TestPool { admin: env.wallet(LAMPORTS_PER_SOL), ..pool };
+    assert!(env.set_emission_curve(&stranger, None).is_err());
+}
+
+#[test]
+fn set_emission_curve_rejects_malformed_curves() {
+    let mut env = TestEnv::new();
+    let (pool, _, _) = curve_pool(&mut env, 1_000_000);
+    env.warp(2 * DAY);
+
+    let invalid = [
+        // Starts in the past
+        schedule(EmissionCurve::Constant { daily: 1 }, 1, 5),
+        // Ends before it starts
+        schedule(EmissionCurve::Constant { daily: 1 }, 3, 3),
+        schedule(EmissionCurve::Halving { start_daily: 1, halving_interval: 0 }, 2, 5),
+        schedule(EmissionCurve::Piecewise { segments: vec![] }, 2, 5),
+        schedule(EmissionCurve::Piecewise { segments: vec![CurveSegment { start_offset: 1, daily: 1 }] }, 2, 5),
+        schedule(
+            EmissionCurve::Piecewise {
+                segments: vec![CurveSegment { start_offset: 0, daily: 1 }, CurveSegment { start_offset: 0, daily: 2 }],
+            },
+            2,
+            5,
+        ),
+    ];
+    for schedule in invalid {
+        assert_eq!(
+            env.set_emission_curve(&pool, schedule.clone()),
+            Err(program_error(ErrorCode::InvalidEmissionCurve)),
+            "{schedule:?}"
+        );
+    }
+}
+
+#[test]
+fn replacing_the_curve_keeps_what_the_old_one_accrued() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = curve_pool(&mut env, 1_000_000);
+    env.set_emission_curve(&pool, schedule(EmissionCurve::Constant { daily: 100_000 }, 0, 10)).unwrap();
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+    env.warp(2 * DAY);
+    env.set_emission_curve(&pool, None).unwrap();
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+
+    // What the removed curve would still have paid stays in the vault
+    assert_eq!(env.token_balance(&reward_vault_address()), 800_000);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, FixedRateTerms, Pool, RewardStrategy};
+
+/// A `FixedRate` pool paying `reward_per_day` per NFT until `end_day`, with `funded` in its
+/// vault and one NFT minted to a staker.
+fn fixed_rate_pool(env: &mut TestEnv, reward_per_day: u64, end_day: u64, funded: u64) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::FixedRate { reward_per_day }, funded + 1_000_000);
+    env.add_reward(&pool, funded, 1).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    env.set_fixed_rate_terms(
+        &pool,
+        FixedRateTerms {
+            end_day,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, nft)
+}
+
+#[test]
+fn fixed_rate_accrues_the_daily_rate_by_the_second() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = fixed_rate_pool(&mut env, 10_000, 10, 1_000_000);
+    env.warp(DAY / 3);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+    // An hour and a bit, across no day boundary
+    env.warp(3_700);
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 10_000 * 3_700 / 86_400);
+
+    // Nothing is left owed beyond the rounding of that one payout
+    let state: Pool = env.fetch(&pool_address());
+    assert!(state.outstanding_rewards <= 1);
+}
+
+#[test]
+fn fixed_rate_reserves_the_rest_of_the_campaign_to_the_second() {
+    let mut env = TestEnv::new();
+    // One token per second until the end of day 1
+    let reward_per_day = DAY as u64;
+    let staked_for = 2 * DAY as u64 - 43_200;
+    let (pool, user, nft) = fixed_rate_pool(&mut env, reward_per_day, 2, staked_for - 1);
+    env.warp(43_200);
+
+    assert_eq!(
+        env.stake(&user, &nft, &spl_token::ID),
+        Err(program_error(ErrorCode::EmissionsNotCovered))
+    );
+    env.add_reward(&pool, 1, 1).unwrap();
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.fixed_rate_liabilities().unwrap(), staked_for);
+
+    // Accrual stops at the end of the campaign, and the vault covers exactly that
+    env.warp(3 * DAY);
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), staked_for);
+    assert_eq!(env.token_balance(&reward_vault_address()), 0);
+}
+
+#[test]
+fn fixed_rate_refuses_stakes_once_the_campaign_ended() {
+    let mut env = TestEnv::new();
+    let (_pool, user, nft) = fixed_rate_pool(&mut env, 10_000, 1, 1_000_000);
+    env.warp(DAY);
+
+    assert_eq!(
+        env.stake(&user, &nft, &spl_token::ID),
+        Err(program_error(ErrorCode::FixedRateCampaignEnded))
+    );
+}
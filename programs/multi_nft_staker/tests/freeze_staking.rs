@@ -0,0 +1,96 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, NftStakeEntry, Pool, RewardStrategy, StakeCustody};
+
+/// A pool paying 100_000 a day for 10 days, with a new user holding one NFT the pool can freeze
+/// and one it cannot.
+fn freeze_pool(env: &mut TestEnv) -> (TestPool, Pubkey, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let freezable = env.create_freezable_nft(&user, &collection);
+    let unfreezable = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, freezable, unfreezable)
+}
+
+/// Moves `user`'s NFT to a new wallet, which only works while the account is not frozen.
+fn transfer_nft(env: &mut TestEnv, user: &Pubkey, nft_mint: &Pubkey) -> TestResult {
+    let recipient = env.wallet(LAMPORTS_PER_SOL);
+    let recipient_nft = env.create_ata(&recipient, nft_mint, &spl_token::ID);
+    let transfer = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &get_associated_token_address_with_program_id(user, nft_mint, &spl_token::ID),
+        &recipient_nft,
+        user,
+        &[],
+        1,
+    );
+    env.process(&transfer.unwrap())
+}
+
+#[test]
+fn an_nft_staked_in_place_stays_frozen_in_the_wallet() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft, _) = freeze_pool(&mut env);
+    let user_nft = get_associated_token_address_with_program_id(&user, &nft, &spl_token::ID);
+
+    env.stake_in_place(&user, &nft).unwrap();
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.custody, StakeCustody::Frozen);
+    assert_eq!(env.token_balance(&user_nft), 1);
+    assert!(env.account(&nft_vault_address(&user, &nft)).is_none());
+    assert!(transfer_nft(&mut env, &user, &nft).is_err());
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 1);
+
+    // Rewards accrue and are claimed as for a vault stake
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+
+    env.warp(DAY);
+    env.unstake_in_place(&pool, &user, &nft).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 300_000);
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 0);
+    transfer_nft(&mut env, &user, &nft).unwrap();
+    assert_eq!(env.token_balance(&user_nft), 0);
+}
+
+#[test]
+fn staking_in_place_needs_the_pool_as_freeze_authority() {
+    let mut env = TestEnv::new();
+    let (_, user, _, unfreezable) = freeze_pool(&mut env);
+
+    assert_eq!(
+        env.stake_in_place(&user, &unfreezable),
+        Err(program_error(ErrorCode::FreezeAuthorityNotPool))
+    );
+}
+
+#[test]
+fn each_custody_is_unstaked_by_its_own_instruction() {
+    let mut env = TestEnv::new();
+    let (pool, user, freezable, unfreezable) = freeze_pool(&mut env);
+    env.stake_in_place(&user, &freezable).unwrap();
+    env.stake(&user, &unfreezable, &spl_token::ID).unwrap();
+    env.warp(DAY);
+
+    // A frozen stake has no vault to unstake from
+    assert!(env.unstake(&pool, &user, &freezable, &spl_token::ID).is_err());
+    assert_eq!(
+        env.unstake_in_place(&pool, &user, &unfreezable),
+        Err(program_error(ErrorCode::WrongCustody))
+    );
+
+    env.unstake(&pool, &user, &unfreezable, &spl_token::ID).unwrap();
+    env.unstake_in_place(&pool, &user, &freezable).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 100_000);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, NftStakeEntry, Pool, RewardStrategy};
+
+#[test]
+fn claims_are_counted_on_the_position_and_the_pool() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 600_000, 6).unwrap();
+    env.add_reward(&pool, 400_000, 4).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_funded, 1_000_000);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!((entry.lifetime_claimed, entry.last_claimed_at), (0, 0));
+
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, Some(50_000)).unwrap();
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!((entry.lifetime_claimed, entry.last_claimed_at), (50_000, env.now()));
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!((state.total_distributed, state.total_claimed), (200_000, 50_000));
+
+    // The reward delegate's share counts towards the position too
+    let delegate = env.wallet(LAMPORTS_PER_SOL);
+    env.create_ata(&delegate, &pool.reward_mint, &spl_token::ID);
+    env.set_reward_delegate(&user, &nft, Some(delegate), 5_000).unwrap();
+    env.warp(DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!((entry.lifetime_claimed, entry.last_claimed_at), (300_000, env.now()));
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!((state.total_distributed, state.total_claimed), (300_000, 300_000));
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 125_000);
+
+    // Tokens sent to the vault outside `add_reward` are not funding, and withdrawing them is
+    // counted on its own
+    env.mint_to(&spl_token::ID, &pool.reward_mint, &reward_vault_address(), &pool.admin, 100_000);
+    env.admin_claim(&pool, 100_000).unwrap();
+    let stats = env.pool_stats(&pool);
+    assert_eq!((stats.total_funded, stats.total_admin_withdrawn), (1_000_000, 100_000));
+    assert_eq!((stats.total_distributed, stats.total_claimed), (300_000, 300_000));
+}
+
+#[test]
+fn stake_seconds_integrate_the_staked_count_over_time() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let first = env.create_metaplex_nft(&user, &collection, true);
+    let second = env.create_metaplex_nft(&user, &collection, true);
+
+    // Nothing staked, nothing counted
+    env.warp(DAY);
+    env.stake(&user, &first, &spl_token::ID).unwrap();
+    assert_eq!(env.fetch::<Pool>(&pool_address()).stake_seconds, 0);
+
+    env.warp(2 * DAY);
+    env.stake(&user, &second, &spl_token::ID).unwrap();
+    assert_eq!(env.fetch::<Pool>(&pool_address()).stake_seconds, 2 * DAY as u64);
+
+    // The stats bring it up to now without writing it
+    env.warp(DAY);
+    assert_eq!(env.pool_stats(&pool).stake_seconds, 4 * DAY as u64);
+    assert_eq!(env.fetch::<Pool>(&pool_address()).stake_seconds, 2 * DAY as u64);
+
+    env.unstake(&pool, &user, &first, &spl_token::ID).unwrap();
+    env.warp(DAY);
+    assert_eq!(env.pool_stats(&pool).stake_seconds, 5 * DAY as u64);
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use anchor_lang::prelude::{borsh, Pubkey};
+use anchor_lang::{system_program, AnchorSerialize, Discriminator};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{AccountMigrated, CollectionKind, ErrorCode, NftStakeEntry, Pool, RewardStrategy};
+
+/// `Pool` before accounts were versioned, in the `lib.rs` layout.
+#[derive(AnchorSerialize)]
+struct LegacyPool {
+    admin: Pubkey,
+    reward_mint: Pubkey,
+    reward_vault: Pubkey,
+    allowed_collections: Vec<Pubkey>,
+    total_staked: u64,
+    current_epoch: u64,
+    last_update_time: i64,
+    epoch_duration: i64,
+    rewards_per_epoch: Vec<u64>,
+    bump: u8,
+    start_staking_timestamp: i64,
+    cumulative_reward_per_nft: u64,
+    last_update_calc_reward_nft_index: u64,
+}
+
+// The legacy space constant counted the discriminator on top of the 8 bytes allocated for it
+const LEGACY_POOL_SIZE: usize =
+    8 + 8 + 32 * 3 + (4 + 32 * Pool::MAX_COLLECTIONS) + 8 * 4 + (4 + 8 * Pool::MAX_EPOCHS) + 1 + 8 * 3;
+
+/// `NftStakeEntry` before accounts were versioned.
+#[derive(AnchorSerialize)]
+struct LegacyNftStakeEntry {
+    user: Pubkey,
+    nft_mint: Pubkey,
+    staked_at: i64,
+    last_claimed_epoch: u64,
+    skipped_reward: u64,
+    bump: u8,
+}
+
+/// Replaces `key`'s data with `data`, keeping its lamports and owner.
+fn overwrite_data(env: &mut TestEnv, key: &Pubkey, data: Vec<u8>) {
+    let mut account = env.account(key).unwrap().clone();
+    account.data = data;
+    env.set_account(*key, account);
+}
+
+fn migrate_pool(env: &mut TestEnv, admin: &Pubkey) -> TestResult {
+    env.run(
+        nft_staking::instruction::MigratePool {},
+        nft_staking::accounts::MigratePool {
+            pool: pool_address(),
+            admin: *admin,
+            system_program: system_program::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &[],
+    )
+}
+
+fn migrate_stake_entry(
+    env: &mut TestEnv,
+    payer: &Pubkey,
+    stake_entry: &Pubkey,
+    nft_mint: &Pubkey,
+) -> TestResult {
+    env.run(
+        nft_staking::instruction::MigrateStakeEntry {},
+        nft_staking::accounts::MigrateStakeEntry {
+            payer: *payer,
+            pool: pool_address(),
+            stake_entry: *stake_entry,
+            nft_metadata_account: metadata_address(nft_mint),
+            system_program: system_program::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &[],
+    )
+}
+
+#[test]
+fn legacy_pool_and_stake_entry_migrate_to_the_current_version_and_unstake() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let stake_entry = stake_entry_address(&user, &nft);
+
+    // Rewrite both accounts as the unversioned program left them
+    let original: Pool = env.fetch(&pool_address());
+    let mut data = Pool::DISCRIMINATOR.to_vec();
+    LegacyPool {
+        admin: original.admin,
+        reward_mint: original.reward_mint,
+        reward_vault: original.reward_vault,
+        allowed_collections: vec![collection],
+        total_staked: original.total_staked,
+        current_epoch: 0,
+        last_update_time: 0,
+        epoch_duration: original.epoch_duration,
+        rewards_per_epoch: original.rewards_per_epoch.clone(),
+        bump: original.bump,
+        start_staking_timestamp: original.start_staking_timestamp,
+        cumulative_reward_per_nft: original.cumulative_reward_per_nft,
+        last_update_calc_reward_nft_index: original.last_update_calc_reward_nft_index,
+    }
+    .serialize(&mut data)
+    .unwrap();
+    data.resize(LEGACY_POOL_SIZE, 0);
+    overwrite_data(&mut env, &pool_address(), data);
+
+    let original_entry: NftStakeEntry = env.fetch(&stake_entry);
+    let mut data = NftStakeEntry::DISCRIMINATOR.to_vec();
+    LegacyNftStakeEntry {
+        user,
+        nft_mint: nft,
+        staked_at: original_entry.staked_at,
+        last_claimed_epoch: 0,
+        skipped_reward: original_entry.skipped_reward,
+        bump: original_entry.bump,
+    }
+    .serialize(&mut data)
+    .unwrap();
+    overwrite_data(&mut env, &stake_entry, data);
+
+    // The pool must be migrated before its entries
+    assert!(migrate_stake_entry(&mut env, &user, &stake_entry, &nft).is_err());
+    migrate_pool(&mut env, &pool.admin).unwrap();
+    let migrated = env.events::<AccountMigrated>();
+    assert_eq!(migrated.len(), 1);
+    assert_eq!((migrated[0].from_version, migrated[0].to_version), (0, Pool::VERSION));
+
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.version, Pool::VERSION);
+    assert_eq!(state.admin, original.admin);
+    assert_eq!(state.reward_vault, original.reward_vault);
+    assert_eq!(state.total_staked, 1);
+    assert_eq!(state.total_shares, 1);
+    assert_eq!(state.rewards_per_epoch, original.rewards_per_epoch);
+    assert_eq!(state.cumulative_reward_per_nft, original.cumulative_reward_per_nft);
+    assert_eq!(state.allowed_collections.len(), 1);
+    assert_eq!(state.allowed_collections[0].collection, collection);
+    assert_eq!(state.allowed_collections[0].staked, 0);
+    assert_eq!(env.account(&pool_address()).unwrap().data.len(), 8 + Pool::ACCOUNT_SPACE);
+
+    migrate_stake_entry(&mut env, &user, &stake_entry, &nft).unwrap();
+    let entry: NftStakeEntry = env.fetch(&stake_entry);
+    assert_eq!(entry.version, NftStakeEntry::VERSION);
+    assert_eq!(entry.user, user);
+    assert_eq!(entry.nft_mint, nft);
+    assert_eq!(entry.staked_at, original_entry.staked_at);
+    assert_eq!(entry.skipped_reward, original_entry.skipped_reward);
+    assert_eq!(entry.collection, collection);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.allowed_collections[0].staked, 1);
+
+    assert_eq!(migrate_pool(&mut env, &pool.admin), Err(program_error(ErrorCode::AlreadyMigrated)));
+    assert_eq!(
+        migrate_stake_entry(&mut env, &user, &stake_entry, &nft),
+        Err(program_error(ErrorCode::AlreadyMigrated))
+    );
+
+    // The migrated position keeps earning and leaves like any other
+    env.warp(2 * DAY);
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    let user_nft = get_associated_token_address_with_program_id(&user, &nft, &spl_token::ID);
+    assert_eq!(env.token_balance(&user_nft), 1);
+    assert!(env.token_balance(&pool.rewards_of(&user)) > 0);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 0);
+    assert_eq!(state.allowed_collections[0].staked, 0);
+}
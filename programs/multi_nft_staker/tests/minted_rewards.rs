@@ -0,0 +1,89 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, Pool, RewardStrategy};
+
+/// A minting pool capped at `emission_cap`, with one NFT of an allowed collection minted to a
+/// new user.
+fn minting_pool(env: &mut TestEnv, emission_cap: u64) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_minting_pool(RewardStrategy::EqualShare, emission_cap);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, nft)
+}
+
+#[test]
+fn claims_and_unstakes_mint_what_the_position_earned() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = minting_pool(&mut env, 1_000_000);
+    env.schedule_emission(&pool, 400_000, 4).unwrap();
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    // Nothing was ever held for the stakers
+    assert_eq!(env.token_balance(&reward_vault_address()), 0);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_minted, 200_000);
+    assert_eq!(state.total_scheduled, 400_000);
+
+    env.warp(2 * DAY);
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 400_000);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_minted, 400_000);
+    assert_eq!(state.outstanding_rewards, 0);
+}
+
+#[test]
+fn schedule_emission_keeps_everything_scheduled_under_the_cap() {
+    let mut env = TestEnv::new();
+    let (pool, _, _) = minting_pool(&mut env, 1_000);
+
+    assert_eq!(
+        env.schedule_emission(&pool, 1_001, 1),
+        Err(program_error(ErrorCode::EmissionCapExceeded))
+    );
+    // Only what lands in the schedule counts: 1_001 over 2 days schedules 1_000
+    env.schedule_emission(&pool, 1_001, 2).unwrap();
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_scheduled, 1_000);
+    assert_eq!(state.rewards_per_epoch, vec![500, 500]);
+
+    assert_eq!(
+        env.schedule_emission(&pool, 1, 1),
+        Err(program_error(ErrorCode::EmissionCapExceeded))
+    );
+}
+
+#[test]
+fn minting_pools_take_no_deposits_and_have_nothing_to_withdraw() {
+    let mut env = TestEnv::new();
+    let (pool, _, _) = minting_pool(&mut env, 1_000);
+
+    assert_eq!(env.add_reward(&pool, 1_000, 1), Err(program_error(ErrorCode::WrongRewardMode)));
+    assert_eq!(env.admin_claim(&pool, 1), Err(program_error(ErrorCode::WrongRewardMode)));
+}
+
+#[test]
+fn initialize_pool_requires_the_pool_to_be_the_mint_authority() {
+    let mut env = TestEnv::new();
+    let admin = env.wallet(10 * LAMPORTS_PER_SOL);
+    let reward_mint = env.create_mint(&spl_token::ID, 6, &admin);
+    let pool = TestPool {
+        admin,
+        reward_mint,
+        reward_token_program: spl_token::ID,
+        reward_mode: nft_staking::RewardMode::Mint,
+    };
+
+    assert_eq!(
+        env.initialize_pool_capped(&pool, 1_000, RewardStrategy::EqualShare),
+        Err(program_error(ErrorCode::MintAuthorityNotPool))
+    );
+}
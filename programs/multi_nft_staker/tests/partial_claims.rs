@@ -0,0 +1,74 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, NftStakeEntry, RewardStrategy};
+
+/// A pool paying 100_000 a day for 10 days, with one NFT staked by a new user two days ago.
+fn two_days_staked(env: &mut TestEnv) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(2 * DAY);
+    (pool, user, nft)
+}
+
+#[test]
+fn a_partial_claim_leaves_the_rest_pending() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = two_days_staked(&mut env);
+
+    env.claim_reward(&pool, &user, &nft, Some(50_000)).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 50_000);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.pending_reward, 150_000);
+
+    assert_eq!(
+        env.claim_reward(&pool, &user, &nft, Some(150_001)),
+        Err(program_error(ErrorCode::ClaimExceedsPendingReward))
+    );
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.pending_reward, 0);
+
+    // Nothing accrues again until the day ends
+    assert_eq!(
+        env.claim_reward(&pool, &user, &nft, None),
+        Err(program_error(ErrorCode::NoRewardsToClaim))
+    );
+}
+
+#[test]
+fn the_owner_can_send_a_claim_to_another_token_account() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = two_days_staked(&mut env);
+    let cold_wallet = env.wallet(LAMPORTS_PER_SOL);
+    let cold_rewards = env.create_ata(&cold_wallet, &pool.reward_mint, &spl_token::ID);
+
+    env.claim_reward_to(&pool, &user, &user, &nft, Some(70_000), Some(cold_rewards)).unwrap();
+    assert_eq!(env.token_balance(&cold_rewards), 70_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 0);
+
+    // The recipient must hold the reward mint
+    let other_mint = env.create_mint(&spl_token::ID, 6, &cold_wallet);
+    let other_rewards = env.create_ata(&cold_wallet, &other_mint, &spl_token::ID);
+    assert!(env
+        .claim_reward_to(&pool, &user, &user, &nft, None, Some(other_rewards))
+        .is_err());
+
+    // Only the owner or its reward delegate may claim
+    let stranger = env.wallet(LAMPORTS_PER_SOL);
+    assert_eq!(
+        env.claim_reward_to(&pool, &stranger, &user, &nft, None, None),
+        Err(program_error(ErrorCode::Unauthorized))
+    );
+
+    env.claim_reward_to(&pool, &user, &user, &nft, None, Some(cold_rewards)).unwrap();
+    assert_eq!(env.token_balance(&cold_rewards), 200_000);
+}
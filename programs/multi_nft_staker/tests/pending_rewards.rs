@@ -0,0 +1,67 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::InstructionError;
+use anchor_lang::AnchorDeserialize;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, NftStakeEntry, PendingRewards, Pool, RewardStrategy};
+
+fn pending_rewards(env: &mut TestEnv, user: &Pubkey, nft_mint: &Pubkey) -> Result<PendingRewards, InstructionError> {
+    env.run(
+        nft_staking::instruction::GetPendingRewards {},
+        nft_staking::accounts::GetPendingRewards {
+            pool: pool_address(),
+            stake_entry: stake_entry_address(user, nft_mint),
+        },
+        &[],
+    )?;
+    let (program_id, data) = env.return_data().unwrap();
+    assert_eq!(*program_id, nft_staking::ID);
+    Ok(PendingRewards::try_from_slice(data).unwrap())
+}
+
+#[test]
+fn get_pending_rewards_reports_what_a_claim_would_pay() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let start: i64 = env.fetch::<Pool>(&pool_address()).start_staking_timestamp;
+
+    // Today's share only becomes claimable once the day is over
+    env.warp(DAY / 2);
+    let pending = pending_rewards(&mut env, &user, &nft).unwrap();
+    assert_eq!((pending.claimable, pending.claimable_next_day), (0, 100_000));
+    assert_eq!(pending.staked_seconds, DAY / 2);
+    assert_eq!((pending.current_day, pending.next_day_starts_at), (0, start + DAY));
+
+    // Someone staking today halves what tomorrow adds
+    env.warp(2 * DAY);
+    let other_user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let other_nft = env.create_metaplex_nft(&other_user, &collection, true);
+    env.stake(&other_user, &other_nft, &spl_token::ID).unwrap();
+    let pending = pending_rewards(&mut env, &user, &nft).unwrap();
+    assert_eq!((pending.claimable, pending.claimable_next_day), (200_000, 250_000));
+    assert_eq!((pending.current_day, pending.next_day_starts_at), (2, start + 3 * DAY));
+
+    // Nothing is written, and the claim pays exactly what was reported
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.pending_reward, 0);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), pending.claimable);
+    let pending = pending_rewards(&mut env, &user, &nft).unwrap();
+    assert_eq!((pending.claimable, pending.claimable_next_day), (0, 50_000));
+}
+
+#[test]
+fn get_pending_rewards_needs_a_staked_position() {
+    let mut env = TestEnv::new();
+    env.setup_pool(RewardStrategy::EqualShare, 0);
+
+    assert!(pending_rewards(&mut env, &Pubkey::new_unique(), &Pubkey::new_unique()).is_err());
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, FixedRateTerms, RewardStrategy, UtilizationCurve, UtilizationPoint};
+
+#[test]
+fn get_pool_stats_reports_today_and_what_is_left() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let counted = Pubkey::new_unique();
+    let uncounted = Pubkey::new_unique();
+    env.add_collection(&pool, &counted, CollectionKind::Metaplex).unwrap();
+    env.add_collection(&pool, &uncounted, CollectionKind::Metaplex).unwrap();
+    env.set_collection_supply(&pool, &counted, 4).unwrap();
+    // A quarter of the counted supply staked emits 40%; the uncounted collection emits in full
+    let curve = UtilizationCurve {
+        points: vec![
+            UtilizationPoint { utilization_bps: 0, emission_bps: 2_000 },
+            UtilizationPoint { utilization_bps: 10_000, emission_bps: 10_000 },
+        ],
+    };
+    env.set_utilization_curve(&pool, Some(curve)).unwrap();
+    for collection in [counted, uncounted] {
+        let user = env.wallet(10 * LAMPORTS_PER_SOL);
+        let nft = env.create_metaplex_nft(&user, &collection, true);
+        env.stake(&user, &nft, &spl_token::ID).unwrap();
+    }
+
+    env.warp(DAY / 2);
+    let stats = env.pool_stats(&pool);
+    assert_eq!(stats.reward_strategy, RewardStrategy::EqualShare);
+    assert_eq!(stats.total_staked, 2);
+    assert_eq!(stats.collections.len(), 2);
+    assert_eq!((stats.collections[0].collection, stats.collections[0].total_supply), (counted, 4));
+    assert_eq!((stats.collections[0].staked, stats.collections[1].staked), (1, 1));
+    assert_eq!((stats.current_day, stats.remaining_funded_days), (0, 10));
+    assert_eq!((stats.todays_budget, stats.todays_emission), (100_000, 70_000));
+    assert_eq!(stats.daily_yield_per_nft, 35_000);
+    assert_eq!((stats.vault_balance, stats.committed_rewards), (1_000_000, 1_000_000));
+    assert_eq!((stats.outstanding_rewards, stats.surplus_rewards), (0, 0));
+    assert_eq!(stats.stake_seconds, DAY as u64);
+
+    // The on-chain accumulator lags until something touches the pool; the stats do not
+    env.warp(2 * DAY);
+    let stats = env.pool_stats(&pool);
+    assert_eq!((stats.current_day, stats.last_updated_day), (2, 0));
+    assert_eq!(stats.remaining_funded_days, 8);
+    assert_eq!((stats.outstanding_rewards, stats.surplus_rewards), (140_000, 60_000));
+    assert_eq!(stats.committed_rewards, 940_000);
+    assert_eq!(stats.total_distributed, 140_000);
+}
+
+#[test]
+fn a_fixed_rate_pool_yields_its_rate_until_the_campaign_ends() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::FixedRate { reward_per_day: 10_000 }, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 1).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    env.set_fixed_rate_terms(
+        &pool,
+        FixedRateTerms {
+            end_day: 3,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // Paid by the second, so the yield does not depend on how many are staked
+    assert_eq!(env.pool_stats(&pool).daily_yield_per_nft, 10_000);
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(2 * DAY);
+    assert_eq!(env.pool_stats(&pool).daily_yield_per_nft, 10_000);
+
+    env.warp(DAY);
+    let stats = env.pool_stats(&pool);
+    assert_eq!((stats.current_day, stats.daily_yield_per_nft), (3, 0));
+    assert_eq!(stats.outstanding_rewards, 30_000);
+}
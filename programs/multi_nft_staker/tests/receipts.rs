@@ -0,0 +1,104 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::spl_token_2022;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, NftStakeEntry, Pool, ReceiptMinted, RewardStrategy};
+
+/// A pool paying 100_000 a day for 10 days, with one NFT of an allowed collection minted to a
+/// new user.
+fn receipt_pool(env: &mut TestEnv) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, nft)
+}
+
+#[test]
+fn the_receipt_holder_claims_and_redeems_the_position() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = receipt_pool(&mut env);
+
+    env.stake_with_receipt(&user, &nft, false).unwrap();
+    let minted = env.events::<ReceiptMinted>();
+    assert_eq!((minted[0].pool, minted[0].user, minted[0].nft_mint), (pool_address(), user, nft));
+    assert_eq!(minted[0].receipt_mint, receipt_mint_address(&nft));
+    assert!(!minted[0].soulbound);
+    assert_eq!(env.token_balance(&TestEnv::receipt_of(&user, &nft, false)), 1);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.receipt_mint, receipt_mint_address(&nft));
+
+    // The receipt stands in for the owner's own instructions
+    env.warp(2 * DAY);
+    assert_eq!(
+        env.claim_reward(&pool, &user, &nft, None),
+        Err(program_error(ErrorCode::PositionHasReceipt))
+    );
+    assert_eq!(
+        env.set_reward_delegate(&user, &nft, Some(Pubkey::new_unique()), 5_000),
+        Err(program_error(ErrorCode::PositionHasReceipt))
+    );
+    env.claim_reward_with_receipt(&pool, &user, &user, &nft, Some(50_000)).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 50_000);
+
+    // Selling the receipt sells the position
+    let buyer = env.wallet(10 * LAMPORTS_PER_SOL);
+    let buyer_receipt = env.create_ata(&buyer, &receipt_mint_address(&nft), &spl_token::ID);
+    let sell = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &TestEnv::receipt_of(&user, &nft, false),
+        &buyer_receipt,
+        &user,
+        &[],
+        1,
+    );
+    env.process(&sell.unwrap()).unwrap();
+    assert_eq!(
+        env.claim_reward_with_receipt(&pool, &user, &user, &nft, None),
+        Err(program_error(ErrorCode::NotReceiptHolder))
+    );
+    env.claim_reward_with_receipt(&pool, &buyer, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&buyer)), 150_000);
+
+    env.warp(DAY);
+    let user_lamports = env.lamports(&user);
+    env.unstake_with_receipt(&pool, &buyer, &user, &nft).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&buyer)), 250_000);
+    let buyer_nft = get_associated_token_address_with_program_id(&buyer, &nft, &spl_token::ID);
+    assert_eq!(env.token_balance(&buyer_nft), 1);
+    assert_eq!(env.token_balance(&buyer_receipt), 0);
+    // The entry's rent goes back to the original staker
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+    assert!(env.lamports(&user) > user_lamports);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 0);
+}
+
+#[test]
+fn a_soulbound_receipt_cannot_change_hands() {
+    let mut env = TestEnv::new();
+    let (_, user, nft) = receipt_pool(&mut env);
+    env.stake_with_receipt(&user, &nft, true).unwrap();
+    let receipt = TestEnv::receipt_of(&user, &nft, true);
+    assert_eq!(env.token_balance(&receipt), 1);
+
+    let buyer = env.wallet(LAMPORTS_PER_SOL);
+    let buyer_receipt = env.create_ata(&buyer, &receipt_mint_address(&nft), &spl_token_2022::ID);
+    let sell = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        &receipt,
+        &receipt_mint_address(&nft),
+        &buyer_receipt,
+        &user,
+        &[],
+        1,
+        0,
+    );
+    assert!(env.process(&sell.unwrap()).is_err());
+    assert_eq!(env.token_balance(&receipt), 1);
+}
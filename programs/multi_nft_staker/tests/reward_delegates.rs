@@ -0,0 +1,149 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, spl_associated_token_account};
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, RewardDelegateSet, RewardIou, RewardStrategy};
+
+/// A pool paying 100_000 a day for 10 days, with one NFT staked by a new user and a reward
+/// delegate holding an account of the reward token.
+fn staked_with_delegate(env: &mut TestEnv) -> (TestPool, Pubkey, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let delegate = env.wallet(LAMPORTS_PER_SOL);
+    env.create_ata(&delegate, &pool.reward_mint, &spl_token::ID);
+    (pool, user, nft, delegate)
+}
+
+/// Unstakes a position with a reward delegate, passing the delegate's accounts and, if
+/// `with_delegate_reward_iou`, its IOU.
+fn unstake_with_delegate(
+    env: &mut TestEnv,
+    pool: &TestPool,
+    user: &Pubkey,
+    nft_mint: &Pubkey,
+    delegate: &Pubkey,
+    with_delegate_reward_iou: bool,
+) -> TestResult {
+    env.run(
+        nft_staking::instruction::Unstake {},
+        nft_staking::accounts::Unstake {
+            user: *user,
+            pool: pool_address(),
+            nft_mint: *nft_mint,
+            stake_entry: stake_entry_address(user, nft_mint),
+            nft_vault: nft_vault_address(user, nft_mint),
+            user_nft_token_account: get_associated_token_address_with_program_id(user, nft_mint, &spl_token::ID),
+            reward_mint: pool.token_reward_mint(),
+            user_reward_token_account: pool.token_rewards_of(user),
+            reward_vault: pool.token_reward_vault(),
+            sol_vault: pool.sol_vault(),
+            delegate_reward_token_account: pool.token_rewards_of(delegate),
+            reward_delegate: None,
+            reward_iou: reward_iou_address(user),
+            delegate_reward_iou: with_delegate_reward_iou.then(|| reward_iou_address(delegate)),
+            stream_reward_iou: None,
+            delegate_stream_reward_iou: None,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            reward_token_program: pool.reward_token_program,
+            associated_token_program: spl_associated_token_account::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &[],
+    )
+}
+
+#[test]
+fn claims_split_every_payout_with_the_reward_delegate() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft, delegate) = staked_with_delegate(&mut env);
+
+    env.set_reward_delegate(&user, &nft, Some(delegate), 2_500).unwrap();
+    let set = env.events::<RewardDelegateSet>();
+    assert_eq!((set[0].pool, set[0].user, set[0].nft_mint), (pool_address(), user, nft));
+    assert_eq!((set[0].previous_reward_delegate, set[0].previous_delegate_share_bps), (Pubkey::default(), 0));
+    assert_eq!((set[0].reward_delegate, set[0].delegate_share_bps), (delegate, 2_500));
+
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 150_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 50_000);
+
+    // The delegate may trigger the claim, but not redirect the owner's share
+    env.warp(DAY);
+    assert_eq!(
+        env.claim_reward_to(&pool, &delegate, &user, &nft, None, Some(pool.rewards_of(&delegate))),
+        Err(program_error(ErrorCode::Unauthorized))
+    );
+    env.claim_reward_to(&pool, &delegate, &user, &nft, None, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 225_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 75_000);
+
+    // Without a delegate, everything goes to the owner again
+    env.set_reward_delegate(&user, &nft, None, 0).unwrap();
+    env.warp(DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 325_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 75_000);
+    assert_eq!(
+        env.claim_reward_to(&pool, &delegate, &user, &nft, None, None),
+        Err(program_error(ErrorCode::Unauthorized))
+    );
+}
+
+#[test]
+fn set_reward_delegate_rejects_a_share_over_the_whole_and_the_default_key() {
+    let mut env = TestEnv::new();
+    let (_, user, nft, delegate) = staked_with_delegate(&mut env);
+
+    assert_eq!(
+        env.set_reward_delegate(&user, &nft, Some(delegate), 10_001),
+        Err(program_error(ErrorCode::InvalidBasisPoints))
+    );
+    assert_eq!(
+        env.set_reward_delegate(&user, &nft, Some(Pubkey::default()), 5_000),
+        Err(program_error(ErrorCode::InvalidRewardDelegate))
+    );
+    // Only the owner sets the delegate
+    assert!(env.set_reward_delegate(&delegate, &nft, Some(delegate), 5_000).is_err());
+}
+
+#[test]
+fn a_short_vault_owes_the_delegate_its_share_on_its_own_iou() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft, delegate) = staked_with_delegate(&mut env);
+    env.set_reward_delegate(&user, &nft, Some(delegate), 5_000).unwrap();
+    env.warp(2 * DAY);
+    let vault_balance = env.token_balance(&reward_vault_address());
+    env.set_token_balance(&reward_vault_address(), 50_000);
+
+    assert_eq!(
+        unstake_with_delegate(&mut env, &pool, &user, &nft, &delegate, false),
+        Err(program_error(ErrorCode::MissingDelegateRewardIou))
+    );
+    unstake_with_delegate(&mut env, &pool, &user, &nft, &delegate, true).unwrap();
+
+    // The 50_000 the vault held is split, and so are the 150_000 it could not pay
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 25_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 25_000);
+    let owed: RewardIou = env.fetch(&reward_iou_address(&user));
+    assert_eq!(owed.amount_owed, 75_000);
+    let delegate_owed: RewardIou = env.fetch(&reward_iou_address(&delegate));
+    assert_eq!((delegate_owed.user, delegate_owed.amount_owed), (delegate, 75_000));
+
+    env.set_token_balance(&reward_vault_address(), vault_balance - 50_000);
+    env.claim_owed(&pool, &delegate).unwrap();
+    env.claim_owed(&pool, &user).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 100_000);
+    assert_eq!(env.token_balance(&pool.rewards_of(&delegate)), 100_000);
+    assert!(env.account(&reward_iou_address(&delegate)).is_none());
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use anchor_spl::token_interface::spl_token_2022::{
+    self, extension::transfer_fee::instruction::initialize_transfer_fee_config,
+    extension::ExtensionType,
+};
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{
+    AdminClaimed, CollectionKind, OwedRewardClaimed, Pool, RewardAdded, RewardClaimed, RewardDeferred, RewardIou, RewardStrategy,
+};
+
+const FEE_BPS: u16 = 250;
+
+#[test]
+fn add_reward_books_what_a_transfer_fee_mint_delivers() {
+    let mut env = TestEnv::new();
+    let admin = env.wallet(10 * LAMPORTS_PER_SOL);
+    let token_program = spl_token_2022::ID;
+    let reward_mint = env.create_mint_with_extensions(
+        &token_program,
+        6,
+        &admin,
+        &[ExtensionType::TransferFeeConfig],
+        |mint| {
+            vec![initialize_transfer_fee_config(&token_program, mint, Some(&admin), Some(&admin), FEE_BPS, u64::MAX)
+                .unwrap()]
+        },
+    );
+    let pool = TestPool {
+        admin,
+        reward_mint,
+        reward_token_program: token_program,
+        reward_mode: nft_staking::RewardMode::Vault,
+    };
+    env.create_ata(&admin, &reward_mint, &token_program);
+    env.mint_to(&token_program, &reward_mint, &pool.admin_rewards(), &admin, 10_000_000);
+    env.initialize_pool(&pool, RewardStrategy::EqualShare).unwrap();
+
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+
+    let received = 1_000_000 - 1_000_000 * FEE_BPS as u64 / 10_000;
+    assert_eq!(env.token_balance(&reward_vault_address()), received);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_funded, received);
+    assert_eq!(state.rewards_per_epoch, vec![received / 10; 10]);
+    let events = env.events::<RewardAdded>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].total_amount, received);
+    assert_eq!(events[0].epochs_funded, 10);
+}
+
+#[test]
+fn add_reward_books_the_full_amount_of_a_plain_mint() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 10_000_000);
+
+    env.add_reward(&pool, 1_000_000, 4).unwrap();
+
+    assert_eq!(env.token_balance(&reward_vault_address()), 1_000_000);
+    assert_eq!(env.token_balance(&pool.admin_rewards()), 9_000_000);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_funded, 1_000_000);
+    assert_eq!(state.rewards_per_epoch, vec![250_000; 4]);
+}
+
+#[test]
+fn admin_claim_leaves_what_stakers_are_owed_and_promised() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 2_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    // Tokens sent to the vault outside `add_reward` are promised to nobody
+    env.mint_to(&spl_token::ID, &pool.reward_mint, &reward_vault_address(), &pool.admin, 300_000);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+
+    // Nothing is staked over day 0, so its reward is left over too
+    env.warp(DAY);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(2 * DAY);
+
+    // Days 1 and 2 are owed to the staker and days 3 to 9 are still to come
+    assert_eq!(
+        env.admin_claim(&pool, 400_001),
+        Err(program_error(nft_staking::ErrorCode::InsufficientVaultBalance))
+    );
+    env.admin_claim(&pool, 400_000).unwrap();
+    assert_eq!(env.token_balance(&pool.admin_rewards()), 1_400_000);
+    assert_eq!(env.token_balance(&reward_vault_address()), 900_000);
+    let claimed = env.events::<AdminClaimed>();
+    assert_eq!((claimed[0].amount, claimed[0].vault_balance), (400_000, 900_000));
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_admin_withdrawn, 400_000);
+
+    // What is left pays the staker in full
+    env.warp(7 * DAY);
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 900_000);
+    assert_eq!(env.token_balance(&reward_vault_address()), 0);
+}
+
+#[test]
+fn unstake_defers_what_a_short_vault_cannot_pay_until_it_is_topped_up() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 2_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(2 * DAY);
+    env.set_token_balance(&reward_vault_address(), 50_000);
+
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+
+    // Days 0 and 1 earned 200_000, of which the vault only held 50_000
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 50_000);
+    assert_eq!(env.token_balance(&reward_vault_address()), 0);
+    let iou: RewardIou = env.fetch(&reward_iou_address(&user));
+    assert_eq!(iou.user, user);
+    assert_eq!(iou.amount_owed, 150_000);
+    assert_eq!(env.events::<RewardClaimed>()[0].amount, 50_000);
+    let deferred = env.events::<RewardDeferred>();
+    assert_eq!(deferred.len(), 1);
+    assert_eq!((deferred[0].amount, deferred[0].total_owed), (150_000, 150_000));
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+
+    assert_eq!(
+        env.claim_owed(&pool, &user),
+        Err(program_error(nft_staking::ErrorCode::InsufficientVaultBalance))
+    );
+
+    env.add_reward(&pool, 100_000, 1).unwrap();
+    env.claim_owed(&pool, &user).unwrap();
+
+    // A partial payout keeps the rest on the IOU
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 150_000);
+    assert_eq!(env.fetch::<RewardIou>(&reward_iou_address(&user)).amount_owed, 50_000);
+    let claimed = env.events::<OwedRewardClaimed>();
+    assert_eq!((claimed[0].amount, claimed[0].remaining_owed), (100_000, 50_000));
+
+    env.add_reward(&pool, 100_000, 1).unwrap();
+    env.claim_owed(&pool, &user).unwrap();
+
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    assert_eq!(env.token_balance(&reward_vault_address()), 50_000);
+    assert!(env.account(&reward_iou_address(&user)).is_none());
+    assert_eq!(
+        env.claim_owed(&pool, &user),
+        Err(program_error(anchor_lang::error::ErrorCode::AccountNotInitialized))
+    );
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, CollectionRate, CollectionWeight, FixedRateTerms, RewardStrategy};
+
+const DAILY_REWARD: u64 = 100_000;
+
+/// Runs the same schedule under `reward_strategy` and returns what each NFT was paid. Both
+/// collections' NFTs are held by their own staker:
+/// - day 0, 00:00: the first collection's NFT is staked
+/// - day 1, 12:00: the second collection's NFT is staked
+/// - day 3, 00:00: the first one is unstaked
+/// - day 4, 00:00: the second one is unstaked
+fn run_schedule(
+    reward_strategy: impl FnOnce(&Pubkey, &Pubkey) -> RewardStrategy,
+    fixed_rate_terms: impl FnOnce(&Pubkey, &Pubkey) -> Option<FixedRateTerms>,
+) -> (u64, u64) {
+    let mut env = TestEnv::new();
+    let (first_collection, second_collection) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let pool = env.setup_pool(reward_strategy(&first_collection, &second_collection), 10 * DAILY_REWARD);
+    env.add_reward(&pool, 10 * DAILY_REWARD, 10).unwrap();
+    env.add_collection(&pool, &first_collection, CollectionKind::Metaplex).unwrap();
+    env.add_collection(&pool, &second_collection, CollectionKind::Metaplex).unwrap();
+    if let Some(terms) = fixed_rate_terms(&first_collection, &second_collection) {
+        env.set_fixed_rate_terms(&pool, terms).unwrap();
+    }
+    let (first_user, second_user) = (env.wallet(10 * LAMPORTS_PER_SOL), env.wallet(10 * LAMPORTS_PER_SOL));
+    let first = env.create_metaplex_nft(&first_user, &first_collection, true);
+    let second = env.create_metaplex_nft(&second_user, &second_collection, true);
+
+    env.stake(&first_user, &first, &spl_token::ID).unwrap();
+    env.warp(DAY + DAY / 2);
+    env.stake(&second_user, &second, &spl_token::ID).unwrap();
+    env.warp(DAY + DAY / 2);
+    env.unstake(&pool, &first_user, &first, &spl_token::ID).unwrap();
+    env.warp(DAY);
+    env.unstake(&pool, &second_user, &second, &spl_token::ID).unwrap();
+
+    (
+        env.token_balance(&pool.rewards_of(&first_user)),
+        env.token_balance(&pool.rewards_of(&second_user)),
+    )
+}
+
+fn no_terms(_: &Pubkey, _: &Pubkey) -> Option<FixedRateTerms> {
+    None
+}
+
+#[test]
+fn equal_share_splits_each_day_by_the_nfts_staked_at_its_end() {
+    let (first, second) = run_schedule(|_, _| RewardStrategy::EqualShare, no_terms);
+    // Day 0 alone, days 1 and 2 halved, day 3 for the second NFT alone
+    assert_eq!(first, DAILY_REWARD + DAILY_REWARD / 2 * 2);
+    assert_eq!(second, DAILY_REWARD / 2 * 2 + DAILY_REWARD);
+}
+
+#[test]
+fn time_weighted_splits_each_second_by_the_nfts_staked_during_it() {
+    let (first, second) = run_schedule(|_, _| RewardStrategy::TimeWeighted, no_terms);
+    // The first NFT has day 1's morning to itself
+    assert_eq!(first, DAILY_REWARD + DAILY_REWARD / 2 + DAILY_REWARD / 4 + DAILY_REWARD / 2);
+    assert_eq!(second, DAILY_REWARD / 4 + DAILY_REWARD / 2 + DAILY_REWARD);
+}
+
+#[test]
+fn weighted_share_splits_each_day_by_collection_weight() {
+    let (first, second) = run_schedule(
+        |first, _| RewardStrategy::WeightedShare {
+            weights: [
+                CollectionWeight {
+                    collection: *first,
+                    weight: 3,
+                },
+                CollectionWeight::default(),
+            ],
+        },
+        no_terms,
+    );
+    // Day 0 by 3 shares, rounding down per share; days 1 and 2 three to one
+    assert_eq!(first, DAILY_REWARD / 3 * 3 + DAILY_REWARD / 4 * 3 * 2);
+    assert_eq!(second, DAILY_REWARD / 4 * 2 + DAILY_REWARD);
+}
+
+#[test]
+fn fixed_rate_pays_each_collection_its_rate_however_many_are_staked() {
+    let (first, second) = run_schedule(
+        |_, _| RewardStrategy::FixedRate { reward_per_day: 10_000 },
+        |_, second| {
+            Some(FixedRateTerms {
+                collection_rates: [
+                    CollectionRate {
+                        collection: *second,
+                        reward_per_day: 5_000,
+                    },
+                    CollectionRate::default(),
+                ],
+                liability_cap: 0,
+                end_day: 10,
+            })
+        },
+    );
+    assert_eq!(first, 3 * 10_000);
+    // Staked from the middle of day 1, so for two and a half days
+    assert_eq!(second, 5 * 5_000 / 2);
+}
@@ -0,0 +1,162 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token::{
+    self,
+    instruction::{freeze_account, thaw_account},
+};
+use common::*;
+use nft_staking::{
+    CollectionKind, ErrorCode, NftStakeEntry, RewardStrategy, RewardStreamAdded, RewardStreamFunded, StreamRewardClaimed, StreamRewardDeferred,
+    StreamRewardIou,
+};
+
+/// A pool paying 100_000 a day for 10 days, with a collection and one user holding an NFT of it.
+fn stream_pool(env: &mut TestEnv) -> (TestPool, Pubkey, Pubkey, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, collection, user, nft)
+}
+
+/// A partner token minted by the admin, who holds `balance` of it to fund streams with.
+fn partner_mint(env: &mut TestEnv, pool: &TestPool, balance: u64) -> Pubkey {
+    let mint = env.create_mint(&spl_token::ID, 6, &pool.admin);
+    let admin_account = env.create_ata(&pool.admin, &mint, &spl_token::ID);
+    env.mint_to(&spl_token::ID, &mint, &admin_account, &pool.admin, balance);
+    mint
+}
+
+#[test]
+fn every_stream_pays_out_alongside_the_pool_reward() {
+    let mut env = TestEnv::new();
+    let (pool, collection, user, nft) = stream_pool(&mut env);
+    let governance = partner_mint(&mut env, &pool, 500_000);
+    let partner = partner_mint(&mut env, &pool, 1_000_000);
+
+    let first = env.add_reward_stream(&pool, &governance).unwrap();
+    let second = env.add_reward_stream(&pool, &partner).unwrap();
+    let added = env.events::<RewardStreamAdded>();
+    assert_eq!((added[0].pool, added[0].reward_stream, added[0].index), (pool_address(), second, 1));
+    assert_eq!(added[0].reward_mint, partner);
+    env.fund_reward_stream(&pool, &first, 500_000, 10).unwrap();
+    env.fund_reward_stream(&pool, &second, 1_000_000, 10).unwrap();
+    let funded = env.events::<RewardStreamFunded>();
+    assert_eq!((funded[0].reward_stream, funded[0].funder), (second, pool.admin));
+    assert_eq!((funded[0].total_amount, funded[0].epochs_funded, funded[0].total_funded), (1_000_000, 10, 1_000_000));
+
+    // Staking checkpoints the entry in every active stream, in slot order
+    assert_eq!(
+        env.stake_with_streams(&user, &nft, &spl_token::ID, &[first]),
+        Err(program_error(ErrorCode::MissingRewardStreamAccounts))
+    );
+    assert_eq!(
+        env.stake_with_streams(&user, &nft, &spl_token::ID, &[second, first]),
+        Err(program_error(ErrorCode::InvalidRewardStreamAccount))
+    );
+    env.stake_with_streams(&user, &nft, &spl_token::ID, &[first, second]).unwrap();
+    let user_governance = env.create_ata(&user, &governance, &spl_token::ID);
+    let user_partner = env.create_ata(&user, &partner, &spl_token::ID);
+
+    env.warp(2 * DAY);
+    assert_eq!(
+        env.claim_reward(&pool, &user, &nft, None),
+        Err(program_error(ErrorCode::MissingRewardStreamAccounts))
+    );
+    let mut stream_accounts = env.stream_payout_accounts(&first, &user);
+    stream_accounts.extend(env.stream_payout_accounts(&second, &user));
+    env.claim_reward_with_streams(&pool, &user, &user, &nft, None, None, &stream_accounts).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    assert_eq!(env.token_balance(&user_governance), 100_000);
+    assert_eq!(env.token_balance(&user_partner), 200_000);
+    let claimed = env.events::<StreamRewardClaimed>();
+    assert_eq!(claimed.len(), 2);
+    assert_eq!((claimed[0].pool, claimed[0].user, claimed[0].nft_mint), (pool_address(), user, nft));
+    assert_eq!((claimed[0].reward_mint, claimed[0].amount), (governance, 100_000));
+    assert_eq!((claimed[1].reward_mint, claimed[1].amount), (partner, 200_000));
+
+    // A later staker only earns each stream from its own stake onwards
+    let late_user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let late_nft = env.create_metaplex_nft(&late_user, &collection, true);
+    env.stake_with_streams(&late_user, &late_nft, &spl_token::ID, &[first, second]).unwrap();
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&late_user, &late_nft));
+    assert_eq!(entry.stream_checkpoints[1].skipped_reward, 200_000);
+
+    env.warp(DAY);
+    env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, false, &stream_accounts).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 250_000);
+    assert_eq!(env.token_balance(&user_governance), 125_000);
+    assert_eq!(env.token_balance(&user_partner), 250_000);
+}
+
+#[test]
+fn a_frozen_stream_payout_waits_on_a_claim_and_is_deferred_on_unstake() {
+    let mut env = TestEnv::new();
+    let (pool, _, user, nft) = stream_pool(&mut env);
+    let partner = partner_mint(&mut env, &pool, 500_000);
+    let reward_stream = env.add_reward_stream(&pool, &partner).unwrap();
+    env.fund_reward_stream(&pool, &reward_stream, 500_000, 10).unwrap();
+    env.stake_with_streams(&user, &nft, &spl_token::ID, &[reward_stream]).unwrap();
+    let user_partner = env.create_ata(&user, &partner, &spl_token::ID);
+    let stream_accounts = env.stream_payout_accounts(&reward_stream, &user);
+    env.warp(2 * DAY);
+
+    // The admin controls the partner mint and freezes the staker's account
+    let freeze = freeze_account(&spl_token::ID, &user_partner, &partner, &pool.admin, &[]);
+    env.process(&freeze.unwrap()).unwrap();
+    env.claim_reward_with_streams(&pool, &user, &user, &nft, None, None, &stream_accounts).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    assert_eq!(env.token_balance(&user_partner), 0);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.stream_checkpoints[0].pending_reward, 100_000);
+
+    let thaw = thaw_account(&spl_token::ID, &user_partner, &partner, &pool.admin, &[]);
+    env.process(&thaw.unwrap()).unwrap();
+    env.claim_reward_with_streams(&pool, &user, &user, &nft, None, None, &stream_accounts).unwrap();
+    assert_eq!(env.token_balance(&user_partner), 100_000);
+
+    // Unstaking cannot wait, so what the stream cannot pay goes on the user's stream IOU
+    env.warp(DAY);
+    let freeze = freeze_account(&spl_token::ID, &user_partner, &partner, &pool.admin, &[]);
+    env.process(&freeze.unwrap()).unwrap();
+    assert_eq!(
+        env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, false, &stream_accounts),
+        Err(program_error(ErrorCode::MissingStreamRewardIou))
+    );
+    env.unstake_with_streams(&pool, &user, &nft, &spl_token::ID, true, &stream_accounts).unwrap();
+    let deferred = env.events::<StreamRewardDeferred>();
+    assert_eq!((deferred[0].reward_stream, deferred[0].amount), (reward_stream, 50_000));
+    let stream_reward_iou: StreamRewardIou = env.fetch(&stream_reward_iou_address(&user));
+    assert_eq!(stream_reward_iou.owed[0].amount, 50_000);
+
+    let thaw = thaw_account(&spl_token::ID, &user_partner, &partner, &pool.admin, &[]);
+    env.process(&thaw.unwrap()).unwrap();
+    env.claim_owed_stream_reward(&user, &reward_stream).unwrap();
+    assert_eq!(env.token_balance(&user_partner), 150_000);
+    assert!(env.account(&stream_reward_iou_address(&user)).is_none());
+}
+
+#[test]
+fn only_the_admin_funds_an_open_ended_stream() {
+    let mut env = TestEnv::new();
+    let (pool, _, _, _) = stream_pool(&mut env);
+    let partner = partner_mint(&mut env, &pool, 500_000);
+    let reward_stream = env.add_reward_stream(&pool, &partner).unwrap();
+
+    let stranger = TestPool { admin: env.wallet(LAMPORTS_PER_SOL), ..pool };
+    assert!(env.add_reward_stream(&stranger, &partner).is_err());
+    assert!(env.fund_reward_stream(&stranger, &reward_stream, 500_000, 10).is_err());
+
+    // Campaigns are funded once, by their sponsor
+    let sponsor = env.sponsor(1_000_000);
+    let campaign = env.create_campaign(&sponsor, 10, 1_000_000).unwrap();
+    let admin_sponsor_rewards = env.create_ata(&pool.admin, &sponsor.reward_mint, &spl_token::ID);
+    env.mint_to(&spl_token::ID, &sponsor.reward_mint, &admin_sponsor_rewards, &sponsor.funder, 1_000);
+    assert_eq!(
+        env.fund_reward_stream(&pool, &campaign, 1_000, 10),
+        Err(program_error(ErrorCode::NotOpenEndedStream))
+    );
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use anchor_lang::prelude::{Pubkey, Rent};
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{AdminClaimed, CollectionKind, ErrorCode, Pool, RewardStrategy};
+
+/// A pool paying `daily` lamports for `days` days, with one NFT of an allowed collection
+/// minted to a new user.
+fn sol_pool(env: &mut TestEnv, daily: u64, days: u64) -> (TestPool, Pubkey, Pubkey) {
+    let pool = env.setup_sol_pool(RewardStrategy::EqualShare);
+    env.add_sol_reward(&pool, daily * days, days).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    (pool, user, nft)
+}
+
+fn rent_exempt_minimum() -> u64 {
+    Rent::default().minimum_balance(0)
+}
+
+#[test]
+fn claims_and_unstakes_pay_lamports_straight_to_the_wallet() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = sol_pool(&mut env, LAMPORTS_PER_SOL, 4);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let staked_balance = env.lamports(&user);
+
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.lamports(&user), staked_balance + 2 * LAMPORTS_PER_SOL);
+    // No token account is involved
+    assert!(env.account(&pool.rewards_of(&user)).is_none());
+    assert_eq!(env.lamports(&sol_vault_address()), rent_exempt_minimum() + 2 * LAMPORTS_PER_SOL);
+
+    env.warp(2 * DAY);
+    let stake_entry_rent = env.lamports(&stake_entry_address(&user, &nft));
+    let nft_vault_rent = env.lamports(&nft_vault_address(&user, &nft));
+    env.unstake(&pool, &user, &nft, &spl_token::ID).unwrap();
+    // The user also gets back the rent of the closed accounts
+    assert_eq!(
+        env.lamports(&user),
+        staked_balance + 4 * LAMPORTS_PER_SOL + stake_entry_rent + nft_vault_rent
+    );
+    // The vault never goes below its rent-exempt minimum
+    assert_eq!(env.lamports(&sol_vault_address()), rent_exempt_minimum());
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_claimed, 4 * LAMPORTS_PER_SOL);
+}
+
+#[test]
+fn the_reward_delegate_is_paid_its_share_in_lamports() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = sol_pool(&mut env, LAMPORTS_PER_SOL, 4);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    let delegate = env.wallet(LAMPORTS_PER_SOL);
+    env.set_reward_delegate(&user, &nft, Some(delegate), 2_500).unwrap();
+    let user_balance = env.lamports(&user);
+
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+
+    assert_eq!(env.lamports(&delegate), LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2);
+    assert_eq!(env.lamports(&user), user_balance + 3 * LAMPORTS_PER_SOL / 2);
+}
+
+#[test]
+fn admin_claim_sol_only_withdraws_lamports_nobody_is_owed_or_promised() {
+    let mut env = TestEnv::new();
+    let (pool, user, nft) = sol_pool(&mut env, LAMPORTS_PER_SOL, 4);
+    // Lamports sent to the vault outside `add_sol_reward` are promised to nobody
+    env.airdrop(&sol_vault_address(), LAMPORTS_PER_SOL);
+
+    // Nothing is staked over day 0, so its reward is left over too
+    env.warp(DAY);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(DAY);
+
+    let admin_balance = env.lamports(&pool.admin);
+    assert_eq!(
+        env.admin_claim_sol(&pool, 2 * LAMPORTS_PER_SOL + 1),
+        Err(program_error(ErrorCode::InsufficientVaultBalance))
+    );
+    env.admin_claim_sol(&pool, 2 * LAMPORTS_PER_SOL).unwrap();
+    assert_eq!(env.lamports(&pool.admin), admin_balance + 2 * LAMPORTS_PER_SOL);
+    let claimed = env.events::<AdminClaimed>();
+    assert_eq!((claimed[0].amount, claimed[0].vault_balance), (2 * LAMPORTS_PER_SOL, 3 * LAMPORTS_PER_SOL));
+
+    // What is left pays the staker in full
+    env.warp(2 * DAY);
+    let user_balance = env.lamports(&user);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.lamports(&user), user_balance + 3 * LAMPORTS_PER_SOL);
+    assert_eq!(env.lamports(&sol_vault_address()), rent_exempt_minimum());
+}
+
+#[test]
+fn admin_claim_sol_is_only_for_pools_paying_in_sol() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+
+    assert_eq!(env.admin_claim_sol(&pool, 1), Err(program_error(ErrorCode::WrongRewardMode)));
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token_interface::spl_token_2022;
+use common::*;
+use nft_staking::{CollectionKind, NftStakeEntry, Pool, RewardStrategy, StakeTransferred};
+
+#[test]
+fn group_member_nft_stakes_and_unstakes_with_an_spl_token_reward_mint() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let group = env.create_token_group();
+    env.add_collection(&pool, &group, CollectionKind::TokenGroup).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_group_member_nft(&user, &group);
+    let user_nft = get_associated_token_address_with_program_id(&user, &nft, &spl_token_2022::ID);
+
+    env.stake(&user, &nft, &spl_token_2022::ID).unwrap();
+
+    assert_eq!(env.token_balance(&user_nft), 0);
+    assert_eq!(env.token_balance(&nft_vault_address(&user, &nft)), 1);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&user, &nft));
+    assert_eq!(entry.collection, group);
+
+    env.warp(2 * DAY);
+    env.unstake(&pool, &user, &nft, &spl_token_2022::ID).unwrap();
+
+    assert_eq!(env.token_balance(&user_nft), 1);
+    assert!(env.account(&nft_vault_address(&user, &nft)).is_none());
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+    let paid = env.token_balance(&pool.rewards_of(&user));
+    assert!(paid > 0);
+    assert_eq!(env.token_balance(&reward_vault_address()), 1_000_000 - paid);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.total_staked, 0);
+}
+
+#[test]
+fn stake_rejects_a_group_member_of_another_group() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let group = env.create_token_group();
+    env.add_collection(&pool, &group, CollectionKind::TokenGroup).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let other_group = env.create_token_group();
+    let nft = env.create_group_member_nft(&user, &other_group);
+
+    // Without Metaplex metadata there is nothing else to prove membership with
+    assert_eq!(
+        env.stake(&user, &nft, &spl_token_2022::ID),
+        Err(program_error(nft_staking::ErrorCode::NotPartOfCollection))
+    );
+}
+
+#[test]
+fn group_member_nft_position_transfers_in_a_pool_paying_spl_token_rewards() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let group = env.create_token_group();
+    env.add_collection(&pool, &group, CollectionKind::TokenGroup).unwrap();
+    let (user, new_owner) = (env.wallet(10 * LAMPORTS_PER_SOL), env.wallet(10 * LAMPORTS_PER_SOL));
+    let nft = env.create_group_member_nft(&user, &group);
+    env.stake(&user, &nft, &spl_token_2022::ID).unwrap();
+    env.warp(2 * DAY);
+
+    env.transfer_stake(&pool, &user, &new_owner, &nft, &spl_token_2022::ID).unwrap();
+
+    // The current owner is paid what the position earned before it moved
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 200_000);
+    assert!(env.account(&stake_entry_address(&user, &nft)).is_none());
+    assert!(env.account(&nft_vault_address(&user, &nft)).is_none());
+    assert_eq!(env.token_balance(&nft_vault_address(&new_owner, &nft)), 1);
+    let entry: NftStakeEntry = env.fetch(&stake_entry_address(&new_owner, &nft));
+    assert_eq!((entry.user, entry.collection), (new_owner, group));
+    let transferred = env.events::<StakeTransferred>();
+    assert_eq!(transferred.len(), 1);
+    assert_eq!(transferred[0].pool, pool_address());
+    assert_eq!((transferred[0].from, transferred[0].to), (user, new_owner));
+
+    env.warp(DAY);
+    env.unstake(&pool, &new_owner, &nft, &spl_token_2022::ID).unwrap();
+
+    let new_owner_nft = get_associated_token_address_with_program_id(&new_owner, &nft, &spl_token_2022::ID);
+    assert_eq!(env.token_balance(&new_owner_nft), 1);
+    assert_eq!(env.token_balance(&pool.rewards_of(&new_owner)), 100_000);
+}
+
+#[test]
+fn stake_rejects_a_group_member_pointer_to_another_account() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let group = env.create_token_group();
+    env.add_collection(&pool, &group, CollectionKind::TokenGroup).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let payer = env.payer;
+    let nft = env.create_group_member_mint(&group, |_| Pubkey::new_unique());
+    let user_nft = env.create_ata(&user, &nft, &spl_token_2022::ID);
+    env.mint_to(&spl_token_2022::ID, &nft, &user_nft, &payer, 1);
+
+    assert_eq!(
+        env.stake(&user, &nft, &spl_token_2022::ID),
+        Err(program_error(nft_staking::ErrorCode::ExternalGroupMember))
+    );
+}
+
+#[test]
+fn stake_rejects_a_group_member_with_more_than_one_token() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let group = env.create_token_group();
+    env.add_collection(&pool, &group, CollectionKind::TokenGroup).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let payer = env.payer;
+    let nft = env.create_group_member_nft(&user, &group);
+    let user_nft = get_associated_token_address_with_program_id(&user, &nft, &spl_token_2022::ID);
+    env.mint_to(&spl_token_2022::ID, &nft, &user_nft, &payer, 1);
+
+    assert_eq!(
+        env.stake(&user, &nft, &spl_token_2022::ID),
+        Err(program_error(nft_staking::ErrorCode::GroupMemberNotAnNft))
+    );
+}
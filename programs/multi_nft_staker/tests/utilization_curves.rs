@@ -0,0 +1,113 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{
+    CollectionKind, CollectionSupplySet, ErrorCode, Pool, RewardStrategy, UtilizationCurve, UtilizationCurveSet, UtilizationPoint,
+};
+
+/// A pool paying 100_000 a day for 10 days with one allowed collection.
+fn utilization_pool(env: &mut TestEnv) -> (TestPool, Pubkey) {
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 1_000_000);
+    env.add_reward(&pool, 1_000_000, 10).unwrap();
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    (pool, collection)
+}
+
+/// 20% of the budget with nothing staked, rising linearly to all of it with the whole supply.
+fn growth_curve() -> UtilizationCurve {
+    UtilizationCurve {
+        points: vec![
+            UtilizationPoint { utilization_bps: 0, emission_bps: 2_000 },
+            UtilizationPoint { utilization_bps: 10_000, emission_bps: 10_000 },
+        ],
+    }
+}
+
+#[test]
+fn the_daily_emission_follows_the_share_of_the_supply_staked() {
+    let mut env = TestEnv::new();
+    let (pool, collection) = utilization_pool(&mut env);
+    env.set_collection_supply(&pool, &collection, 4).unwrap();
+    let set = env.events::<CollectionSupplySet>();
+    assert_eq!((set[0].pool, set[0].collection), (pool_address(), collection));
+    assert_eq!((set[0].old_total_supply, set[0].new_total_supply), (0, 4));
+    env.set_utilization_curve(&pool, Some(growth_curve())).unwrap();
+    let set = env.events::<UtilizationCurveSet>();
+    assert_eq!((set[0].old_curve.clone(), set[0].new_curve.clone()), (None, Some(growth_curve())));
+
+    // A quarter of the supply staked emits 40% of each day's budget
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(2 * DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 80_000);
+    // The rest stays in the vault, accounted as surplus
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.surplus_rewards, 120_000);
+    assert_eq!(env.token_balance(&reward_vault_address()), 920_000);
+
+    // Half of it emits 60%, shared by the two
+    let other_user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let other_nft = env.create_metaplex_nft(&other_user, &collection, true);
+    env.stake(&other_user, &other_nft, &spl_token::ID).unwrap();
+    env.warp(DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 110_000);
+    let state: Pool = env.fetch(&pool_address());
+    assert_eq!(state.surplus_rewards, 160_000);
+
+    // Without the curve, every day pays its full budget again
+    env.set_utilization_curve(&pool, None).unwrap();
+    env.warp(DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 160_000);
+}
+
+#[test]
+fn a_collection_without_a_registered_supply_emits_in_full() {
+    let mut env = TestEnv::new();
+    let (pool, collection) = utilization_pool(&mut env);
+    env.set_utilization_curve(&pool, Some(growth_curve())).unwrap();
+
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.warp(DAY);
+    env.claim_reward(&pool, &user, &nft, None).unwrap();
+    assert_eq!(env.token_balance(&pool.rewards_of(&user)), 100_000);
+}
+
+#[test]
+fn utilization_settings_are_validated() {
+    let mut env = TestEnv::new();
+    let (pool, _) = utilization_pool(&mut env);
+
+    assert_eq!(
+        env.set_collection_supply(&pool, &Pubkey::new_unique(), 4),
+        Err(program_error(ErrorCode::CollectionNotAllowed))
+    );
+    assert_eq!(
+        env.set_utilization_curve(&pool, Some(UtilizationCurve { points: vec![] })),
+        Err(program_error(ErrorCode::InvalidUtilizationCurve))
+    );
+    let mut unsorted = growth_curve();
+    unsorted.points.reverse();
+    assert_eq!(
+        env.set_utilization_curve(&pool, Some(unsorted)),
+        Err(program_error(ErrorCode::InvalidUtilizationCurve))
+    );
+    // A curve may never emit more than the budget
+    let mut over_budget = growth_curve();
+    over_budget.points[1].emission_bps = 10_001;
+    assert_eq!(
+        env.set_utilization_curve(&pool, Some(over_budget)),
+        Err(program_error(ErrorCode::InvalidBasisPoints))
+    );
+
+    let stranger = TestPool { admin: env.wallet(LAMPORTS_PER_SOL), ..pool };
+    assert!(env.set_utilization_curve(&stranger, Some(growth_curve())).is_err());
+}
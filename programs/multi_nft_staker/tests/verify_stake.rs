@@ -0,0 +1,214 @@
+mod common;
+
+use anchor_lang::prelude::{AccountInfo, AccountMeta, ProgramError, Pubkey};
+use anchor_lang::solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    program::set_return_data,
+};
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{CollectionKind, ErrorCode, RewardStrategy, StakeRequirements, StakeVerification};
+use solana_program_test::processor;
+
+const GATED_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// A program gating on staked positions the way an integrator would: it checks the
+/// `StakeRequirements` in its instruction data with `gating::verify_stake` and returns the
+/// verification it read back. Takes the staking program, the pool, the user and then the
+/// user's stake entries.
+fn process_gated_program(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [nft_staking_program, pool, user, stake_entries @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let requirements = StakeRequirements::try_from_slice(data)?;
+    let verification = nft_staking::gating::verify_stake(
+        nft_staking_program.clone(),
+        pool.clone(),
+        user.clone(),
+        stake_entries.to_vec(),
+        requirements,
+    )?;
+    let mut return_data = Vec::new();
+    verification.serialize(&mut return_data)?;
+    set_return_data(&return_data);
+    Ok(())
+}
+
+fn verify_stake(
+    env: &mut TestEnv,
+    user: &Pubkey,
+    stake_entries: &[Pubkey],
+    requirements: StakeRequirements,
+) -> Result<StakeVerification, InstructionError> {
+    let stake_entries: Vec<AccountMeta> = stake_entries
+        .iter()
+        .map(|stake_entry| AccountMeta::new_readonly(*stake_entry, false))
+        .collect();
+    env.run(
+        nft_staking::instruction::VerifyStake { requirements },
+        nft_staking::accounts::VerifyStake {
+            pool: pool_address(),
+            user: *user,
+        },
+        &stake_entries,
+    )?;
+    let (program_id, data) = env.return_data().unwrap();
+    assert_eq!(*program_id, nft_staking::ID);
+    Ok(StakeVerification::try_from_slice(data).unwrap())
+}
+
+fn requirements(min_count: u32) -> StakeRequirements {
+    StakeRequirements {
+        min_count,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn verify_stake_counts_positions_matching_every_filter() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    let other_collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    env.add_collection(&pool, &other_collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let first = env.create_metaplex_nft(&user, &collection, true);
+    let second = env.create_metaplex_nft(&user, &collection, true);
+    let other = env.create_metaplex_nft(&user, &other_collection, true);
+
+    env.stake(&user, &first, &spl_token::ID).unwrap();
+    let first_staked_at = env.now();
+    env.warp(DAY);
+    env.stake(&user, &second, &spl_token::ID).unwrap();
+    env.stake(&user, &other, &spl_token::ID).unwrap();
+    env.warp(DAY);
+    let entries = [first, second, other].map(|nft| stake_entry_address(&user, &nft));
+
+    let verification = verify_stake(&mut env, &user, &entries, requirements(3)).unwrap();
+    assert_eq!(verification.user, user);
+    assert_eq!(verification.matching_count, 3);
+    assert_eq!(verification.oldest_staked_at, Some(first_staked_at));
+
+    let in_collection = StakeRequirements {
+        collection: Some(collection),
+        ..requirements(2)
+    };
+    let verification = verify_stake(&mut env, &user, &entries, in_collection.clone()).unwrap();
+    assert_eq!(verification.matching_count, 2);
+    assert_eq!(
+        verify_stake(&mut env, &user, &entries, StakeRequirements { min_count: 3, ..in_collection }).unwrap_err(),
+        program_error(ErrorCode::StakeRequirementsNotMet)
+    );
+
+    let staked_two_days = StakeRequirements {
+        min_stake_age_seconds: 2 * DAY,
+        ..requirements(1)
+    };
+    let verification = verify_stake(&mut env, &user, &entries, staked_two_days).unwrap();
+    assert_eq!(verification.matching_count, 1);
+    assert_eq!(verification.oldest_staked_at, Some(first_staked_at));
+}
+
+#[test]
+fn verify_stake_excludes_receipt_backed_positions() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let plain = env.create_metaplex_nft(&user, &collection, true);
+    let with_receipt = env.create_metaplex_nft(&user, &collection, true);
+    env.stake(&user, &plain, &spl_token::ID).unwrap();
+    env.stake_with_receipt(&user, &with_receipt, false).unwrap();
+    let entries = [plain, with_receipt].map(|nft| stake_entry_address(&user, &nft));
+
+    assert_eq!(
+        verify_stake(&mut env, &user, &entries, requirements(2)).unwrap_err(),
+        program_error(ErrorCode::StakeRequirementsNotMet)
+    );
+    let verification = verify_stake(&mut env, &user, &entries, requirements(1)).unwrap();
+    assert_eq!(verification.matching_count, 1);
+
+    // A receipt position alone never satisfies the check, even with no minimum
+    assert_eq!(
+        verify_stake(&mut env, &user, &entries[1..], requirements(0)).unwrap_err(),
+        program_error(ErrorCode::StakeRequirementsNotMet)
+    );
+}
+
+#[test]
+fn verify_stake_rejects_another_users_entry_and_duplicates() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let other_user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nft = env.create_metaplex_nft(&user, &collection, true);
+    let other_nft = env.create_metaplex_nft(&other_user, &collection, true);
+    env.stake(&user, &nft, &spl_token::ID).unwrap();
+    env.stake(&other_user, &other_nft, &spl_token::ID).unwrap();
+    let entry = stake_entry_address(&user, &nft);
+    let other_entry = stake_entry_address(&other_user, &other_nft);
+
+    assert_eq!(
+        verify_stake(&mut env, &user, &[entry, other_entry], requirements(1)).unwrap_err(),
+        program_error(ErrorCode::InvalidStakeEntry)
+    );
+    assert_eq!(
+        verify_stake(&mut env, &user, &[entry, entry], requirements(2)).unwrap_err(),
+        program_error(ErrorCode::InvalidStakeEntry)
+    );
+    // The owner of the entries is whoever is passed as `user`
+    assert_eq!(
+        verify_stake(&mut env, &other_user, &[other_entry], requirements(1)).unwrap().user,
+        other_user
+    );
+}
+
+#[test]
+fn gating_verify_stake_returns_the_verification_to_the_calling_program() {
+    let mut env = TestEnv::with_programs(|program_test| {
+        program_test.add_program("gated_program", GATED_PROGRAM_ID, processor!(process_gated_program));
+    });
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let user = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nfts = [(); 2].map(|_| env.create_metaplex_nft(&user, &collection, true));
+    for nft in &nfts {
+        env.stake(&user, nft, &spl_token::ID).unwrap();
+    }
+    let staked_at = env.now();
+    env.warp(DAY);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(nft_staking::ID, false),
+        AccountMeta::new_readonly(pool_address(), false),
+        AccountMeta::new_readonly(user, false),
+    ];
+    accounts.extend(nfts.iter().map(|nft| AccountMeta::new_readonly(stake_entry_address(&user, nft), false)));
+    let gate = |env: &mut TestEnv, min_count| {
+        let mut data = Vec::new();
+        requirements(min_count).serialize(&mut data).unwrap();
+        env.process(&Instruction {
+            program_id: GATED_PROGRAM_ID,
+            accounts: accounts.clone(),
+            data,
+        })
+    };
+
+    gate(&mut env, 2).unwrap();
+    let (program_id, data) = env.return_data().unwrap();
+    assert_eq!(*program_id, GATED_PROGRAM_ID);
+    let verification = StakeVerification::try_from_slice(data).unwrap();
+    assert_eq!(verification.user, user);
+    assert_eq!(verification.matching_count, 2);
+    assert_eq!(verification.oldest_staked_at, Some(staked_at));
+
+    // The caller fails with the staking program's error
+    assert_eq!(gate(&mut env, 3), Err(program_error(ErrorCode::StakeRequirementsNotMet)));
+}
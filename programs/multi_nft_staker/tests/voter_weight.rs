@@ -0,0 +1,130 @@
+mod common;
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::spl_token;
+use common::*;
+use nft_staking::{
+    CollectionKind, RewardStrategy, VoterWeightAction, VoterWeightConfig, VoterWeightRecord, VoterWeightSettings,
+};
+
+// `VoterWeightRecord`'s discriminator in the SPL Governance addin API
+const ADDIN_DISCRIMINATOR: [u8; 8] = [0x2e, 0xf9, 0x9b, 0x4b, 0x99, 0xf8, 0x74, 0x09];
+
+fn voter_weight_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[b"voter_weight_config"], &nft_staking::ID).0
+}
+
+fn voter_weight_record_address(settings: &VoterWeightSettings, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"voter-weight-record",
+            settings.realm.as_ref(),
+            settings.governing_token_mint.as_ref(),
+            owner.as_ref(),
+        ],
+        &nft_staking::ID,
+    )
+    .0
+}
+
+#[test]
+fn voter_weight_record_uses_the_addin_discriminator() {
+    assert_eq!(VoterWeightRecord::DISCRIMINATOR, ADDIN_DISCRIMINATOR);
+    assert_eq!(hash(b"account:VoterWeightRecord").to_bytes()[..8], ADDIN_DISCRIMINATOR);
+}
+
+#[test]
+fn update_voter_weight_record_sets_the_action_and_expires_with_the_slot() {
+    let mut env = TestEnv::new();
+    let pool = env.setup_pool(RewardStrategy::EqualShare, 0);
+    let collection = Pubkey::new_unique();
+    env.add_collection(&pool, &collection, CollectionKind::Metaplex).unwrap();
+    let owner = env.wallet(10 * LAMPORTS_PER_SOL);
+    let nfts = [
+        env.create_metaplex_nft(&owner, &collection, true),
+        env.create_metaplex_nft(&owner, &collection, true),
+    ];
+    for nft in &nfts {
+        env.stake(&owner, nft, &spl_token::ID).unwrap();
+    }
+
+    let settings = VoterWeightSettings {
+        realm: Pubkey::new_unique(),
+        governing_token_mint: Pubkey::new_unique(),
+        weight_per_nft: 10,
+        collection_multipliers: Vec::new(),
+        duration_multipliers: Vec::new(),
+    };
+    env.run(
+        nft_staking::instruction::ConfigureVoterWeight {
+            settings: settings.clone(),
+        },
+        nft_staking::accounts::ConfigureVoterWeight {
+            pool: pool_address(),
+            admin: pool.admin,
+            voter_weight_config: voter_weight_config_address(),
+            system_program: system_program::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &[],
+    )
+    .unwrap();
+    let config: VoterWeightConfig = env.fetch(&voter_weight_config_address());
+    assert_eq!(config.settings.weight_per_nft, 10);
+
+    let record_address = voter_weight_record_address(&settings, &owner);
+    env.run(
+        nft_staking::instruction::CreateVoterWeightRecord {
+            governing_token_owner: owner,
+        },
+        nft_staking::accounts::CreateVoterWeightRecord {
+            voter_weight_config: voter_weight_config_address(),
+            voter_weight_record: record_address,
+            payer: owner,
+            system_program: system_program::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &[],
+    )
+    .unwrap();
+    assert_eq!(env.account(&record_address).unwrap().data[..8], ADDIN_DISCRIMINATOR);
+    let record: VoterWeightRecord = env.fetch(&record_address);
+    assert_eq!(record.voter_weight, 0);
+    assert_eq!(record.voter_weight_expiry, Some(0));
+    assert_eq!(record.weight_action, None);
+
+    let proposal = Pubkey::new_unique();
+    let stake_entries: Vec<_> = nfts
+        .iter()
+        .map(|nft| AccountMeta::new_readonly(stake_entry_address(&owner, nft), false))
+        .collect();
+    env.run(
+        nft_staking::instruction::UpdateVoterWeightRecord {
+            action: VoterWeightAction::CreateProposal,
+            action_target: proposal,
+        },
+        nft_staking::accounts::UpdateVoterWeightRecord {
+            voter_weight_config: voter_weight_config_address(),
+            voter_weight_record: record_address,
+            governing_token_owner: owner,
+            system_program: system_program::ID,
+            event_authority: event_authority_address(),
+            program: nft_staking::ID,
+        },
+        &stake_entries,
+    )
+    .unwrap();
+
+    let record: VoterWeightRecord = env.fetch(&record_address);
+    assert_eq!(record.realm, settings.realm);
+    assert_eq!(record.governing_token_mint, settings.governing_token_mint);
+    assert_eq!(record.governing_token_owner, owner);
+    assert_eq!(record.voter_weight, 20);
+    assert_eq!(record.voter_weight_expiry, Some(env.slot()));
+    assert_eq!(record.weight_action, Some(VoterWeightAction::CreateProposal));
+    assert_eq!(record.weight_action_target, Some(proposal));
+}